@@ -0,0 +1,190 @@
+/**
+ * WebSocket メッセージの型定義（water-controller-relay の DTO から生成）
+ *
+ * 直接編集しないこと。water-controller-relay で次のコマンドを実行して更新する:
+ * cargo run --bin server -- schema --format typescript --output ../water-controller-app/src/lib/types/websocket.generated.ts
+ */
+
+/**
+ * WebSocket プロトコルのバージョン（hello メッセージの protocolVersion）
+ */
+export const PROTOCOL_VERSION = 1
+
+/**
+ * axis-input メッセージ（方向の入力から求めた 2 次元のベクトル）
+ */
+export type AxisInputMessage = {
+  type: 'axis-input'
+  x: number
+  y: number
+}
+
+/**
+ * button-pressed / button-released メッセージ（ボタンのエッジイベント）
+ */
+export type ButtonEdgeMessage = {
+  type: 'button-pressed' | 'button-released'
+}
+
+/**
+ * button-input メッセージ
+ */
+export type ButtonInputMessage = {
+  type: 'button-input'
+  isPushed: boolean
+}
+
+/**
+ * controller-input メッセージ
+ */
+export type ControllerInputMessage = {
+  type: 'controller-input'
+  left: number
+  right: number
+  up: number
+  down: number
+}
+
+/**
+ * diagnostic メッセージ（入力源から受信した行のパースエラー）
+ */
+export type DiagnosticMessage = {
+  type: 'diagnostic'
+  /**
+   * エラーの種類（`field-count` / `parse-int` / `invalid-button-value` / `invalid-bitmask` /
+   * `invalid-raw-value` / `invalid-controller-value` / `invalid-controller-triple-combination`）
+   */
+  kind: string
+  /**
+   * エラーの説明
+   */
+  message: string
+  /**
+   * パースに失敗した行
+   */
+  rawLine: string
+  /**
+   * 直近 1 分間のパースエラーの数（種類を問わない）
+   */
+  ratePerMinute: number
+  /**
+   * 不正な値が入っていたフィールドの位置（0 始まり。特定できない場合は `null`）
+   */
+  fieldIndex?: number | null
+}
+
+/**
+ * 方向
+ */
+export type Direction = 'left' | 'right' | 'up' | 'down'
+
+/**
+ * 認識するジェスチャー
+ */
+export type Gesture =
+  | 'swipe-left'
+  | 'swipe-right'
+  | 'swipe-up'
+  | 'swipe-down'
+  | 'stir-clockwise'
+  | 'stir-counter-clockwise'
+  | 'double-tap'
+  | 'long-press'
+  | 'splash'
+
+/**
+ * gesture メッセージ（入力の時系列から認識したジェスチャー）
+ */
+export type GestureMessage = {
+  type: 'gesture'
+  gesture: Gesture
+}
+
+/**
+ * hello メッセージ（接続直後、`state-snapshot` より前に 1 回だけ送信する）
+ */
+export type HelloMessage = {
+  type: 'hello'
+  /**
+   * WebSocket プロトコルのバージョン
+   */
+  protocolVersion: number
+  /**
+   * リレーサーバのバージョン
+   */
+  serverVersion: string
+  /**
+   * この接続のエンコーディング（`json` / `msgpack` / `cbor`）
+   */
+  encoding: string
+}
+
+/**
+ * lagged メッセージ（受信が追いつかず、ブロードキャストされたメッセージを読み飛ばしたことの通知）
+ */
+export type LaggedMessage = {
+  type: 'lagged'
+  /**
+   * 読み飛ばしたメッセージの数
+   */
+  skipped: number
+}
+
+/**
+ * level-changed メッセージ（方向のレベルのエッジイベント）
+ */
+export type LevelChangedMessage = {
+  type: 'level-changed'
+  direction: Direction
+  from: number
+  to: number
+}
+
+/**
+ * 電極 1 つの静電容量（MPR121 の filtered data と baseline value）
+ */
+export type RawElectrode = {
+  filtered: number
+  baseline: number
+}
+
+/**
+ * raw-sensor メッセージ（電極ごとの静電容量）
+ */
+export type RawSensorMessage = {
+  type: 'raw-sensor'
+  left: [RawElectrode, RawElectrode, RawElectrode]
+  right: [RawElectrode, RawElectrode, RawElectrode]
+  up: [RawElectrode, RawElectrode, RawElectrode]
+  down: [RawElectrode, RawElectrode, RawElectrode]
+}
+
+/**
+ * ハブがブロードキャストするイベント
+ */
+export type RelayEvent =
+  | ButtonInputMessage
+  | ControllerInputMessage
+  | AxisInputMessage
+  | ButtonEdgeMessage
+  | LevelChangedMessage
+  | GestureMessage
+  | RawSensorMessage
+  | DiagnosticMessage
+
+/**
+ * state-snapshot メッセージ（接続直後に送信する現在の入力状態）
+ */
+export type StateSnapshotMessage = {
+  type: 'state-snapshot'
+  isPushed: boolean
+  left: number
+  right: number
+  up: number
+  down: number
+}
+
+/**
+ * リレーサーバからクライアントへのメッセージ（Union 型）
+ */
+export type WsMessage = HelloMessage | StateSnapshotMessage | LaggedMessage | RelayEvent
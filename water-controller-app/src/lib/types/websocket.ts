@@ -1,14 +1,28 @@
 /**
  * WebSocket 関連の型定義
+ *
+ * メッセージの型は water-controller-relay の DTO から生成した websocket.generated.ts を使う
  */
 
-/**
- * ボタン入力メッセージ
- */
-export type ButtonInputMessage = {
-  type: 'button-input'
-  isPushed: boolean
-}
+export type {
+  AxisInputMessage,
+  ButtonEdgeMessage,
+  ButtonInputMessage,
+  ControllerInputMessage,
+  DiagnosticMessage,
+  Direction,
+  Gesture as GestureKind,
+  GestureMessage,
+  HelloMessage,
+  LaggedMessage,
+  LevelChangedMessage,
+  RawElectrode,
+  RawSensorMessage,
+  RelayEvent,
+  StateSnapshotMessage,
+  WsMessage
+} from './websocket.generated'
+export { PROTOCOL_VERSION } from './websocket.generated'
 
 /**
  * コントローラ入力値の Enum
@@ -24,22 +38,6 @@ export enum InputLevel {
   High = 3
 }
 
-/**
- * コントローラ入力メッセージ
- */
-export type ControllerInputMessage = {
-  type: 'controller-input'
-  left: InputLevel
-  right: InputLevel
-  up: InputLevel
-  down: InputLevel
-}
-
-/**
- * WebSocket メッセージ（Union 型）
- */
-export type WsMessage = ButtonInputMessage | ControllerInputMessage
-
 /**
  * WebSocket 接続状態
  */
@@ -1,11 +1,18 @@
-import { useEffect } from 'react'
-import type { WsMessage, ConnectionStatus } from '../../../lib/types/websocket'
+import { useEffect, useState } from 'react'
+import type {
+  WsMessage,
+  ConnectionStatus,
+  DiagnosticMessage
+} from '../../../lib/types/websocket'
 import { InputLevel } from '../../../lib/types/websocket'
 import type { ControllerState } from '../features/controller/types'
 import { useAnimationFps } from '../hooks/useAnimationFps'
 import { useControllerFps } from '../hooks/useControllerFps'
 import { DEBUG_UI_BOTTOM_OFFSET } from '../constants'
 
+/** パースエラーの表示を消すまでの時間（ミリ秒）。リレーサーバは直近 1 分間の頻度を送るので、それに合わせる */
+const DIAGNOSTIC_DISPLAY_MS = 60_000
+
 interface DebugOverlayProps {
   /** WebSocket 接続状態 */
   status: ConnectionStatus
@@ -35,6 +42,12 @@ interface DebugOverlayProps {
   } | null
 }
 
+/** パースエラーの種類・フィールドの位置・頻度を 1 行にまとめる（例: `parse-int #5 (120/min)`） */
+function formatDiagnostic({ kind, fieldIndex, ratePerMinute }: DiagnosticMessage): string {
+  const field = fieldIndex != null ? ` #${fieldIndex}` : ''
+  return `${kind}${field} (${ratePerMinute}/min)`
+}
+
 /**
  * デバッグオーバーレイコンポーネント
  *
@@ -55,6 +68,23 @@ export function DebugOverlay({
 }: DebugOverlayProps): React.JSX.Element | null {
   const animationFps = useAnimationFps()
   const controllerFps = useControllerFps(lastMessage)
+  const [lastDiagnostic, setLastDiagnostic] = useState<DiagnosticMessage | null>(null)
+
+  // 配線の不良などで入力が届かないことを「入力なし」と区別できるよう、最後のパースエラーを表示し続ける
+  useEffect(() => {
+    if (lastMessage?.type === 'diagnostic') {
+      setLastDiagnostic(lastMessage)
+    }
+  }, [lastMessage])
+
+  // 新しいパースエラーが届かなくなったら表示を消す
+  useEffect(() => {
+    if (!lastDiagnostic) {
+      return
+    }
+    const timer = setTimeout(() => setLastDiagnostic(null), DIAGNOSTIC_DISPLAY_MS)
+    return () => clearTimeout(timer)
+  }, [lastDiagnostic])
 
   // currentContent の変更をログ出力
   useEffect(() => {
@@ -148,6 +178,17 @@ export function DebugOverlay({
             <span className="fps-label">Animation FPS:</span>
             <span className="fps-value">{animationFps}</span>
           </div>
+
+          {/* シリアル入力のパースエラー（リレーサーバの diagnostic メッセージ） */}
+          {lastDiagnostic && (
+            <div className="diagnostic-display">
+              <div className="diagnostic-summary">
+                <span className="diagnostic-label">Parse error:</span>
+                <span className="diagnostic-value">{formatDiagnostic(lastDiagnostic)}</span>
+              </div>
+              <div className="diagnostic-line">{lastDiagnostic.rawLine}</div>
+            </div>
+          )}
         </div>
 
         {/* コントローラビジュアライゼーション */}
@@ -30,7 +30,20 @@ export function useWebSocketInput(
       return
     }
 
-    if (lastMessage.type === 'controller-input') {
+    if (lastMessage.type === 'state-snapshot') {
+      // 接続直後の入力状態で初期化
+      setEvent({
+        state: {
+          left: lastMessage.left,
+          right: lastMessage.right,
+          up: lastMessage.up,
+          down: lastMessage.down,
+          button: lastMessage.isPushed
+        },
+        source: InputSource.WebSocket,
+        timestamp: Date.now()
+      })
+    } else if (lastMessage.type === 'controller-input') {
       setEvent({
         state: {
           left: lastMessage.left,
@@ -8,5 +8,9 @@ export const messageHandler = (message: WsMessage) => {
     console.log(
       `[WebSocket] Controller: left=${message.left}, right=${message.right}, up=${message.up}, down=${message.down}`
     )
+  } else if (message.type === 'state-snapshot') {
+    console.log(
+      `[WebSocket] Snapshot: button=${message.isPushed ? 'PUSHED' : 'RELEASED'}, left=${message.left}, right=${message.right}, up=${message.up}, down=${message.down}`
+    )
   }
 }
@@ -1,6 +1,7 @@
 import { useEffect, useRef, useState, useCallback } from 'react'
 import { createLogger } from '../lib/logger'
 import type { WsMessage, ConnectionStatus } from '../../../lib/types/websocket'
+import { PROTOCOL_VERSION } from '../../../lib/types/websocket'
 
 const logger = createLogger('renderer.useWebSocket')
 
@@ -74,6 +75,19 @@ export function useWebSocket(
         try {
           const data = JSON.parse(event.data) as WsMessage
           // logger.debug('WebSocket message received:', data)
+          if (data.type === 'hello' && data.protocolVersion !== PROTOCOL_VERSION) {
+            logger.warn(
+              `Relay protocol version mismatch: server=${data.protocolVersion}, app=${PROTOCOL_VERSION}`
+            )
+          }
+          if (data.type === 'lagged') {
+            logger.warn(`Relay skipped ${data.skipped} messages because the app lagged behind`)
+          }
+          if (data.type === 'diagnostic') {
+            logger.warn(
+              `Relay failed to parse a serial line (${data.kind}, ${data.ratePerMinute}/min): ${data.rawLine}`
+            )
+          }
           setLastMessage(data)
 
           if (onMessage) {
@@ -1,10 +1,28 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use std::{path::PathBuf, time::Duration};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
+use serde::{Deserialize, Serialize};
 use tracing::Level;
 
+use crate::{
+    axis::AxisCurve,
+    config::{ConfigOverrides, ConfigReloader, RelayConfig},
+    hub::{BroadcastMode, LaggedPolicy},
+    serial::{
+        decoder::LineFormat,
+        detect::parse_usb_id,
+        device::{DEFAULT_PROBE_TIMEOUT_MS, DeviceListFormat, ProbeOptions},
+        framing::Framing,
+    },
+    source::{InputSourceConfig, simulator::SimulatorMode},
+    websocket::schema::SchemaFormat,
+};
+
 pub const DEFAULT_SERIAL_PORT: &str = "/dev/cu.usbmodem1101";
 pub const DEFAULT_BAUD_RATE: u32 = 115_200;
 pub const DEFAULT_WS_HOST: &str = "127.0.0.1";
 pub const DEFAULT_WS_PORT: u16 = 8080;
+pub const DEFAULT_SIMULATOR_INTERVAL_MS: u64 = 100;
 
 #[derive(Parser)]
 #[command(author, version, about = "Relay Arduino sensor data to stdout", long_about = None)]
@@ -12,51 +30,219 @@ struct CliArgs {
     #[command(subcommand)]
     command: Option<Command>,
 
-    /// シリアルポートのパス（例: /dev/tty.usbserial-XXXXX）
-    #[arg(short = 'p', long = "port", value_name = "SERIAL_PORT", default_value = DEFAULT_SERIAL_PORT)]
+    /// 設定ファイル（JSON）のパス。実行中にファイルを変更すると自動で読み込み直す
+    #[arg(
+        short = 'c',
+        long = "config",
+        value_name = "CONFIG_FILE",
+        global = true
+    )]
+    config: Option<PathBuf>,
+
+    /// シリアルポートのパス（例: /dev/tty.usbserial-XXXXX）。省略時は設定ファイルの値か /dev/cu.usbmodem1101。
+    /// 指定するとポートの自動検出は行わない
+    #[arg(short = 'p', long = "port", value_name = "SERIAL_PORT")]
     port: Option<String>,
 
-    /// ボーレート。省略時は 115200。
-    #[arg(short = 'b', long = "baud-rate", value_name = "BAUD_RATE", default_value_t = DEFAULT_BAUD_RATE)]
-    baud: u32,
+    /// ボーレート。省略時は設定ファイルの値か 115200。
+    #[arg(short = 'b', long = "baud-rate", value_name = "BAUD_RATE")]
+    baud: Option<u32>,
+
+    /// USB ベンダー ID（16 進数。例: 2341）が一致するシリアルポートを自動で選ぶ
+    #[arg(long = "detect-vid", value_name = "VID", value_parser = parse_usb_id, conflicts_with = "port")]
+    detect_vid: Option<u16>,
+
+    /// USB プロダクト ID（16 進数。例: 0043）が一致するシリアルポートを自動で選ぶ
+    #[arg(long = "detect-pid", value_name = "PID", value_parser = parse_usb_id, conflicts_with = "port")]
+    detect_pid: Option<u16>,
+
+    /// USB シリアル番号が一致するシリアルポートを自動で選ぶ
+    #[arg(
+        long = "detect-serial",
+        value_name = "SERIAL_NUMBER",
+        conflicts_with = "port"
+    )]
+    detect_serial: Option<String>,
+
+    /// ポート名がパターン（例: '/dev/cu.usbmodem*'）に一致するシリアルポートを自動で選ぶ
+    #[arg(long = "detect-name", value_name = "PATTERN", conflicts_with = "port")]
+    detect_name: Option<String>,
+
+    /// ファームウェアの行形式（csv13: 13 フィールドの CSV / proto8: プロトタイプの 8 フィールドの CSV / mpr121: MPR121 のビットマスク）
+    #[arg(long = "line-format", value_enum, global = true)]
+    line_format: Option<LineFormat>,
+
+    /// シリアル通信の伝送方式（text: 改行区切りのテキスト / binary: CRC 付きのバイナリフレーム）
+    #[arg(long = "framing", value_enum, global = true)]
+    framing: Option<Framing>,
+
+    /// トレイを回転して設置した角度（時計回り。0/90/180/270）。設定ファイルの channelMap.rotation を上書きする
+    #[arg(long = "tray-rotation", value_name = "DEGREES", global = true)]
+    tray_rotation: Option<u16>,
+
+    /// WebSocket サーバのホストアドレス。省略時は設定ファイルの値か 127.0.0.1
+    #[arg(long = "ws-host", value_name = "WS_HOST", global = true)]
+    ws_host: Option<String>,
+
+    /// WebSocket サーバのポート番号。省略時は設定ファイルの値か 8080
+    #[arg(long = "ws-port", value_name = "WS_PORT", global = true)]
+    ws_port: Option<u16>,
 
-    /// WebSocket サーバのホストアドレス
-    #[arg(long = "ws-host", value_name = "WS_HOST", default_value = DEFAULT_WS_HOST)]
-    ws_host: String,
+    /// 読み取った生の行をタイムスタンプ付きで記録するキャプチャファイルのパス
+    #[arg(long = "record", value_name = "CAPTURE_FILE", global = true)]
+    record: Option<PathBuf>,
 
-    /// WebSocket サーバのポート番号
-    #[arg(long = "ws-port", value_name = "WS_PORT", default_value_t = DEFAULT_WS_PORT)]
-    ws_port: u16,
+    /// 入力状態メッセージの配信方法（full: 毎回両方配信 / changes: 変化したもののみ配信）
+    #[arg(long = "broadcast-mode", value_enum, global = true)]
+    broadcast_mode: Option<BroadcastMode>,
 
-    /// ログレベル（trace/debug/info/warn/error）。
-    #[arg(short = 'l', long = "log-level", value_enum, default_value_t = LogLevel::Info)]
-    log_level: LogLevel,
+    /// 受信が追いつかないクライアントの扱い（notify: 読み飛ばして lagged メッセージで通知 / disconnect: 切断）
+    #[arg(long = "lagged-policy", value_enum, global = true)]
+    lagged_policy: Option<LaggedPolicy>,
+
+    /// 電極ごとの静電容量を raw-sensor メッセージとして配信する（拡張形式の行を受信した場合のみ）
+    #[arg(long = "raw-sensor", global = true)]
+    raw_sensor: bool,
+
+    /// 入力源から受信した行のパースエラーを diagnostic メッセージとして配信する
+    #[arg(long = "diagnostics", global = true)]
+    diagnostics: bool,
+
+    /// 方向のレベルを切り替えてから次に切り替えられるまでの最小時間（ミリ秒）
+    #[arg(long = "filter-min-hold-ms", value_name = "MS", global = true)]
+    filter_min_hold_ms: Option<u64>,
+
+    /// 多数決フィルタに使う直近のサンプル数（M）
+    #[arg(long = "filter-window", value_name = "M", global = true)]
+    filter_window: Option<usize>,
+
+    /// 多数決フィルタでレベルを採用するのに必要なサンプル数（N）
+    #[arg(long = "filter-majority", value_name = "N", global = true)]
+    filter_majority: Option<usize>,
+
+    /// レベルが上がる場合に必要な連続サンプル数（全方向共通）
+    #[arg(long = "filter-rise", value_name = "SAMPLES", global = true)]
+    filter_rise: Option<u32>,
+
+    /// レベルが下がる場合に必要な連続サンプル数（全方向共通）
+    #[arg(long = "filter-fall", value_name = "SAMPLES", global = true)]
+    filter_fall: Option<u32>,
+
+    /// 方向の入力から求めた 2 次元のベクトルを axis-input メッセージとして配信する
+    #[arg(long = "axis", global = true)]
+    axis: bool,
+
+    /// axis-input のデッドゾーン（ベクトルの長さがこの値以下なら (0, 0)。0 以上 1 未満）
+    #[arg(long = "axis-dead-zone", value_name = "RATIO", global = true)]
+    axis_dead_zone: Option<f64>,
+
+    /// axis-input のベクトルの長さに適用するカーブ
+    #[arg(long = "axis-curve", value_enum, global = true)]
+    axis_curve: Option<AxisCurve>,
+
+    /// 入力の時系列から認識したジェスチャーを gesture メッセージとして配信する
+    #[arg(long = "gesture", global = true)]
+    gesture: bool,
+
+    /// ログレベル（trace/debug/info/warn/error）。省略時は設定ファイルの値か info
+    #[arg(short = 'l', long = "log-level", value_enum, global = true)]
+    log_level: Option<LogLevel>,
 }
 
 #[derive(Subcommand)]
 enum Command {
     /// 接続可能なシリアルポートを列挙する
     #[command(name = "device-list")]
-    DeviceList,
+    DeviceList {
+        /// 出力形式（text/json/table）
+        #[arg(short = 'f', long = "format", value_enum, default_value_t = DeviceListFormat::Text)]
+        format: DeviceListFormat,
+
+        /// 各ポートを短時間開き、ファームウェア形式の行を出力しているかを調べる（ボーレートは --baud-rate、行形式は --line-format の値）
+        #[arg(long = "probe")]
+        probe: bool,
+
+        /// --probe で各ポートから行を受信する時間（ミリ秒）
+        #[arg(long = "probe-timeout-ms", value_name = "MS", default_value_t = DEFAULT_PROBE_TIMEOUT_MS)]
+        probe_timeout_ms: u64,
+    },
+
+    /// シリアルポートの代わりにシミュレータの入力を WebSocket で配信する
+    #[command(name = "simulate")]
+    Simulate {
+        /// シミュレーションモード（pattern/random/keyboard）
+        #[arg(short = 'm', long = "mode", value_enum, default_value_t = SimulatorMode::Pattern)]
+        mode: SimulatorMode,
+
+        /// 行を生成する間隔（ミリ秒）。keyboard モードでは無視される
+        #[arg(short = 'i', long = "interval-ms", value_name = "INTERVAL_MS", default_value_t = DEFAULT_SIMULATOR_INTERVAL_MS)]
+        interval_ms: u64,
+
+        /// random モードの乱数シード。省略時は毎回異なる入力になる
+        #[arg(long = "seed", value_name = "SEED")]
+        seed: Option<u64>,
+    },
+
+    /// `--record` で記録したキャプチャファイルを再生して WebSocket で配信する
+    #[command(name = "replay")]
+    Replay {
+        /// キャプチャファイルのパス
+        #[arg(value_name = "CAPTURE_FILE")]
+        path: PathBuf,
+
+        /// 再生速度の倍率（1.0 で記録時と同じタイミング、2.0 で 2 倍速）
+        #[arg(short = 's', long = "speed", value_name = "SPEED", default_value_t = 1.0, value_parser = parse_speed)]
+        speed: f64,
+
+        /// 末尾まで再生したら先頭に戻って繰り返す
+        #[arg(long = "loop")]
+        looped: bool,
+    },
+
+    /// WebSocket メッセージのスキーマ（JSON Schema / TypeScript の型定義）を出力する
+    #[command(name = "schema")]
+    Schema {
+        /// 出力形式（json-schema/typescript）
+        #[arg(short = 'f', long = "format", value_enum, default_value_t = SchemaFormat::JsonSchema)]
+        format: SchemaFormat,
+
+        /// 出力先のファイル。省略時は標準出力
+        #[arg(short = 'o', long = "output", value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
 }
 
-#[derive(Clone, Copy, Debug, ValueEnum)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Trace,
     Debug,
+    #[default]
     Info,
     Warn,
     Error,
 }
 
+// 起動時に 1 度だけ生成するので、バリアントのサイズの差は問題にならない
+#[allow(clippy::large_enum_variant)]
 pub enum Operation {
     Run {
-        port: String,
-        baud: u32,
-        ws_host: String,
-        ws_port: u16,
+        source: InputSourceConfig,
+        record: Option<PathBuf>,
+        config: RelayConfig,
+        /// `--config` を指定した場合の設定ファイルの監視
+        reloader: Option<ConfigReloader>,
+    },
+    DeviceList {
+        format: DeviceListFormat,
+        /// `--probe` を指定した場合の設定
+        probe: Option<ProbeOptions>,
+    },
+    Schema {
+        format: SchemaFormat,
+        /// 出力先のファイル（`None` の場合は標準出力）
+        output: Option<PathBuf>,
     },
-    DeviceList,
 }
 
 pub struct ParsedArgs {
@@ -66,21 +252,102 @@ pub struct ParsedArgs {
 
 pub fn parse_args() -> ParsedArgs {
     let args = CliArgs::parse();
-    let operation = match args.command {
-        Some(Command::DeviceList) => Operation::DeviceList,
-        None => Operation::Run {
-            port: args
-                .port
-                .expect("clap ensures SERIAL_PORT is provided when no subcommand is used"),
-            baud: args.baud,
-            ws_host: args.ws_host,
-            ws_port: args.ws_port,
+    let overrides = ConfigOverrides {
+        serial_port: args.port,
+        baud_rate: args.baud,
+        detect_vid: args.detect_vid,
+        detect_pid: args.detect_pid,
+        detect_serial: args.detect_serial,
+        detect_name: args.detect_name,
+        line_format: args.line_format,
+        tray_rotation: args.tray_rotation,
+        framing: args.framing,
+        ws_host: args.ws_host,
+        ws_port: args.ws_port,
+        broadcast_mode: args.broadcast_mode,
+        lagged_policy: args.lagged_policy,
+        raw_sensor: args.raw_sensor.then_some(true),
+        diagnostics: args.diagnostics.then_some(true),
+        filter_min_hold_ms: args.filter_min_hold_ms,
+        filter_window: args.filter_window,
+        filter_majority: args.filter_majority,
+        filter_rise: args.filter_rise,
+        filter_fall: args.filter_fall,
+        axis: args.axis.then_some(true),
+        axis_dead_zone: args.axis_dead_zone,
+        axis_curve: args.axis_curve,
+        gesture: args.gesture.then_some(true),
+        log_level: args.log_level,
+    };
+    let config = match overrides.resolve(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => CliArgs::command().error(ErrorKind::InvalidValue, e).exit(),
+    };
+    let log_level = config.logging.level.into();
+
+    let source = match args.command {
+        Some(Command::DeviceList {
+            format,
+            probe,
+            probe_timeout_ms,
+        }) => {
+            let probe = probe.then(|| ProbeOptions {
+                baud_rate: config.serial.baud_rate,
+                line_format: config.serial.line_format,
+                channel_map: config.serial.channel_map,
+                timeout: Duration::from_millis(probe_timeout_ms),
+            });
+            return ParsedArgs {
+                operation: Operation::DeviceList { format, probe },
+                log_level,
+            };
+        }
+        Some(Command::Schema { format, output }) => {
+            return ParsedArgs {
+                operation: Operation::Schema { format, output },
+                log_level,
+            };
+        }
+        Some(Command::Simulate {
+            mode,
+            interval_ms,
+            seed,
+        }) => InputSourceConfig::Simulator {
+            mode,
+            interval: Duration::from_millis(interval_ms),
+            seed,
+        },
+        Some(Command::Replay {
+            path,
+            speed,
+            looped,
+        }) => InputSourceConfig::Replay {
+            path,
+            speed,
+            looped,
         },
+        None => InputSourceConfig::from_serial_config(&config.serial),
     };
 
     ParsedArgs {
-        operation,
-        log_level: args.log_level.into(),
+        operation: Operation::Run {
+            source,
+            record: args.record,
+            config,
+            reloader: args.config.map(|path| ConfigReloader::new(path, overrides)),
+        },
+        log_level,
+    }
+}
+
+fn parse_speed(value: &str) -> Result<f64, String> {
+    let speed = value
+        .parse::<f64>()
+        .map_err(|e| format!("invalid speed '{value}': {e}"))?;
+    if speed.is_finite() && speed > 0.0 {
+        Ok(speed)
+    } else {
+        Err(format!("speed must be a positive number (got {value})"))
     }
 }
 
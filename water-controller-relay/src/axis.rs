@@ -0,0 +1,185 @@
+//! 方向の入力から 2 次元のベクトルへの変換
+//!
+//! 4 方向の離散的なレベル（0〜3）から、`x = right - left`、`y = up - down` の 2 次元のベクトルを求める。
+//! コンテンツごとにレベルを移動量に変換しなくて済むよう、リレーサーバで 1 度だけ計算して `axis-input` として配信する。
+//!
+//! 次の順で計算する:
+//!
+//! 1. 各方向のレベルを 3 で割って 0〜1 にし、`x` / `y` を求める（-1〜1）
+//! 2. 斜めの入力で長さが 1 を超える場合は、向きを保ったまま長さを 1 にする
+//! 3. デッドゾーン: 長さが `deadZone` 以下なら (0, 0) とし、それ以外は `deadZone`〜1 を 0〜1 に伸ばす
+//! 4. カーブ: 伸ばした長さにカーブを適用する（向きは変えない）
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::serial::input::{ControllerInput, ControllerValue};
+
+/// 出力する値の小数点以下の桁数
+const DECIMAL_PLACES: i32 = 4;
+
+/// ベクトルの長さに適用するカーブ
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum AxisCurve {
+    /// そのまま（`t`）
+    #[default]
+    Linear,
+    /// 弱い入力ほど小さくする（`t^2`）
+    Quadratic,
+    /// 弱い入力をさらに小さくする（`t^3`）
+    Cubic,
+    /// 両端をなだらかにする（`3t^2 - 2t^3`）
+    Smoothstep,
+}
+
+impl AxisCurve {
+    /// 0〜1 の値にカーブを適用する
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Self::Linear => t,
+            Self::Quadratic => t * t,
+            Self::Cubic => t * t * t,
+            Self::Smoothstep => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// axis-input メッセージの設定
+///
+/// ## JSON 例
+///
+/// ```json
+/// { "enabled": true, "deadZone": 0.2, "curve": "quadratic" }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct AxisConfig {
+    /// axis-input メッセージを配信するか
+    pub enabled: bool,
+    /// ベクトルの長さがこの値以下なら入力なしとみなす（0 以上 1 未満）
+    pub dead_zone: f64,
+    /// デッドゾーンを除いた長さに適用するカーブ
+    pub curve: AxisCurve,
+}
+
+impl AxisConfig {
+    /// 設定値の整合性を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..1.0).contains(&self.dead_zone) {
+            return Err(format!(
+                "axis deadZone must be at least 0 and less than 1, got {}",
+                self.dead_zone
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 2 次元のベクトル（長さは 1 以下）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AxisVector {
+    /// 右が正
+    pub x: f64,
+    /// 上が正
+    pub y: f64,
+}
+
+impl AxisVector {
+    /// 方向の入力からベクトルを求める
+    ///
+    /// ## 引数
+    ///
+    /// - `controller`: 方向の入力
+    /// - `config`: デッドゾーンとカーブの設定
+    pub fn from_controller(controller: &ControllerInput, config: &AxisConfig) -> Self {
+        let level = |value: &ControllerValue| f64::from(value.level()) / 3.0;
+        let x = level(&controller.right) - level(&controller.left);
+        let y = level(&controller.up) - level(&controller.down);
+
+        let length = x.hypot(y);
+        if length <= config.dead_zone {
+            return Self::default();
+        }
+        let clamped = length.min(1.0);
+        let scaled = (clamped - config.dead_zone) / (1.0 - config.dead_zone);
+        let factor = config.curve.apply(scaled) / length;
+
+        Self {
+            x: round(x * factor),
+            y: round(y * factor),
+        }
+    }
+}
+
+/// 配信するメッセージが長くならないよう、小数点以下を丸める
+fn round(value: f64) -> f64 {
+    let scale = 10f64.powi(DECIMAL_PLACES);
+    (value * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(left: u8, right: u8, up: u8, down: u8) -> ControllerInput {
+        ControllerInput {
+            left: ControllerValue::from_level(left),
+            right: ControllerValue::from_level(right),
+            up: ControllerValue::from_level(up),
+            down: ControllerValue::from_level(down),
+        }
+    }
+
+    #[test]
+    fn test_opposite_directions_cancel_and_diagonal_is_clamped() {
+        // テスト項目: 逆向きの入力は打ち消し合い、斜めの入力は長さ 1 に収まる
+        // given (前提条件):
+        let config = AxisConfig::default();
+
+        // when (操作):
+        let right = AxisVector::from_controller(&controller(1, 3, 0, 0), &config);
+        let cancelled = AxisVector::from_controller(&controller(2, 2, 0, 0), &config);
+        let diagonal = AxisVector::from_controller(&controller(0, 3, 0, 1), &config);
+
+        // then (期待する結果):
+        assert_eq!(right, AxisVector { x: 0.6667, y: 0.0 });
+        assert_eq!(cancelled, AxisVector::default());
+        assert_eq!(
+            diagonal,
+            AxisVector {
+                x: 0.9487,
+                y: -0.3162
+            }
+        );
+    }
+
+    #[test]
+    fn test_dead_zone_and_curve() {
+        // テスト項目: デッドゾーン以下の入力は (0, 0) になり、それ以外は伸ばしてからカーブを適用する
+        // given (前提条件):
+        let config = AxisConfig {
+            enabled: true,
+            dead_zone: 0.4,
+            curve: AxisCurve::Quadratic,
+        };
+
+        // when (操作):
+        let low = AxisVector::from_controller(&controller(0, 0, 1, 0), &config);
+        let middle = AxisVector::from_controller(&controller(0, 0, 2, 0), &config);
+        let high = AxisVector::from_controller(&controller(0, 0, 3, 0), &config);
+
+        // then (期待する結果): Middle は (2/3 - 0.4) / 0.6 = 4/9 を 2 乗する
+        assert_eq!(low, AxisVector::default());
+        assert_eq!(middle, AxisVector { x: 0.0, y: 0.1975 });
+        assert_eq!(high, AxisVector { x: 0.0, y: 1.0 });
+        assert!(
+            AxisConfig {
+                dead_zone: 1.0,
+                ..config
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
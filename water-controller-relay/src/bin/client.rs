@@ -47,6 +47,10 @@ async fn main() -> io::Result<()> {
             Ok(Message::Text(text)) => {
                 info!("Received: {}", text);
             }
+            Ok(Message::Binary(data)) => {
+                // MessagePack / CBOR で接続した場合はバイナリフレームで届く
+                info!("Received binary: {} bytes", data.len());
+            }
             Ok(Message::Close(_)) => {
                 info!("Server closed connection");
                 break;
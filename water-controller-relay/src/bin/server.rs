@@ -5,25 +5,37 @@ use water_controller_relay::{
     args::{Operation, parse_args},
     logger::logger_init,
     relay::run_loop,
-    serial::input::list_serial_devices,
+    serial::device::list_serial_devices,
+    websocket::schema,
 };
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
     let parsed = parse_args();
-    logger_init(parsed.log_level)?;
+    let log_level = logger_init(parsed.log_level)?;
     info!(level = %parsed.log_level, "water-controller-relay starting");
 
     match parsed.operation {
-        Operation::DeviceList => {
-            list_serial_devices()?;
+        Operation::DeviceList { format, probe } => {
+            list_serial_devices(format, probe)?;
+            Ok(())
+        }
+        Operation::Schema { format, output } => {
+            let rendered = schema::render(format);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, rendered)?;
+                    info!(path = %path.display(), "Wrote message schema");
+                }
+                None => print!("{rendered}"),
+            }
             Ok(())
         }
         Operation::Run {
-            port,
-            baud,
-            ws_host,
-            ws_port,
-        } => run_loop(&port, baud, &ws_host, ws_port).await,
+            source,
+            record,
+            config,
+            reloader,
+        } => run_loop(source, record, config, reloader, Some(log_level)).await,
     }
 }
@@ -0,0 +1,550 @@
+//! リレーサーバの設定ファイル
+//!
+//! シリアル通信・WebSocket・入力フィルタ・ログの設定を JSON ファイルから読み込み、
+//! コマンドライン引数で指定した値で上書きする。
+//!
+//! ## JSON 例
+//!
+//! ```json
+//! {
+//!   "serial": { "port": "/dev/cu.usbmodem1101", "baudRate": 115200 },
+//!   "websocket": { "host": "127.0.0.1", "port": 8080, "broadcastMode": "changes" },
+//!   "filter": { "window": 5, "majority": 3 },
+//!   "axis": { "enabled": true, "deadZone": 0.2, "curve": "quadratic" },
+//!   "gesture": { "enabled": true, "longPressMs": 1000 },
+//!   "logging": { "level": "debug" }
+//! }
+//! ```
+//!
+//! 省略した項目は既定値になる。優先順位は「コマンドライン引数 > 設定ファイル > 既定値」。
+//!
+//! `serial.detect` を指定すると、`serial.port` の代わりに USB の VID / PID などが一致するポートを自動で選ぶ。
+//!
+//! ```json
+//! { "serial": { "detect": { "vid": "0x2341", "pid": "0x0043" } } }
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::{
+    args::{DEFAULT_BAUD_RATE, DEFAULT_SERIAL_PORT, DEFAULT_WS_HOST, DEFAULT_WS_PORT, LogLevel},
+    axis::{AxisConfig, AxisCurve},
+    filter::{FilterConfig, Thresholds},
+    gesture::GestureConfig,
+    hub::{BroadcastMode, LaggedPolicy},
+    serial::{channel_map::ChannelMap, decoder::LineFormat, detect::PortMatcher, framing::Framing},
+    websocket::heartbeat::HeartbeatConfig,
+};
+
+pub const DEFAULT_READ_TIMEOUT_MS: u64 = 100;
+pub const DEFAULT_RETRY_INTERVAL_MS: u64 = 100;
+/// シリアルポートからのデータの読み取りが秒間 100 回行われる場合に、ブロードキャストチャネルのサイズを設定する
+pub const DEFAULT_BROADCAST_CHANNEL_SIZE: usize = 100;
+/// ファームウェアは 100ms ごとに行を出力するので、数秒途切れたら配線や接続の異常とみなす
+pub const DEFAULT_READY_TIMEOUT_MS: u64 = 5000;
+/// 設定ファイルの変更を確認する間隔
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// リレーサーバの設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct RelayConfig {
+    pub serial: SerialConfig,
+    pub websocket: WebSocketConfig,
+    pub filter: FilterConfig,
+    pub axis: AxisConfig,
+    pub gesture: GestureConfig,
+    pub logging: LoggingConfig,
+}
+
+/// シリアル通信の設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct SerialConfig {
+    /// シリアルポートのパス
+    pub port: String,
+    /// ボーレート
+    pub baud_rate: u32,
+    /// 読み取りタイムアウト（ミリ秒）
+    pub read_timeout_ms: u64,
+    /// 入力源を開き直すまでの待ち時間（ミリ秒）
+    pub retry_interval_ms: u64,
+    /// ポートの自動検出の条件。指定した場合は `port` より優先し、開き直すたびに検出し直す
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detect: Option<PortMatcher>,
+    /// ファームウェアの行形式
+    pub line_format: LineFormat,
+    /// フィールドと方向の対応
+    pub channel_map: ChannelMap,
+    /// 伝送方式（改行区切りのテキストまたはバイナリフレーム）
+    pub framing: Framing,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_SERIAL_PORT.to_string(),
+            baud_rate: DEFAULT_BAUD_RATE,
+            read_timeout_ms: DEFAULT_READ_TIMEOUT_MS,
+            retry_interval_ms: DEFAULT_RETRY_INTERVAL_MS,
+            detect: None,
+            line_format: LineFormat::default(),
+            channel_map: ChannelMap::default(),
+            framing: Framing::default(),
+        }
+    }
+}
+
+impl SerialConfig {
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout_ms)
+    }
+
+    pub fn retry_interval(&self) -> Duration {
+        Duration::from_millis(self.retry_interval_ms)
+    }
+}
+
+/// WebSocket サーバ（と同じポートの HTTP エンドポイント）の設定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// ホストアドレス
+    pub host: String,
+    /// ポート番号
+    pub port: u16,
+    /// ブロードキャストチャネルのサイズ
+    pub broadcast_channel_size: usize,
+    /// 入力状態メッセージの配信方法
+    pub broadcast_mode: BroadcastMode,
+    /// 受信が追いつかないクライアントの扱い
+    pub lagged_policy: LaggedPolicy,
+    /// 静電容量を含む入力を受信したときに raw-sensor メッセージを配信するか
+    pub raw_sensor: bool,
+    /// 入力源から受信した行のパースに失敗したときに diagnostic メッセージを配信するか
+    pub diagnostics: bool,
+    /// クライアントへの Ping の間隔と Pong を待つ時間
+    pub heartbeat: HeartbeatConfig,
+    /// `/readyz` で準備完了とみなす、最後に行を受信してからの時間の上限（ミリ秒）
+    pub ready_timeout_ms: u64,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_WS_HOST.to_string(),
+            port: DEFAULT_WS_PORT,
+            broadcast_channel_size: DEFAULT_BROADCAST_CHANNEL_SIZE,
+            broadcast_mode: BroadcastMode::default(),
+            lagged_policy: LaggedPolicy::default(),
+            raw_sensor: false,
+            diagnostics: false,
+            heartbeat: HeartbeatConfig::default(),
+            ready_timeout_ms: DEFAULT_READY_TIMEOUT_MS,
+        }
+    }
+}
+
+/// ログの設定
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// ログレベル
+    pub level: LogLevel,
+}
+
+impl RelayConfig {
+    /// 設定ファイルを読み込む
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("failed to read config file {}: {err}", path.display()),
+            )
+        })?;
+        serde_json::from_str(&text).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid config file {}: {err}", path.display()),
+            )
+        })
+    }
+
+    /// 設定値の整合性を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        if self.serial.baud_rate == 0 {
+            return Err("serial.baudRate must be positive".to_string());
+        }
+        // 0 にすると読み取りがタイムアウトし続け、読み取りループが CPU を使い切る
+        if self.serial.read_timeout_ms == 0 {
+            return Err("serial.readTimeoutMs must be positive".to_string());
+        }
+        if self.serial.retry_interval_ms == 0 {
+            return Err("serial.retryIntervalMs must be positive".to_string());
+        }
+        self.serial
+            .channel_map
+            .validate()
+            .map_err(|e| format!("serial.{e}"))?;
+        if self.websocket.broadcast_channel_size == 0 {
+            return Err("websocket.broadcastChannelSize must be positive".to_string());
+        }
+        self.websocket.heartbeat.validate()?;
+        if self.websocket.ready_timeout_ms == 0 {
+            return Err("websocket.readyTimeoutMs must be positive".to_string());
+        }
+        self.filter.validate()?;
+        self.axis.validate()?;
+        self.gesture.validate()
+    }
+}
+
+/// コマンドライン引数で指定された、設定ファイルより優先する値
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    pub serial_port: Option<String>,
+    pub baud_rate: Option<u32>,
+    pub detect_vid: Option<u16>,
+    pub detect_pid: Option<u16>,
+    pub detect_serial: Option<String>,
+    pub detect_name: Option<String>,
+    pub line_format: Option<LineFormat>,
+    pub tray_rotation: Option<u16>,
+    pub framing: Option<Framing>,
+    pub ws_host: Option<String>,
+    pub ws_port: Option<u16>,
+    pub broadcast_mode: Option<BroadcastMode>,
+    pub lagged_policy: Option<LaggedPolicy>,
+    pub raw_sensor: Option<bool>,
+    pub diagnostics: Option<bool>,
+    pub filter_min_hold_ms: Option<u64>,
+    pub filter_window: Option<usize>,
+    pub filter_majority: Option<usize>,
+    pub filter_rise: Option<u32>,
+    pub filter_fall: Option<u32>,
+    pub axis: Option<bool>,
+    pub axis_dead_zone: Option<f64>,
+    pub axis_curve: Option<AxisCurve>,
+    pub gesture: Option<bool>,
+    pub log_level: Option<LogLevel>,
+}
+
+impl ConfigOverrides {
+    /// 指定された値で設定を上書きする
+    ///
+    /// `serial_port` を指定した場合は、設定ファイルのポートの自動検出を無効にする。
+    /// `detect_*` を指定した場合は、設定ファイルの検出条件の該当する項目だけを置き換える。
+    /// `filter_rise` / `filter_fall` はすべての方向に適用する。
+    pub fn apply(&self, config: &mut RelayConfig) {
+        if let Some(port) = &self.serial_port {
+            config.serial.port = port.clone();
+            config.serial.detect = None;
+        }
+        if let Some(baud_rate) = self.baud_rate {
+            config.serial.baud_rate = baud_rate;
+        }
+        if let Some(line_format) = self.line_format {
+            config.serial.line_format = line_format;
+        }
+        if let Some(framing) = self.framing {
+            config.serial.framing = framing;
+        }
+        if let Some(rotation) = self.tray_rotation {
+            config.serial.channel_map.rotation = rotation;
+        }
+        if self.detect_vid.is_some()
+            || self.detect_pid.is_some()
+            || self.detect_serial.is_some()
+            || self.detect_name.is_some()
+        {
+            let detect = config
+                .serial
+                .detect
+                .get_or_insert_with(PortMatcher::default);
+            if let Some(vid) = self.detect_vid {
+                detect.vid = Some(vid);
+            }
+            if let Some(pid) = self.detect_pid {
+                detect.pid = Some(pid);
+            }
+            if let Some(serial) = &self.detect_serial {
+                detect.serial_number = Some(serial.clone());
+            }
+            if let Some(name) = &self.detect_name {
+                detect.name = Some(name.clone());
+            }
+        }
+        if let Some(host) = &self.ws_host {
+            config.websocket.host = host.clone();
+        }
+        if let Some(port) = self.ws_port {
+            config.websocket.port = port;
+        }
+        if let Some(broadcast_mode) = self.broadcast_mode {
+            config.websocket.broadcast_mode = broadcast_mode;
+        }
+        if let Some(lagged_policy) = self.lagged_policy {
+            config.websocket.lagged_policy = lagged_policy;
+        }
+        if let Some(raw_sensor) = self.raw_sensor {
+            config.websocket.raw_sensor = raw_sensor;
+        }
+        if let Some(diagnostics) = self.diagnostics {
+            config.websocket.diagnostics = diagnostics;
+        }
+        if let Some(min_hold_ms) = self.filter_min_hold_ms {
+            config.filter.min_hold_ms = min_hold_ms;
+        }
+        if let Some(window) = self.filter_window {
+            config.filter.window = window;
+        }
+        if let Some(majority) = self.filter_majority {
+            config.filter.majority = majority;
+        }
+        if self.filter_rise.is_some() || self.filter_fall.is_some() {
+            let thresholds = &mut config.filter.thresholds;
+            for direction in [
+                &mut thresholds.left,
+                &mut thresholds.right,
+                &mut thresholds.up,
+                &mut thresholds.down,
+            ] {
+                *direction = Thresholds {
+                    rise: self.filter_rise.unwrap_or(direction.rise),
+                    fall: self.filter_fall.unwrap_or(direction.fall),
+                };
+            }
+        }
+        if let Some(enabled) = self.axis {
+            config.axis.enabled = enabled;
+        }
+        if let Some(dead_zone) = self.axis_dead_zone {
+            config.axis.dead_zone = dead_zone;
+        }
+        if let Some(curve) = self.axis_curve {
+            config.axis.curve = curve;
+        }
+        if let Some(enabled) = self.gesture {
+            config.gesture.enabled = enabled;
+        }
+        if let Some(level) = self.log_level {
+            config.logging.level = level;
+        }
+    }
+
+    /// 設定ファイル（省略時は既定値）を読み込み、上書きと検証を行った設定を返す
+    pub fn resolve(&self, path: Option<&Path>) -> io::Result<RelayConfig> {
+        let mut config = match path {
+            Some(path) => RelayConfig::load(path)?,
+            None => RelayConfig::default(),
+        };
+        self.apply(&mut config);
+        config
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(config)
+    }
+}
+
+/// 設定ファイルのホットリロード
+///
+/// 設定ファイルの更新時刻を定期的に確認し、変更されていれば読み込み直して `watch` チャネルに流す。
+/// 読み込みや検証に失敗した場合は警告を出して、直前の設定を使い続ける。
+pub struct ConfigReloader {
+    pub path: PathBuf,
+    pub overrides: ConfigOverrides,
+}
+
+impl ConfigReloader {
+    pub fn new(path: PathBuf, overrides: ConfigOverrides) -> Self {
+        Self { path, overrides }
+    }
+
+    /// 設定ファイルの監視を開始する
+    ///
+    /// `tx` の購読者がいなくなると終了する。
+    pub async fn watch(self, tx: watch::Sender<RelayConfig>) {
+        info!(path = %self.path.display(), "Watching config file for changes");
+        let mut last_modified = modified_time(&self.path);
+
+        loop {
+            tokio::time::sleep(RELOAD_POLL_INTERVAL).await;
+            if tx.is_closed() {
+                break;
+            }
+
+            let modified = modified_time(&self.path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match self.overrides.resolve(Some(&self.path)) {
+                Ok(config) => {
+                    info!(path = %self.path.display(), "Config file changed, reloading");
+                    tx.send_if_modified(|current| {
+                        if *current == config {
+                            return false;
+                        }
+                        *current = config;
+                        true
+                    });
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to reload config file, keeping previous settings");
+                }
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_config_uses_defaults() {
+        // テスト項目: 一部の項目だけを指定した設定ファイルは、残りが既定値になる
+        // given (前提条件):
+        let json =
+            r#"{"serial": {"port": "/dev/ttyACM0"}, "filter": {"window": 5, "majority": 3}}"#;
+
+        // when (操作):
+        let config: RelayConfig = serde_json::from_str(json).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(config.serial.port, "/dev/ttyACM0");
+        assert_eq!(config.serial.baud_rate, DEFAULT_BAUD_RATE);
+        assert_eq!(config.websocket, WebSocketConfig::default());
+        assert_eq!((config.filter.window, config.filter.majority), (5, 3));
+        assert_eq!(config.filter.min_hold_ms, 0);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        // テスト項目: 未知の項目を含む設定ファイルはエラーになる（綴り間違いに気づけるようにする）
+        // given (前提条件):
+        let json = r#"{"websocket": {"prot": 9000}}"#;
+
+        // when (操作):
+        let result = serde_json::from_str::<RelayConfig>(json);
+
+        // then (期待する結果):
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_overrides_take_precedence_over_file() {
+        // テスト項目: コマンドライン引数で指定した値が設定ファイルの値より優先される
+        // given (前提条件):
+        let mut config: RelayConfig = serde_json::from_str(
+            r#"{"websocket": {"port": 9000, "host": "0.0.0.0"}, "filter": {"thresholds": {"up": {"rise": 2, "fall": 4}}}}"#,
+        )
+        .unwrap();
+        let overrides = ConfigOverrides {
+            ws_port: Some(9100),
+            filter_fall: Some(3),
+            log_level: Some(LogLevel::Debug),
+            ..Default::default()
+        };
+
+        // when (操作):
+        overrides.apply(&mut config);
+
+        // then (期待する結果):
+        assert_eq!(config.websocket.port, 9100);
+        assert_eq!(config.websocket.host, "0.0.0.0");
+        assert_eq!(config.filter.thresholds.up, Thresholds { rise: 2, fall: 3 });
+        assert_eq!(
+            config.filter.thresholds.left,
+            Thresholds { rise: 1, fall: 3 }
+        );
+        assert_eq!(config.logging.level, LogLevel::Debug);
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_filter() {
+        // テスト項目: 上書き後の設定が不正な場合は InvalidInput エラーになる
+        // given (前提条件):
+        let overrides = ConfigOverrides {
+            filter_window: Some(2),
+            filter_majority: Some(3),
+            ..Default::default()
+        };
+
+        // when (操作):
+        let err = overrides.resolve(None).unwrap_err();
+
+        // then (期待する結果):
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_serial_timeouts() {
+        // テスト項目: シリアル通信の読み取りタイムアウトと再接続の間隔が 0 の場合はエラーになる
+        // given (前提条件):
+        let read_timeout: RelayConfig =
+            serde_json::from_str(r#"{"serial": {"readTimeoutMs": 0}}"#).unwrap();
+        let retry_interval: RelayConfig =
+            serde_json::from_str(r#"{"serial": {"retryIntervalMs": 0}}"#).unwrap();
+
+        // when (操作):
+        let results = [read_timeout.validate(), retry_interval.validate()];
+
+        // then (期待する結果):
+        assert_eq!(
+            results,
+            [
+                Err("serial.readTimeoutMs must be positive".to_string()),
+                Err("serial.retryIntervalMs must be positive".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_port_override_disables_detection() {
+        // テスト項目: 検出条件はコマンドライン引数で項目ごとに上書きでき、ポートを指定すると検出は無効になる
+        // given (前提条件):
+        let json = r#"{"serial": {"detect": {"vid": "0x2341", "name": "/dev/cu.*"}}}"#;
+        let config: RelayConfig = serde_json::from_str(json).unwrap();
+        let detect_overrides = ConfigOverrides {
+            detect_pid: Some(0x0043),
+            ..Default::default()
+        };
+        let port_overrides = ConfigOverrides {
+            serial_port: Some("/dev/ttyACM0".to_string()),
+            ..Default::default()
+        };
+
+        // when (操作):
+        let mut detected = config.clone();
+        detect_overrides.apply(&mut detected);
+        let mut fixed = config;
+        port_overrides.apply(&mut fixed);
+
+        // then (期待する結果):
+        assert_eq!(
+            detected.serial.detect,
+            Some(PortMatcher {
+                vid: Some(0x2341),
+                pid: Some(0x0043),
+                serial_number: None,
+                name: Some("/dev/cu.*".to_string()),
+            })
+        );
+        assert_eq!(fixed.serial.detect, None);
+        assert_eq!(fixed.serial.port, "/dev/ttyACM0");
+    }
+}
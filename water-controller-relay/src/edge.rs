@@ -0,0 +1,199 @@
+//! 入力の差分検出
+//!
+//! 直前の入力と比較して、変化した部分とエッジイベント（ボタンの押下・解放、方向のレベル変化）を求める
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::serial::input::{ControllerValue, SerialInput};
+
+/// 方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// すべての方向（エッジイベントはこの順に並ぶ）
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    /// 反対の方向
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    /// 時計回りに 90 度回転した方向
+    pub fn clockwise(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    fn value(self, input: &SerialInput) -> &ControllerValue {
+        let controller = &input.controller;
+        match self {
+            Direction::Left => &controller.left,
+            Direction::Right => &controller.right,
+            Direction::Up => &controller.up,
+            Direction::Down => &controller.down,
+        }
+    }
+}
+
+/// エッジイベント
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEdge {
+    /// ボタンが押された
+    ButtonPressed,
+    /// ボタンが離された
+    ButtonReleased,
+    /// 方向のレベルが変化した
+    LevelChanged {
+        direction: Direction,
+        from: u8,
+        to: u8,
+    },
+}
+
+/// 直前の入力との差分
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputDiff {
+    /// ボタンの状態が変化したか
+    pub button_changed: bool,
+    /// いずれかの方向のレベルが変化したか
+    pub controller_changed: bool,
+    /// 発生したエッジイベント（ボタン → 方向の順）
+    pub edges: Vec<InputEdge>,
+}
+
+impl InputDiff {
+    /// 何も変化していないか
+    pub fn is_empty(&self) -> bool {
+        !self.button_changed && !self.controller_changed
+    }
+}
+
+/// 直前の入力 `previous` と新しい入力 `current` の差分を求める
+pub fn diff_inputs(previous: &SerialInput, current: &SerialInput) -> InputDiff {
+    let mut diff = InputDiff::default();
+
+    if previous.button.is_pushed != current.button.is_pushed {
+        diff.button_changed = true;
+        diff.edges.push(if current.button.is_pushed {
+            InputEdge::ButtonPressed
+        } else {
+            InputEdge::ButtonReleased
+        });
+    }
+
+    for direction in Direction::ALL {
+        let from = direction.value(previous).level();
+        let to = direction.value(current).level();
+        if from != to {
+            diff.controller_changed = true;
+            diff.edges.push(InputEdge::LevelChanged {
+                direction,
+                from,
+                to,
+            });
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::input::{ButtonInput, ControllerInput};
+
+    fn input(is_pushed: bool, left: u8, right: u8, up: u8, down: u8) -> SerialInput {
+        SerialInput {
+            button: ButtonInput { is_pushed },
+            controller: ControllerInput {
+                left: ControllerValue::from_level(left),
+                right: ControllerValue::from_level(right),
+                up: ControllerValue::from_level(up),
+                down: ControllerValue::from_level(down),
+            },
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_of_same_input_is_empty() {
+        // テスト項目: 同じ入力同士の差分は空になる
+        // given (前提条件):
+        let previous = input(true, 1, 0, 3, 0);
+
+        // when (操作):
+        let diff = diff_inputs(&previous, &previous.clone());
+
+        // then (期待する結果):
+        assert!(diff.is_empty());
+        assert!(diff.edges.is_empty());
+    }
+
+    #[test]
+    fn test_diff_detects_button_edges() {
+        // テスト項目: ボタンの押下と解放がそれぞれ ButtonPressed / ButtonReleased になる
+        // given (前提条件):
+        let released = input(false, 0, 0, 0, 0);
+        let pressed = input(true, 0, 0, 0, 0);
+
+        // when (操作):
+        let press = diff_inputs(&released, &pressed);
+        let release = diff_inputs(&pressed, &released);
+
+        // then (期待する結果):
+        assert!(press.button_changed && !press.controller_changed);
+        assert_eq!(press.edges, vec![InputEdge::ButtonPressed]);
+        assert_eq!(release.edges, vec![InputEdge::ButtonReleased]);
+    }
+
+    #[test]
+    fn test_diff_detects_level_changes_per_direction() {
+        // テスト項目: 変化した方向ごとに from/to 付きの LevelChanged が生成される
+        // given (前提条件):
+        let previous = input(false, 0, 2, 1, 0);
+        let current = input(false, 3, 2, 0, 0);
+
+        // when (操作):
+        let diff = diff_inputs(&previous, &current);
+
+        // then (期待する結果):
+        assert!(!diff.button_changed && diff.controller_changed);
+        assert_eq!(
+            diff.edges,
+            vec![
+                InputEdge::LevelChanged {
+                    direction: Direction::Left,
+                    from: 0,
+                    to: 3
+                },
+                InputEdge::LevelChanged {
+                    direction: Direction::Up,
+                    from: 1,
+                    to: 0
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! 入力フィルタ
+//!
+//! 水面の波で MPR121 の電極の値がばたつき、方向のレベルが 1 秒間に何度も Low と Middle を行き来するため、
+//! パースした入力を配信する前に方向ごとに安定化させる。
+//!
+//! 方向ごとに次の順で判定する:
+//!
+//! 1. 多数決: 直近 `window` 個のサンプルのうち `majority` 個以上を占めるレベルを候補とする
+//! 2. ヒステリシス: 候補が出力より高い場合は `rise` 回、低い場合は `fall` 回連続したら出力を切り替える
+//! 3. 最小保持時間: 出力を切り替えてから `minHoldMs` ミリ秒経つまでは次の切り替えを保留する
+//!
+//! 既定値（`window` = `majority` = `rise` = `fall` = 1、`minHoldMs` = 0）ではフィルタは入力をそのまま通す。
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    edge::Direction,
+    serial::input::{ControllerValue, SerialInput},
+};
+
+/// 出力のレベルを切り替えるのに必要な連続サンプル数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// レベルが上がる場合
+    pub rise: u32,
+    /// レベルが下がる場合
+    pub fall: u32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { rise: 1, fall: 1 }
+    }
+}
+
+/// 方向ごとの [`Thresholds`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct DirectionThresholds {
+    pub left: Thresholds,
+    pub right: Thresholds,
+    pub up: Thresholds,
+    pub down: Thresholds,
+}
+
+impl DirectionThresholds {
+    /// すべての方向に同じしきい値を設定する
+    pub fn uniform(thresholds: Thresholds) -> Self {
+        Self {
+            left: thresholds,
+            right: thresholds,
+            up: thresholds,
+            down: thresholds,
+        }
+    }
+
+    fn get(&self, direction: Direction) -> Thresholds {
+        match direction {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+        }
+    }
+}
+
+/// 入力フィルタの設定
+///
+/// ## JSON 例
+///
+/// ```json
+/// {
+///   "minHoldMs": 150,
+///   "window": 5,
+///   "majority": 3,
+///   "thresholds": {
+///     "up": { "rise": 1, "fall": 3 }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// 出力を切り替えてから次に切り替えられるまでの最小時間（ミリ秒）
+    pub min_hold_ms: u64,
+    /// 多数決に使う直近のサンプル数（M）
+    pub window: usize,
+    /// 候補とするのに必要なサンプル数（N）
+    pub majority: usize,
+    /// 方向ごとのしきい値
+    pub thresholds: DirectionThresholds,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            min_hold_ms: 0,
+            window: 1,
+            majority: 1,
+            thresholds: DirectionThresholds::default(),
+        }
+    }
+}
+
+impl FilterConfig {
+    /// 設定値の整合性を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window == 0 {
+            return Err("filter window must be at least 1".to_string());
+        }
+        if self.majority == 0 || self.majority > self.window {
+            return Err(format!(
+                "filter majority must be between 1 and window ({}), got {}",
+                self.window, self.majority
+            ));
+        }
+        for direction in Direction::ALL {
+            let thresholds = self.thresholds.get(direction);
+            if thresholds.rise == 0 || thresholds.fall == 0 {
+                return Err(format!(
+                    "filter rise/fall thresholds for {direction:?} must be at least 1"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn min_hold(&self) -> Duration {
+        Duration::from_millis(self.min_hold_ms)
+    }
+}
+
+/// 1 方向分のフィルタの状態
+#[derive(Debug, Default)]
+struct ChannelFilter {
+    /// 直近のサンプル（古い順）
+    history: VecDeque<u8>,
+    /// 現在の出力レベル
+    output: u8,
+    /// 出力と異なる候補と、その候補が連続した回数
+    pending: Option<(u8, u32)>,
+    /// 最後に出力を切り替えた時刻
+    changed_at: Option<Instant>,
+}
+
+impl ChannelFilter {
+    fn update(
+        &mut self,
+        level: u8,
+        now: Instant,
+        config: &FilterConfig,
+        thresholds: Thresholds,
+    ) -> u8 {
+        self.history.push_back(level);
+        while self.history.len() > config.window {
+            self.history.pop_front();
+        }
+
+        let candidate = self.majority_level(config.majority);
+        if candidate == self.output {
+            self.pending = None;
+            return self.output;
+        }
+
+        let count = match self.pending {
+            Some((pending, count)) if pending == candidate => count + 1,
+            _ => 1,
+        };
+        self.pending = Some((candidate, count));
+
+        let required = if candidate > self.output {
+            thresholds.rise
+        } else {
+            thresholds.fall
+        };
+        if count < required {
+            return self.output;
+        }
+        if let Some(changed_at) = self.changed_at
+            && now.saturating_duration_since(changed_at) < config.min_hold()
+        {
+            return self.output;
+        }
+
+        self.output = candidate;
+        self.pending = None;
+        self.changed_at = Some(now);
+        self.output
+    }
+
+    /// `majority` 個以上を占めるレベルを返す（該当がなければ現在の出力）
+    ///
+    /// 複数のレベルが該当する場合は最も多いものを選び、同数なら現在の出力を優先する。
+    fn majority_level(&self, majority: usize) -> u8 {
+        let mut counts = [0usize; 4];
+        for level in &self.history {
+            counts[usize::from(*level).min(3)] += 1;
+        }
+
+        let mut best = self.output;
+        let mut best_count = 0;
+        for (level, count) in (0u8..).zip(counts) {
+            if count < majority {
+                continue;
+            }
+            if count > best_count || (count == best_count && level == self.output) {
+                best = level;
+                best_count = count;
+            }
+        }
+        best
+    }
+}
+
+/// 方向のレベルを安定化させる入力フィルタ
+#[derive(Debug, Default)]
+pub struct InputFilter {
+    config: FilterConfig,
+    channels: [ChannelFilter; 4],
+}
+
+impl InputFilter {
+    pub fn new(config: FilterConfig) -> Self {
+        Self {
+            config,
+            channels: Default::default(),
+        }
+    }
+
+    /// 現在の設定を返す
+    pub fn config(&self) -> &FilterConfig {
+        &self.config
+    }
+
+    /// 設定を変更する
+    ///
+    /// 現在の出力レベルは維持し、サンプルの履歴と保留中の候補は破棄する。
+    pub fn set_config(&mut self, config: FilterConfig) {
+        self.config = config;
+        for channel in &mut self.channels {
+            channel.history.clear();
+            channel.pending = None;
+        }
+    }
+
+    /// 入力を 1 サンプル分フィルタに通し、安定化した入力を返す
+    ///
+    /// ボタンはフィルタしない。
+    pub fn apply(&mut self, input: &SerialInput, now: Instant) -> SerialInput {
+        let mut filtered = input.clone();
+        for (channel, direction) in self.channels.iter_mut().zip(Direction::ALL) {
+            let value = match direction {
+                Direction::Left => &mut filtered.controller.left,
+                Direction::Right => &mut filtered.controller.right,
+                Direction::Up => &mut filtered.controller.up,
+                Direction::Down => &mut filtered.controller.down,
+            };
+            let level = channel.update(
+                value.level(),
+                now,
+                &self.config,
+                self.config.thresholds.get(direction),
+            );
+            *value = ControllerValue::from_level(level);
+        }
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::input::{ButtonInput, ControllerInput};
+
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn up_input(level: u8) -> SerialInput {
+        SerialInput {
+            button: ButtonInput { is_pushed: false },
+            controller: ControllerInput {
+                left: ControllerValue::from_level(0),
+                right: ControllerValue::from_level(0),
+                up: ControllerValue::from_level(level),
+                down: ControllerValue::from_level(0),
+            },
+            raw: None,
+        }
+    }
+
+    /// 上方向のレベルの列を一定間隔のサンプルとしてフィルタに通し、出力された上方向のレベルの列を返す
+    fn run_up(filter: &mut InputFilter, levels: &[u8]) -> Vec<u8> {
+        let start = Instant::now();
+        (0u32..)
+            .zip(levels)
+            .map(|(idx, level)| {
+                filter
+                    .apply(&up_input(*level), start + SAMPLE_INTERVAL * idx)
+                    .controller
+                    .up
+                    .level()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_default_filter_passes_input_through() {
+        // テスト項目: 既定の設定ではフィルタは入力をそのまま通す
+        // given (前提条件):
+        let mut filter = InputFilter::new(FilterConfig::default());
+        let noisy = [0, 1, 2, 1, 2, 3, 0];
+
+        // when (操作):
+        let output = run_up(&mut filter, &noisy);
+
+        // then (期待する結果):
+        assert_eq!(output, noisy);
+    }
+
+    #[test]
+    fn test_majority_suppresses_isolated_flicker() {
+        // テスト項目: 3-of-5 の多数決で、Low と Middle の間の単発のばたつきを除去する
+        // given (前提条件):
+        let mut filter = InputFilter::new(FilterConfig {
+            window: 5,
+            majority: 3,
+            ..Default::default()
+        });
+        let noisy = [1, 1, 1, 2, 1, 1, 2, 1, 2, 2, 2, 2];
+
+        // when (操作):
+        let output = run_up(&mut filter, &noisy);
+
+        // then (期待する結果):
+        // 最初の Low は 3 サンプル目でそろい、Middle は 3 個そろった 10 サンプル目で切り替わる
+        assert_eq!(output, [0, 0, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_rise_and_fall_thresholds_are_applied_separately() {
+        // テスト項目: 上昇は 1 サンプルで、下降は 3 サンプル連続で切り替える
+        // given (前提条件):
+        let mut filter = InputFilter::new(FilterConfig {
+            thresholds: DirectionThresholds::uniform(Thresholds { rise: 1, fall: 3 }),
+            ..Default::default()
+        });
+        let noisy = [2, 1, 2, 1, 1, 1, 1, 3];
+
+        // when (操作):
+        let output = run_up(&mut filter, &noisy);
+
+        // then (期待する結果):
+        assert_eq!(output, [2, 2, 2, 2, 2, 1, 1, 3]);
+    }
+
+    #[test]
+    fn test_thresholds_are_per_direction() {
+        // テスト項目: しきい値は方向ごとに独立して適用される
+        // given (前提条件):
+        let mut filter = InputFilter::new(FilterConfig {
+            thresholds: DirectionThresholds {
+                up: Thresholds { rise: 2, fall: 1 },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let mut both = up_input(2);
+        both.controller.left = ControllerValue::from_level(2);
+
+        // when (操作):
+        let output = filter.apply(&both, Instant::now());
+
+        // then (期待する結果):
+        assert_eq!(output.controller.left.level(), 2);
+        assert_eq!(output.controller.up.level(), 0);
+    }
+
+    #[test]
+    fn test_min_hold_delays_consecutive_changes() {
+        // テスト項目: 出力を切り替えてから最小保持時間が経つまでは次の切り替えを保留する
+        // given (前提条件):
+        let mut filter = InputFilter::new(FilterConfig {
+            min_hold_ms: 35,
+            ..Default::default()
+        });
+        // 10ms 間隔: 0ms で Low に切り替わり、Middle は 35ms 経過後の 40ms のサンプルで反映される
+        let noisy = [1, 2, 2, 2, 2, 2, 1, 1, 1, 1];
+
+        // when (操作):
+        let output = run_up(&mut filter, &noisy);
+
+        // then (期待する結果):
+        assert_eq!(output, [1, 1, 1, 1, 2, 2, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_validate_rejects_inconsistent_config() {
+        // テスト項目: window より大きい majority や 0 のしきい値は検証エラーになる
+        // given (前提条件):
+        let too_large_majority = FilterConfig {
+            window: 3,
+            majority: 4,
+            ..Default::default()
+        };
+        let zero_fall = FilterConfig {
+            thresholds: DirectionThresholds::uniform(Thresholds { rise: 1, fall: 0 }),
+            ..Default::default()
+        };
+
+        // when (操作):
+        let results = [
+            too_large_majority.validate(),
+            zero_fall.validate(),
+            FilterConfig::default().validate(),
+        ];
+
+        // then (期待する結果):
+        assert!(results[0].is_err());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+    }
+}
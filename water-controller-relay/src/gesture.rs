@@ -0,0 +1,426 @@
+//! ジェスチャー認識
+//!
+//! 入力の時系列を監視し、スワイプやかき混ぜなどの高レベルなイベントに変換する。
+//! コンテンツごとに入力の履歴を持たなくて済むよう、リレーサーバで認識して `gesture` として配信する。
+//!
+//! 方向は、レベルが 0 から 1 以上になったとき（触れ始めたとき）を「触れた」とみなす。
+//! 複数の方向に同時に触れた場合は、動きの順序がわからないためスワイプとかき混ぜには使わない。
+//!
+//! - スワイプ: ある方向に触れた直後に、反対の方向に触れる（`left` → `right` で `swipe-right`）
+//! - かき混ぜ: 4 方向に時計回り（`up` → `right` → `down` → `left` など）または反時計回りの順で触れる
+//! - ダブルタップ: ボタンを 2 回続けて押す
+//! - 長押し: ボタンを押し続ける
+//! - スプラッシュ: 4 方向がほぼ同時に High になる
+//!
+//! 判定に使う時間はそれぞれ [`GestureConfig`] で指定する。
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    edge::{Direction, InputEdge, diff_inputs},
+    serial::input::SerialInput,
+};
+
+/// かき混ぜと判定するのに必要な、続けて触れた方向の数（1 周）
+const STIR_STEPS: usize = 4;
+
+/// 認識するジェスチャー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Gesture {
+    SwipeLeft,
+    SwipeRight,
+    SwipeUp,
+    SwipeDown,
+    StirClockwise,
+    StirCounterClockwise,
+    DoubleTap,
+    LongPress,
+    Splash,
+}
+
+impl Gesture {
+    fn swipe(direction: Direction) -> Self {
+        match direction {
+            Direction::Left => Self::SwipeLeft,
+            Direction::Right => Self::SwipeRight,
+            Direction::Up => Self::SwipeUp,
+            Direction::Down => Self::SwipeDown,
+        }
+    }
+}
+
+/// ジェスチャー認識の設定
+///
+/// ## JSON 例
+///
+/// ```json
+/// {
+///   "enabled": true,
+///   "swipeWindowMs": 400,
+///   "stirWindowMs": 1500,
+///   "doubleTapWindowMs": 300,
+///   "longPressMs": 800,
+///   "splashWindowMs": 150
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct GestureConfig {
+    /// gesture メッセージを配信するか
+    pub enabled: bool,
+    /// スワイプ: 最初の方向に触れてから反対の方向に触れるまでの最大時間（ミリ秒）
+    pub swipe_window_ms: u64,
+    /// かき混ぜ: 1 周分の方向に触れるまでの最大時間（ミリ秒）
+    pub stir_window_ms: u64,
+    /// ダブルタップ: 1 回目に押してから 2 回目に押すまでの最大時間（ミリ秒）
+    pub double_tap_window_ms: u64,
+    /// 長押し: 押し続ける時間（ミリ秒）
+    pub long_press_ms: u64,
+    /// スプラッシュ: 最初の方向が High になってから 4 方向が High になるまでの最大時間（ミリ秒）
+    pub splash_window_ms: u64,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            swipe_window_ms: 400,
+            stir_window_ms: 1500,
+            double_tap_window_ms: 300,
+            long_press_ms: 800,
+            splash_window_ms: 150,
+        }
+    }
+}
+
+impl GestureConfig {
+    /// 設定値の整合性を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        let windows = [
+            ("swipeWindowMs", self.swipe_window_ms),
+            ("stirWindowMs", self.stir_window_ms),
+            ("doubleTapWindowMs", self.double_tap_window_ms),
+            ("longPressMs", self.long_press_ms),
+            ("splashWindowMs", self.splash_window_ms),
+        ];
+        match windows.iter().find(|(_, ms)| *ms == 0) {
+            Some((name, _)) => Err(format!("gesture {name} must be positive")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// ジェスチャー認識器
+///
+/// 入力を受け取るたびに [`GestureEngine::update`] を、入力がなくても一定間隔で
+/// [`GestureEngine::poll`] を呼び出す（ファームウェアは入力が変化したときにのみ行を出力するため、
+/// 長押しは入力を待たずに判定する）。
+#[derive(Debug)]
+pub struct GestureEngine {
+    config: GestureConfig,
+    previous: SerialInput,
+    /// 直近に触れた方向と時刻（古い順）
+    touches: VecDeque<(Direction, Instant)>,
+    /// ボタンを押している場合は押した時刻
+    pressed_at: Option<Instant>,
+    /// 今回の押下で長押しを認識済みか
+    long_press_fired: bool,
+    /// ダブルタップの 1 回目として待っている押下の時刻
+    tap_at: Option<Instant>,
+    /// 方向ごとの High になった時刻（`Direction::ALL` の順）
+    high_since: [Option<Instant>; 4],
+    /// 4 方向が High のままの間にスプラッシュを認識済みか
+    splash_fired: bool,
+}
+
+impl GestureEngine {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            previous: SerialInput::idle(),
+            touches: VecDeque::new(),
+            pressed_at: None,
+            long_press_fired: false,
+            tap_at: None,
+            high_since: [None; 4],
+            splash_fired: false,
+        }
+    }
+
+    /// 設定を変更する
+    ///
+    /// 入力の状態は維持し、認識途中のジェスチャーは破棄する。
+    pub fn set_config(&mut self, config: GestureConfig) {
+        self.config = config;
+        self.touches.clear();
+        self.tap_at = None;
+    }
+
+    /// 入力を 1 つ受け取り、認識したジェスチャーを返す
+    pub fn update(&mut self, input: &SerialInput, now: Instant) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        let mut touched = Vec::new();
+
+        for edge in diff_inputs(&self.previous, input).edges {
+            match edge {
+                InputEdge::ButtonPressed => {
+                    self.pressed_at = Some(now);
+                    self.long_press_fired = false;
+                    match self.tap_at.take() {
+                        Some(tap_at)
+                            if now - tap_at
+                                <= Duration::from_millis(self.config.double_tap_window_ms) =>
+                        {
+                            gestures.push(Gesture::DoubleTap);
+                        }
+                        _ => self.tap_at = Some(now),
+                    }
+                }
+                InputEdge::ButtonReleased => self.pressed_at = None,
+                InputEdge::LevelChanged {
+                    direction,
+                    from,
+                    to,
+                } => {
+                    if from == 0 && to > 0 {
+                        touched.push(direction);
+                    }
+                    self.track_high(direction, to, now);
+                }
+            }
+        }
+        match touched.as_slice() {
+            [direction] => gestures.extend(self.touch(*direction, now)),
+            // 同時に触れた方向は動きの順序がわからないため、スワイプとかき混ぜの途中経過を破棄する
+            [] => {}
+            _ => self.touches.clear(),
+        }
+        gestures.extend(self.splash());
+        gestures.extend(self.poll(now));
+
+        self.previous = input.clone();
+        gestures
+    }
+
+    /// 時間の経過だけで認識できるジェスチャー（長押し）を返す
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        let pressed_at = self.pressed_at?;
+        if self.long_press_fired
+            || now - pressed_at < Duration::from_millis(self.config.long_press_ms)
+        {
+            return None;
+        }
+        self.long_press_fired = true;
+        // 長押しした押下はダブルタップの 1 回目にしない
+        self.tap_at = None;
+        Some(Gesture::LongPress)
+    }
+
+    /// 方向に触れたときに、スワイプとかき混ぜを判定する
+    fn touch(&mut self, direction: Direction, now: Instant) -> Option<Gesture> {
+        let stir_window = Duration::from_millis(self.config.stir_window_ms);
+        while self
+            .touches
+            .front()
+            .is_some_and(|(_, at)| now - *at > stir_window)
+        {
+            self.touches.pop_front();
+        }
+
+        if let Some((last, at)) = self.touches.back()
+            && *last == direction.opposite()
+            && now - *at <= Duration::from_millis(self.config.swipe_window_ms)
+        {
+            self.touches.clear();
+            return Some(Gesture::swipe(direction));
+        }
+
+        self.touches.push_back((direction, now));
+        if self.touches.len() < STIR_STEPS {
+            return None;
+        }
+        let recent: Vec<Direction> = self
+            .touches
+            .iter()
+            .skip(self.touches.len() - STIR_STEPS)
+            .map(|(direction, _)| *direction)
+            .collect();
+        let gesture = if recent.windows(2).all(|w| w[0].clockwise() == w[1]) {
+            Gesture::StirClockwise
+        } else if recent.windows(2).all(|w| w[1].clockwise() == w[0]) {
+            Gesture::StirCounterClockwise
+        } else {
+            return None;
+        };
+        self.touches.clear();
+        Some(gesture)
+    }
+
+    fn track_high(&mut self, direction: Direction, level: u8, now: Instant) {
+        let idx = Direction::ALL
+            .iter()
+            .position(|d| *d == direction)
+            .unwrap_or_default();
+        if level == 3 {
+            self.high_since[idx].get_or_insert(now);
+        } else {
+            self.high_since[idx] = None;
+            self.splash_fired = false;
+        }
+    }
+
+    fn splash(&mut self) -> Option<Gesture> {
+        if self.splash_fired {
+            return None;
+        }
+        let since: Option<Vec<Instant>> = self.high_since.iter().copied().collect();
+        let since = since?;
+        let first = since.iter().min()?;
+        let last = since.iter().max()?;
+        if *last - *first > Duration::from_millis(self.config.splash_window_ms) {
+            return None;
+        }
+        self.splash_fired = true;
+        Some(Gesture::Splash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::input::parse_input_line;
+
+    /// キャプチャファイルと同じ「経過ミリ秒<TAB>行」の列を認識器に通し、認識したジェスチャーと経過ミリ秒を返す
+    fn recognize(config: GestureConfig, recorded: &str) -> Vec<(u64, Gesture)> {
+        let mut engine = GestureEngine::new(config);
+        let start = Instant::now();
+        let mut gestures = Vec::new();
+        for entry in recorded.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (elapsed_ms, line) = entry.split_once('\t').unwrap();
+            let elapsed_ms: u64 = elapsed_ms.parse().unwrap();
+            let now = start + Duration::from_millis(elapsed_ms);
+            let input = parse_input_line(line).unwrap();
+            gestures.extend(
+                engine
+                    .update(&input, now)
+                    .into_iter()
+                    .map(|g| (elapsed_ms, g)),
+            );
+        }
+        gestures
+    }
+
+    // 既定のチャネルマップでは、フィールド 1〜3 が down、4〜6 が left、7〜9 が right、10〜12 が up
+
+    #[test]
+    fn test_swipe_requires_opposite_touch_within_window() {
+        // テスト項目: 反対の方向に続けて触れるとスワイプになり、時間が空くとスワイプにならない
+        // given (前提条件): left → right（100 ms）、up → down（900 ms）
+        let recorded = "
+            0\t0,0,0,0,1,0,0,0,0,0,0,0,0
+            100\t0,0,0,0,0,0,0,1,0,0,0,0,0
+            200\t0,0,0,0,0,0,0,0,0,0,0,0,0
+            300\t0,0,0,0,0,0,0,0,0,0,1,0,0
+            1200\t0,1,0,0,0,0,0,0,0,0,0,0,0
+        ";
+
+        // when (操作):
+        let gestures = recognize(GestureConfig::default(), recorded);
+
+        // then (期待する結果):
+        assert_eq!(gestures, vec![(100, Gesture::SwipeRight)]);
+    }
+
+    #[test]
+    fn test_stir_in_both_directions() {
+        // テスト項目: 4 方向に時計回り・反時計回りの順で触れるとかき混ぜになる（途中でスワイプにはならない）
+        // given (前提条件): up → right → down → left、続けて up → left → down → right
+        let recorded = "
+            0\t0,0,0,0,0,0,0,0,0,0,1,0,0
+            100\t0,0,0,0,0,0,0,1,0,0,0,0,0
+            200\t0,1,0,0,0,0,0,0,0,0,0,0,0
+            300\t0,0,0,0,1,0,0,0,0,0,0,0,0
+            1000\t0,0,0,0,0,0,0,0,0,0,1,0,0
+            1100\t0,0,0,0,1,0,0,0,0,0,0,0,0
+            1200\t0,1,0,0,0,0,0,0,0,0,0,0,0
+            1300\t0,0,0,0,0,0,0,1,0,0,0,0,0
+        ";
+
+        // when (操作):
+        let gestures = recognize(GestureConfig::default(), recorded);
+
+        // then (期待する結果):
+        assert_eq!(
+            gestures,
+            vec![
+                (300, Gesture::StirClockwise),
+                (1300, Gesture::StirCounterClockwise)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_button_double_tap_and_long_press() {
+        // テスト項目: 続けて 2 回押すとダブルタップ、押し続けると入力がなくても長押しになる
+        // given (前提条件):
+        let recorded = "
+            0\t1,0,0,0,0,0,0,0,0,0,0,0,0
+            80\t0,0,0,0,0,0,0,0,0,0,0,0,0
+            200\t1,0,0,0,0,0,0,0,0,0,0,0,0
+            250\t0,0,0,0,0,0,0,0,0,0,0,0,0
+            1000\t1,0,0,0,0,0,0,0,0,0,0,0,0
+        ";
+        let mut engine = GestureEngine::new(GestureConfig::default());
+        let start = Instant::now();
+        engine.update(
+            &parse_input_line("1,0,0,0,0,0,0,0,0,0,0,0,0").unwrap(),
+            start,
+        );
+
+        // when (操作):
+        let taps = recognize(GestureConfig::default(), recorded);
+        let early = engine.poll(start + Duration::from_millis(500));
+        let long = engine.poll(start + Duration::from_millis(800));
+        let repeated = engine.poll(start + Duration::from_millis(900));
+
+        // then (期待する結果):
+        assert_eq!(taps, vec![(200, Gesture::DoubleTap)]);
+        assert_eq!(
+            (early, long, repeated),
+            (None, Some(Gesture::LongPress), None)
+        );
+    }
+
+    #[test]
+    fn test_splash_when_all_directions_reach_high_together() {
+        // テスト項目: 4 方向がほぼ同時に High になるとスプラッシュになり、High のままでは繰り返さない
+        // given (前提条件): 1 回目は 50 ms 以内に 4 方向が High、2 回目は 500 ms かけて High
+        let recorded = "
+            0\t0,1,1,1,1,1,1,0,0,0,0,0,0
+            50\t0,1,1,1,1,1,1,1,1,1,1,1,1
+            100\t0,1,1,1,1,1,1,1,1,1,1,1,1
+            200\t0,0,0,0,0,0,0,0,0,0,0,0,0
+            300\t0,1,1,1,0,0,0,0,0,0,0,0,0
+            800\t0,1,1,1,1,1,1,1,1,1,1,1,1
+        ";
+
+        // when (操作):
+        let gestures = recognize(GestureConfig::default(), recorded);
+
+        // then (期待する結果):
+        assert_eq!(
+            gestures
+                .into_iter()
+                .filter(|(_, g)| *g == Gesture::Splash)
+                .collect::<Vec<_>>(),
+            vec![(50, Gesture::Splash)]
+        );
+    }
+}
@@ -0,0 +1,702 @@
+//! 入力の集約と配信
+//!
+//! 入力源の読み取りタスクと WebSocket サーバの間で共有される状態を管理する
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::{
+    axis::{AxisConfig, AxisVector},
+    edge::{InputEdge, diff_inputs},
+    filter::{FilterConfig, InputFilter},
+    gesture::{Gesture, GestureConfig, GestureEngine},
+    metrics::RelayMetrics,
+    serial::{
+        framing::FrameMetrics,
+        input::{ParseInputError, SerialInput},
+    },
+    source::status::SourceStatus,
+    websocket::{
+        clients::ClientRegistry,
+        message::{
+            AxisInputMessage, ButtonEdgeMessage, ButtonInputMessage, ControllerInputMessage,
+            DiagnosticMessage, GestureMessage, LevelChangedMessage, RawSensorMessage, RelayEvent,
+        },
+    },
+};
+
+/// 入力の発生元
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputOrigin {
+    /// 入力源（シリアルポート・シミュレータ・キャプチャ再生）
+    Source,
+    /// WebSocket クライアントの `simulate-input` コマンド
+    Client,
+}
+
+/// 入力状態メッセージ（button-input / controller-input）の配信方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BroadcastMode {
+    /// 入力を受信するたびに両方のメッセージを配信する
+    #[default]
+    Full,
+    /// 直前の入力から変化したメッセージのみ配信する
+    Changes,
+}
+
+/// 同じ種類のパースエラーの diagnostic メッセージを配信する最短の間隔
+///
+/// 配線の不良などで毎行パースに失敗しても、クライアントにはこの間隔でしか届けない。
+pub const DIAGNOSTIC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 受信が追いつかないクライアントを [`LaggedPolicy::Disconnect`] で閉じるときのクローズコード
+pub const CLOSE_CODE_LAGGED: u16 = 4001;
+
+/// 受信が追いつかないクライアントの扱い
+///
+/// クライアントへの送信が遅く、ブロードキャストチャネルに溜まったメッセージが
+/// `broadcastChannelSize` を超えると、古いメッセージから読み飛ばされる。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaggedPolicy {
+    /// 溜まったメッセージを読み飛ばして最新の状態に追いつき、lagged メッセージと state-snapshot メッセージを送信する
+    #[default]
+    Notify,
+    /// クローズコード 4001 で接続を閉じる
+    Disconnect,
+}
+
+/// 実行中に変更できる設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeSettings {
+    /// 入力源からの入力の配信を一時停止しているか（`simulate-input` は配信される）
+    pub paused: bool,
+    /// 入力状態メッセージの配信方法
+    pub broadcast_mode: BroadcastMode,
+    /// 受信が追いつかないクライアントの扱い
+    pub lagged_policy: LaggedPolicy,
+    /// 入力源からの入力に適用するフィルタの設定
+    pub filter: FilterConfig,
+    /// 静電容量を含む入力を受信したときに raw-sensor メッセージを配信するか
+    pub raw_sensor: bool,
+    /// 入力源から受信した行のパースに失敗したときに diagnostic メッセージを配信するか
+    pub diagnostics: bool,
+    /// axis-input メッセージの設定
+    pub axis: AxisConfig,
+    /// ジェスチャー認識の設定
+    pub gesture: GestureConfig,
+}
+
+/// `set-config` コマンドで指定する設定の差分
+///
+/// 省略したフィールドは変更しない。
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RuntimeSettingsPatch {
+    pub paused: Option<bool>,
+    pub broadcast_mode: Option<BroadcastMode>,
+    pub lagged_policy: Option<LaggedPolicy>,
+    /// フィルタの設定（指定した場合は全体を置き換える）
+    pub filter: Option<FilterConfig>,
+    pub raw_sensor: Option<bool>,
+    pub diagnostics: Option<bool>,
+    /// axis-input メッセージの設定（指定した場合は全体を置き換える）
+    pub axis: Option<AxisConfig>,
+    /// ジェスチャー認識の設定（指定した場合は全体を置き換える）
+    pub gesture: Option<GestureConfig>,
+}
+
+impl RuntimeSettingsPatch {
+    /// 差分の値を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(filter) = &self.filter {
+            filter.validate()?;
+        }
+        if let Some(axis) = &self.axis {
+            axis.validate()?;
+        }
+        if let Some(gesture) = &self.gesture {
+            gesture.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// ブロードキャストチャネルに流すイベント
+///
+/// シリアライズするとイベントそのものになる（受信時刻は含まない）。
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct BroadcastEvent {
+    pub event: RelayEvent,
+    /// 元になった行を入力源から受信した時刻（`simulate-input` と、時間の経過で認識したジェスチャーは `None`）
+    #[serde(skip)]
+    pub read_at: Option<Instant>,
+}
+
+impl BroadcastEvent {
+    pub fn message_type(&self) -> &str {
+        self.event.message_type()
+    }
+}
+
+impl From<RelayEvent> for BroadcastEvent {
+    fn from(event: RelayEvent) -> Self {
+        Self {
+            event,
+            read_at: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HubState {
+    latest_input: Option<SerialInput>,
+    settings: RuntimeSettings,
+    filter: InputFilter,
+    gestures: GestureEngine,
+    /// エラーの種類ごとに、最後に diagnostic メッセージを配信した時刻
+    last_diagnostics: HashMap<&'static str, Instant>,
+}
+
+/// 入力源と WebSocket サーバの間で共有されるハブ
+///
+/// 入力を型付きのイベント（[`RelayEvent`]）に変換してブロードキャストチャネルに流し、最新の入力と実行時設定を保持する。
+/// イベントのシリアライズは受け取る側（WebSocket の各接続など）で行う。
+#[derive(Clone)]
+pub struct Hub {
+    broadcast_tx: broadcast::Sender<BroadcastEvent>,
+    state: Arc<Mutex<HubState>>,
+    frame_metrics: Arc<FrameMetrics>,
+    source_status: Arc<SourceStatus>,
+    metrics: Arc<RelayMetrics>,
+    clients: ClientRegistry,
+    started_at: Instant,
+}
+
+impl Hub {
+    /// ハブを生成する
+    ///
+    /// ## 引数
+    ///
+    /// - `capacity`: ブロードキャストチャネルのサイズ
+    /// - `settings`: 実行時設定の初期値
+    pub fn new(capacity: usize, settings: RuntimeSettings) -> Self {
+        let (broadcast_tx, _) = broadcast::channel(capacity);
+        Self {
+            broadcast_tx,
+            state: Arc::new(Mutex::new(HubState {
+                latest_input: None,
+                filter: InputFilter::new(settings.filter),
+                gestures: GestureEngine::new(settings.gesture),
+                last_diagnostics: HashMap::new(),
+                settings,
+            })),
+            frame_metrics: Arc::new(FrameMetrics::default()),
+            source_status: Arc::new(SourceStatus::default()),
+            metrics: Arc::new(RelayMetrics::default()),
+            clients: ClientRegistry::default(),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// シリアル通信のバイナリフレームの受信状況（入力源の再接続をまたいで累計する）
+    pub fn frame_metrics(&self) -> &Arc<FrameMetrics> {
+        &self.frame_metrics
+    }
+
+    /// 入力源の稼働状況（入力源の再接続をまたいで累計する）
+    pub fn source_status(&self) -> &SourceStatus {
+        &self.source_status
+    }
+
+    /// 配信に関するメトリクス
+    pub fn metrics(&self) -> &RelayMetrics {
+        &self.metrics
+    }
+
+    /// 接続中の WebSocket クライアントの一覧
+    pub fn clients(&self) -> &ClientRegistry {
+        &self.clients
+    }
+
+    /// ハブを生成した（リレーサーバを起動した）時刻
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// ブロードキャストチャネルを subscribe する
+    pub fn subscribe(&self) -> broadcast::Receiver<BroadcastEvent> {
+        self.broadcast_tx.subscribe()
+    }
+
+    /// 入力を配信する
+    ///
+    /// 入力源からの入力は、`paused` が有効な間は破棄し、それ以外はフィルタに通してから配信する。
+    ///
+    /// 直前の入力と比較し、入力状態メッセージ（`broadcast_mode` が `changes` の場合は変化したもののみ。
+    /// `axis` が有効な場合は controller-input に続けて axis-input も配信する）に
+    /// 続けて、エッジイベント（button-pressed / button-released / level-changed）を配信する。
+    /// 最初の入力は入力なしの状態と比較し、入力状態メッセージは常に両方配信する。
+    ///
+    /// `gesture` が有効な場合は、エッジイベントに続けて認識したジェスチャーを gesture メッセージとして配信する。
+    /// `raw_sensor` が有効で、入力が静電容量を含む場合は、最後に raw-sensor メッセージを配信する。
+    ///
+    /// 入力源からの入力のイベントには、直前に受信した行の時刻（[`SourceStatus::record_line`]）を付ける。
+    pub fn publish_input(&self, input: SerialInput, origin: InputOrigin) {
+        let read_at = match origin {
+            InputOrigin::Source => self.source_status.last_line_read_at(),
+            InputOrigin::Client => None,
+        };
+        let (input, previous, mode, raw_sensor, axis, gestures) = {
+            let mut state = self.lock_state();
+            if origin == InputOrigin::Source && state.settings.paused {
+                debug!(input = %input, "Broadcasting is paused, dropping input");
+                return;
+            }
+            let input = match origin {
+                InputOrigin::Source => state.filter.apply(&input, Instant::now()),
+                InputOrigin::Client => input,
+            };
+            // 無効な間も入力の状態は追跡し、有効にした直後に誤認識しないようにする
+            let gestures = state.gestures.update(&input, Instant::now());
+            let gestures = if state.settings.gesture.enabled {
+                gestures
+            } else {
+                Vec::new()
+            };
+            (
+                input.clone(),
+                state.latest_input.replace(input),
+                state.settings.broadcast_mode,
+                state.settings.raw_sensor,
+                state.settings.axis,
+                gestures,
+            )
+        };
+
+        let full = mode == BroadcastMode::Full || previous.is_none();
+        let diff = diff_inputs(&previous.unwrap_or_else(SerialInput::idle), &input);
+
+        // button-input メッセージを送信
+        if full || diff.button_changed {
+            self.broadcast(
+                RelayEvent::ButtonInput(ButtonInputMessage::new(&input.button)),
+                read_at,
+            );
+        }
+        // controller-input メッセージを送信
+        if full || diff.controller_changed {
+            self.broadcast(
+                RelayEvent::ControllerInput(ControllerInputMessage::new(&input.controller)),
+                read_at,
+            );
+        }
+        // axis-input メッセージを送信
+        if axis.enabled && (full || diff.controller_changed) {
+            self.broadcast(
+                RelayEvent::AxisInput(AxisInputMessage::new(AxisVector::from_controller(
+                    &input.controller,
+                    &axis,
+                ))),
+                read_at,
+            );
+        }
+
+        // エッジイベントを送信
+        for edge in diff.edges {
+            match edge {
+                InputEdge::ButtonPressed => {
+                    self.broadcast(
+                        RelayEvent::ButtonEdge(ButtonEdgeMessage::new(true)),
+                        read_at,
+                    );
+                }
+                InputEdge::ButtonReleased => {
+                    self.broadcast(
+                        RelayEvent::ButtonEdge(ButtonEdgeMessage::new(false)),
+                        read_at,
+                    );
+                }
+                InputEdge::LevelChanged {
+                    direction,
+                    from,
+                    to,
+                } => {
+                    self.broadcast(
+                        RelayEvent::LevelChanged(LevelChangedMessage::new(direction, from, to)),
+                        read_at,
+                    );
+                }
+            }
+        }
+
+        // gesture メッセージを送信
+        self.broadcast_gestures(gestures, read_at);
+
+        // raw-sensor メッセージを送信
+        if raw_sensor && let Some(raw) = &input.raw {
+            self.broadcast(RelayEvent::RawSensor(RawSensorMessage::new(raw)), read_at);
+        }
+    }
+
+    /// 入力がなくても時間の経過で認識できるジェスチャー（長押し）を配信する
+    ///
+    /// 入力源とは別に、一定間隔で呼び出す。
+    pub fn poll_gestures(&self) {
+        let gesture = {
+            let mut state = self.lock_state();
+            let gesture = state.gestures.poll(Instant::now());
+            gesture.filter(|_| state.settings.gesture.enabled)
+        };
+        self.broadcast_gestures(gesture, None);
+    }
+
+    fn broadcast_gestures(
+        &self,
+        gestures: impl IntoIterator<Item = Gesture>,
+        read_at: Option<Instant>,
+    ) {
+        for gesture in gestures {
+            debug!(?gesture, "Gesture recognized");
+            self.broadcast(RelayEvent::Gesture(GestureMessage::new(gesture)), read_at);
+        }
+    }
+
+    /// 入力源から受信した行のパースエラーを記録する
+    ///
+    /// `diagnostics` が有効な場合は、エラーの種類ごとに [`DIAGNOSTIC_INTERVAL`] に 1 回まで diagnostic メッセージを配信する。
+    ///
+    /// ## 引数
+    ///
+    /// - `error`: パースエラー
+    /// - `raw_line`: パースに失敗した行
+    pub fn report_parse_error(&self, error: &ParseInputError, raw_line: &str) {
+        let now = Instant::now();
+        let rate_per_minute = self.source_status.record_parse_error(error.kind(), now);
+        let due = {
+            let mut state = self.lock_state();
+            let due = state.settings.diagnostics
+                && state
+                    .last_diagnostics
+                    .get(error.kind())
+                    .is_none_or(|last| now.duration_since(*last) >= DIAGNOSTIC_INTERVAL);
+            if due {
+                state.last_diagnostics.insert(error.kind(), now);
+            }
+            due
+        };
+        if due {
+            self.broadcast(
+                RelayEvent::Diagnostic(DiagnosticMessage::new(error, raw_line, rate_per_minute)),
+                self.source_status.last_line_read_at(),
+            );
+        }
+    }
+
+    /// 最後に配信した入力を返す
+    pub fn latest_input(&self) -> Option<SerialInput> {
+        self.lock_state().latest_input.clone()
+    }
+
+    /// 現在の実行時設定を返す
+    pub fn settings(&self) -> RuntimeSettings {
+        self.lock_state().settings.clone()
+    }
+
+    /// 実行時設定に差分を適用し、適用後の設定を返す
+    pub fn apply_settings(&self, patch: RuntimeSettingsPatch) -> RuntimeSettings {
+        let mut state = self.lock_state();
+        if let Some(paused) = patch.paused {
+            state.settings.paused = paused;
+        }
+        if let Some(broadcast_mode) = patch.broadcast_mode {
+            state.settings.broadcast_mode = broadcast_mode;
+        }
+        if let Some(lagged_policy) = patch.lagged_policy {
+            state.settings.lagged_policy = lagged_policy;
+        }
+        if let Some(filter) = patch.filter {
+            state.settings.filter = filter;
+            state.filter.set_config(filter);
+        }
+        if let Some(raw_sensor) = patch.raw_sensor {
+            state.settings.raw_sensor = raw_sensor;
+        }
+        if let Some(diagnostics) = patch.diagnostics {
+            state.settings.diagnostics = diagnostics;
+        }
+        if let Some(axis) = patch.axis {
+            state.settings.axis = axis;
+        }
+        if let Some(gesture) = patch.gesture {
+            state.settings.gesture = gesture;
+            state.gestures.set_config(gesture);
+        }
+        info!(settings = ?state.settings, "Runtime settings updated");
+        state.settings.clone()
+    }
+
+    fn broadcast(&self, event: RelayEvent, read_at: Option<Instant>) {
+        // 接続中のクライアントがいる場合のみブロードキャスト
+        if self.broadcast_tx.receiver_count() > 0 {
+            debug!(?event, "Broadcasting {}", event.message_type());
+            let message_type = event.message_type().to_string();
+            match self.broadcast_tx.send(BroadcastEvent { event, read_at }) {
+                Ok(_) => self.metrics.record_broadcast(&message_type),
+                Err(e) => warn!(error = %e, "Failed to broadcast {}", e.0.message_type()),
+            }
+        }
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, HubState> {
+        // 状態の更新中にパニックしても、保持している値は常に整合しているのでそのまま使う
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::input::parse_input_line;
+
+    fn json(event: BroadcastEvent) -> String {
+        serde_json::to_string(&event).unwrap()
+    }
+
+    #[test]
+    fn test_publish_input_broadcasts_button_and_controller_messages() {
+        // テスト項目: publish_input が button-input と controller-input を順に配信し、最新の入力を保持する
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        let mut rx = hub.subscribe();
+        let input = parse_input_line("1,1,0,0,0,0,0,0,0,0,0,0,0").unwrap();
+
+        // when (操作):
+        hub.publish_input(input.clone(), InputOrigin::Source);
+
+        // then (期待する結果):
+        assert_eq!(
+            json(rx.try_recv().unwrap()),
+            r#"{"type":"button-input","isPushed":true}"#
+        );
+        assert_eq!(
+            json(rx.try_recv().unwrap()),
+            r#"{"type":"controller-input","left":0,"right":0,"up":0,"down":1}"#
+        );
+        assert_eq!(json(rx.try_recv().unwrap()), r#"{"type":"button-pressed"}"#);
+        assert_eq!(
+            json(rx.try_recv().unwrap()),
+            r#"{"type":"level-changed","direction":"down","from":0,"to":1}"#
+        );
+        assert!(rx.try_recv().is_err());
+        assert_eq!(hub.latest_input(), Some(input));
+    }
+
+    #[test]
+    fn test_paused_hub_drops_source_input_but_not_client_input() {
+        // テスト項目: paused の間は入力源の入力を破棄し、クライアントの入力は配信する
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        let mut rx = hub.subscribe();
+        hub.apply_settings(RuntimeSettingsPatch {
+            paused: Some(true),
+            ..Default::default()
+        });
+        let source_input = parse_input_line("1,0,0,0,0,0,0,0,0,0,0,0,0").unwrap();
+        let client_input = parse_input_line("0,0,0,0,0,0,0,0,0,0,0,0,0").unwrap();
+
+        // when (操作):
+        hub.publish_input(source_input, InputOrigin::Source);
+        hub.publish_input(client_input.clone(), InputOrigin::Client);
+
+        // then (期待する結果):
+        assert_eq!(
+            json(rx.try_recv().unwrap()),
+            r#"{"type":"button-input","isPushed":false}"#
+        );
+        assert_eq!(hub.latest_input(), Some(client_input));
+        assert!(hub.settings().paused);
+    }
+
+    #[test]
+    fn test_changes_mode_broadcasts_only_changed_messages() {
+        // テスト項目: changes モードでは、変化した入力状態メッセージとエッジイベントのみ配信する
+        // given (前提条件):
+        let hub = Hub::new(
+            16,
+            RuntimeSettings {
+                broadcast_mode: BroadcastMode::Changes,
+                ..Default::default()
+            },
+        );
+        let mut rx = hub.subscribe();
+        let idle = parse_input_line("0,0,0,0,0,0,0,0,0,0,0,0,0").unwrap();
+        let up = parse_input_line("0,0,0,0,0,0,0,0,0,0,1,1,0").unwrap();
+
+        // when (操作):
+        hub.publish_input(idle.clone(), InputOrigin::Source);
+        let first: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok().map(json)).collect();
+        hub.publish_input(idle, InputOrigin::Source);
+        hub.publish_input(up, InputOrigin::Source);
+        let rest: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok().map(json)).collect();
+
+        // then (期待する結果):
+        assert_eq!(
+            first,
+            vec![
+                r#"{"type":"button-input","isPushed":false}"#,
+                r#"{"type":"controller-input","left":0,"right":0,"up":0,"down":0}"#,
+            ]
+        );
+        assert_eq!(
+            rest,
+            vec![
+                r#"{"type":"controller-input","left":0,"right":0,"up":2,"down":0}"#,
+                r#"{"type":"level-changed","direction":"up","from":0,"to":2}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_applies_only_to_source_input() {
+        // テスト項目: フィルタは入力源の入力にのみ適用され、クライアントの入力はそのまま配信される
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        hub.apply_settings(RuntimeSettingsPatch {
+            filter: Some(FilterConfig {
+                window: 3,
+                majority: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let up = parse_input_line("0,0,0,0,0,0,0,0,0,0,1,1,1").unwrap();
+
+        // when (操作):
+        hub.publish_input(up.clone(), InputOrigin::Source);
+        let filtered = hub.latest_input().unwrap();
+        hub.publish_input(up.clone(), InputOrigin::Client);
+        let injected = hub.latest_input().unwrap();
+
+        // then (期待する結果):
+        assert_eq!(filtered, SerialInput::idle());
+        assert_eq!(injected, up);
+    }
+
+    #[test]
+    fn test_axis_input_follows_controller_input() {
+        // テスト項目: axis を有効にすると、controller-input に続けて axis-input を配信する
+        // given (前提条件):
+        let hub = Hub::new(
+            16,
+            RuntimeSettings {
+                axis: AxisConfig {
+                    enabled: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let mut rx = hub.subscribe();
+        let input = parse_input_line("0,0,0,0,1,1,1,0,0,0,0,0,0").unwrap();
+
+        // when (操作):
+        hub.publish_input(input, InputOrigin::Source);
+        let messages: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok().map(json)).collect();
+
+        // then (期待する結果): 既定のチャネルマップではフィールド 4〜6 が左方向
+        assert_eq!(
+            &messages[1..3],
+            [
+                r#"{"type":"controller-input","left":3,"right":0,"up":0,"down":0}"#,
+                r#"{"type":"axis-input","x":-1.0,"y":0.0}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_sensor_message_is_opt_in() {
+        // テスト項目: raw-sensor メッセージは raw_sensor を有効にした場合のみ、エッジイベントの後に配信される
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        let mut rx = hub.subscribe();
+        let filtered = "500,500,500,500,500,500,500,500,500,500,500,500";
+        let baseline = "510,520,530,540,550,560,570,580,590,600,610,620";
+        let input =
+            parse_input_line(&format!("0,0,0,0,0,0,0,0,0,0,0,0,0;{filtered};{baseline}")).unwrap();
+
+        // when (操作):
+        hub.publish_input(input.clone(), InputOrigin::Source);
+        let disabled: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok().map(json)).collect();
+        hub.apply_settings(RuntimeSettingsPatch {
+            raw_sensor: Some(true),
+            ..Default::default()
+        });
+        hub.publish_input(input, InputOrigin::Source);
+        let enabled: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok().map(json)).collect();
+
+        // then (期待する結果): 既定のチャネルマップではフィールド 1〜3 が下方向
+        assert!(disabled.iter().all(|m| !m.contains("raw-sensor")));
+        let raw: serde_json::Value = serde_json::from_str(enabled.last().unwrap()).unwrap();
+        assert_eq!(raw["type"], "raw-sensor");
+        assert_eq!(
+            raw["down"],
+            serde_json::json!([
+                {"filtered": 500, "baseline": 510},
+                {"filtered": 500, "baseline": 520},
+                {"filtered": 500, "baseline": 530}
+            ])
+        );
+        assert_eq!(raw["up"][2]["baseline"], 620);
+    }
+
+    #[test]
+    fn test_diagnostics_are_opt_in_and_throttled_per_kind() {
+        // テスト項目: diagnostic メッセージは diagnostics を有効にした場合のみ、エラーの種類ごとに間隔を空けて配信される
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        let mut rx = hub.subscribe();
+        let short = "1,1";
+        let invalid = "0,0,0,0,0,2,0,0,0,0,0,0,0";
+        let error = |line: &str| parse_input_line(line).unwrap_err();
+
+        // when (操作):
+        hub.report_parse_error(&error(short), short);
+        let disabled: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok().map(json)).collect();
+        hub.apply_settings(RuntimeSettingsPatch {
+            diagnostics: Some(true),
+            ..Default::default()
+        });
+        hub.report_parse_error(&error(short), short);
+        hub.report_parse_error(&error(short), short);
+        hub.report_parse_error(&error(invalid), invalid);
+        let enabled: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|event| serde_json::to_value(&event).unwrap())
+            .collect();
+
+        // then (期待する結果):
+        assert!(disabled.is_empty());
+        assert_eq!(enabled.len(), 2);
+        assert_eq!(enabled[0]["kind"], "field-count");
+        assert_eq!(enabled[0]["fieldIndex"], serde_json::Value::Null);
+        assert_eq!(enabled[0]["ratePerMinute"], 2);
+        assert_eq!(enabled[1]["kind"], "invalid-controller-value");
+        assert_eq!(enabled[1]["fieldIndex"], 5);
+        assert_eq!(enabled[1]["rawLine"], invalid);
+        assert_eq!(enabled[1]["ratePerMinute"], 4);
+        assert_eq!(hub.source_status().snapshot(Instant::now()).parse_errors, 4);
+    }
+}
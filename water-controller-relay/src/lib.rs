@@ -3,8 +3,16 @@
 //! Arduino から受信したシリアルデータを WebSocket 経由で配信するライブラリ
 
 pub mod args;
+pub mod axis;
+pub mod config;
+pub mod edge;
+pub mod filter;
+pub mod gesture;
+pub mod hub;
 pub mod logger;
+pub mod metrics;
 pub mod relay;
 pub mod serial;
+pub mod source;
 pub mod tui;
 pub mod websocket;
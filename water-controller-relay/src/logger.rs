@@ -1,16 +1,41 @@
 use std::io;
 
 use tracing::Level;
+use tracing_subscriber::{
+    Registry, filter::LevelFilter, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
 
-pub fn logger_init(level: Level) -> io::Result<()> {
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(level)
+/// 実行中にログレベルを変更するためのハンドル
+#[derive(Clone)]
+pub struct LogLevelHandle(reload::Handle<LevelFilter, Registry>);
+
+impl LogLevelHandle {
+    /// ログレベルを変更する
+    pub fn set(&self, level: Level) -> io::Result<()> {
+        self.0
+            .modify(|filter| *filter = LevelFilter::from_level(level))
+            .map_err(io::Error::other)
+    }
+}
+
+/// ログを標準エラー出力に出力する
+///
+/// 標準出力は `device-list --format json` などのコマンドの出力に使うため、ログを混ぜない。
+pub fn logger_init(level: Level) -> io::Result<LogLevelHandle> {
+    let (filter, handle) = reload::Layer::new(LevelFilter::from_level(level));
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
         .with_target(true)
         .with_thread_ids(true)
         .with_thread_names(true)
         .with_file(true)
-        .with_line_number(true)
-        .finish();
+        .with_line_number(true);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .try_init()
+        .map_err(io::Error::other)?;
 
-    tracing::subscriber::set_global_default(subscriber).map_err(io::Error::other)
+    Ok(LogLevelHandle(handle))
 }
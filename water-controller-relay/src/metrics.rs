@@ -0,0 +1,319 @@
+//! Prometheus 形式のメトリクス
+//!
+//! 数日間の展示でコントローラの状態をグラフにできるよう、`/metrics` でテキスト形式
+//! （exposition format 0.0.4）のメトリクスを返す。
+//!
+//! 行・パースエラー・再接続の数は入力源の稼働状況（[`crate::source::status`]）から、接続数はクライアントの一覧から
+//! 出力時に読み出す。ここでは配信に関するカウンタと、行の受信から WebSocket の送信までの遅延のヒストグラムを保持する。
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    hub::{Hub, LaggedPolicy},
+    serial::input::ParseInputError,
+};
+
+/// 遅延のヒストグラムのバケットの上限（秒）
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+];
+
+/// 配信に関するメトリクス（WebSocket の各接続と共有する）
+#[derive(Debug, Default)]
+pub struct RelayMetrics {
+    /// メッセージの種類ごとの配信数
+    broadcast: Mutex<BTreeMap<String, u64>>,
+    /// 受信が追いつかないクライアントのために読み飛ばしたメッセージの数
+    lagged_messages: AtomicU64,
+    /// 受信が追いつかなくなり、最新の状態を送り直したクライアントの数
+    lagged_notified: AtomicU64,
+    /// 受信が追いつかなくなり、切断したクライアントの数
+    lagged_disconnected: AtomicU64,
+    /// 行の受信から WebSocket の送信までの遅延
+    latency: Histogram,
+}
+
+impl RelayMetrics {
+    /// ブロードキャストチャネルにメッセージを流したときに呼ぶ
+    pub fn record_broadcast(&self, message_type: &str) {
+        let mut broadcast = self
+            .broadcast
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match broadcast.get_mut(message_type) {
+            Some(count) => *count += 1,
+            None => {
+                broadcast.insert(message_type.to_string(), 1);
+            }
+        }
+    }
+
+    /// クライアントの受信が追いつかず、`skipped` 件のメッセージを読み飛ばしたときに呼ぶ
+    pub fn record_lagged(&self, skipped: u64, policy: LaggedPolicy) {
+        self.lagged_messages.fetch_add(skipped, Ordering::Relaxed);
+        match policy {
+            LaggedPolicy::Notify => &self.lagged_notified,
+            LaggedPolicy::Disconnect => &self.lagged_disconnected,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 入力源から受信した行を元にしたメッセージを WebSocket で送信したときに、その遅延を記録する
+    pub fn observe_latency(&self, latency: Duration) {
+        self.latency.observe(latency);
+    }
+}
+
+/// 累積しない（バケットごとの）カウントを保持し、出力時に累積するヒストグラム
+#[derive(Debug, Default)]
+struct Histogram {
+    /// `LATENCY_BUCKETS` の各バケットと +Inf のカウント
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, value: Duration) {
+        let seconds = value.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|upper| seconds <= *upper)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(value.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// ハブの状態から、Prometheus のテキスト形式のメトリクスを出力する
+pub fn render(hub: &Hub) -> String {
+    let metrics = hub.metrics();
+    let source = hub.source_status().snapshot(Instant::now());
+    let frames = hub.frame_metrics().snapshot();
+    let mut out = String::new();
+
+    write_metric(
+        &mut out,
+        "water_controller_lines_read_total",
+        "counter",
+        "Lines read from the input source.",
+        &[("", source.lines)],
+    );
+    let parse_errors: Vec<(String, u64)> = ParseInputError::KINDS
+        .iter()
+        .map(|kind| {
+            let count = source.parse_errors_by_kind.get(*kind).copied();
+            (format!("kind=\"{kind}\""), count.unwrap_or(0))
+        })
+        .collect();
+    write_metric(
+        &mut out,
+        "water_controller_parse_errors_total",
+        "counter",
+        "Lines that failed to parse, by error kind.",
+        &labeled(&parse_errors),
+    );
+    write_metric(
+        &mut out,
+        "water_controller_serial_reconnects_total",
+        "counter",
+        "Times the input source was reopened after the first open.",
+        &[("", source.reconnects)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_source_open",
+        "gauge",
+        "Whether the input source is open.",
+        &[("", u64::from(source.open))],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_frames_total",
+        "counter",
+        "Binary frames received intact.",
+        &[("", frames.frames)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_corrupt_frames_total",
+        "counter",
+        "Binary frames whose CRC did not match.",
+        &[("", frames.corrupt_frames)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_lost_frames_total",
+        "counter",
+        "Binary frames estimated lost from sequence number gaps.",
+        &[("", frames.lost_frames)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_frame_dropped_bytes_total",
+        "counter",
+        "Bytes discarded while searching for the frame sync byte.",
+        &[("", frames.dropped_bytes)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_frame_sequence_gaps_total",
+        "counter",
+        "Times the binary frame sequence number skipped ahead.",
+        &[("", frames.sequence_gaps)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_frame_sequence_resets_total",
+        "counter",
+        "Times the binary frame sequence number repeated or went back (duplicate frame or firmware restart).",
+        &[("", frames.sequence_resets)],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_frame_invalid_payloads_total",
+        "counter",
+        "Binary frames dropped because the payload was not UTF-8.",
+        &[("", frames.invalid_payloads)],
+    );
+
+    let broadcast: Vec<(String, u64)> = metrics
+        .broadcast
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|(message_type, count)| (format!("type=\"{message_type}\""), *count))
+        .collect();
+    write_metric(
+        &mut out,
+        "water_controller_messages_broadcast_total",
+        "counter",
+        "Messages put on the broadcast channel, by message type.",
+        &labeled(&broadcast),
+    );
+    write_metric(
+        &mut out,
+        "water_controller_lagged_messages_total",
+        "counter",
+        "Broadcast messages skipped because a client lagged behind.",
+        &[("", metrics.lagged_messages.load(Ordering::Relaxed))],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_lagged_clients_total",
+        "counter",
+        "Times a client lagged behind the broadcast channel, by policy applied.",
+        &[
+            (
+                "policy=\"notify\"",
+                metrics.lagged_notified.load(Ordering::Relaxed),
+            ),
+            (
+                "policy=\"disconnect\"",
+                metrics.lagged_disconnected.load(Ordering::Relaxed),
+            ),
+        ],
+    );
+    write_metric(
+        &mut out,
+        "water_controller_connected_clients",
+        "gauge",
+        "WebSocket clients currently connected.",
+        &[("", hub.clients().list().len() as u64)],
+    );
+
+    let name = "water_controller_line_to_send_latency_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {name} Time from reading a serial line to sending the resulting message over WebSocket."
+    );
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    let mut cumulative = 0;
+    for (index, bucket) in metrics.latency.buckets.iter().enumerate() {
+        cumulative += bucket.load(Ordering::Relaxed);
+        let upper = LATENCY_BUCKETS
+            .get(index)
+            .map_or_else(|| "+Inf".to_string(), |upper| upper.to_string());
+        let _ = writeln!(out, "{name}_bucket{{le=\"{upper}\"}} {cumulative}");
+    }
+    let sum = metrics.latency.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+    let _ = writeln!(out, "{name}_sum {sum}");
+    let _ = writeln!(out, "{name}_count {cumulative}");
+
+    out
+}
+
+fn labeled(samples: &[(String, u64)]) -> Vec<(&str, u64)> {
+    samples
+        .iter()
+        .map(|(labels, value)| (labels.as_str(), *value))
+        .collect()
+}
+
+/// 1 つのメトリクスを HELP / TYPE とともに出力する（ラベルが空の場合はラベルを付けない）
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, u64)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hub::RuntimeSettings;
+
+    #[test]
+    fn test_render_exports_counters_and_cumulative_latency_buckets() {
+        // テスト項目: カウンタをラベルごとに出力し、遅延のヒストグラムのバケットを累積して出力する
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        hub.source_status().record_line(Instant::now());
+        hub.source_status()
+            .record_parse_error("field-count", Instant::now());
+        hub.metrics().record_broadcast("button-input");
+        hub.metrics().record_broadcast("button-input");
+        hub.metrics().record_lagged(5, LaggedPolicy::Notify);
+        hub.metrics().observe_latency(Duration::from_micros(300));
+        hub.metrics().observe_latency(Duration::from_millis(2));
+        hub.metrics().observe_latency(Duration::from_secs(3));
+
+        // when (操作):
+        let text = render(&hub);
+
+        // then (期待する結果):
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            "water_controller_lines_read_total 1",
+            "water_controller_parse_errors_total{kind=\"field-count\"} 1",
+            "water_controller_parse_errors_total{kind=\"parse-int\"} 0",
+            "water_controller_messages_broadcast_total{type=\"button-input\"} 2",
+            "water_controller_lagged_messages_total 5",
+            "water_controller_lagged_clients_total{policy=\"notify\"} 1",
+            "water_controller_connected_clients 0",
+            "# TYPE water_controller_line_to_send_latency_seconds histogram",
+            "water_controller_line_to_send_latency_seconds_bucket{le=\"0.0005\"} 1",
+            "water_controller_line_to_send_latency_seconds_bucket{le=\"0.001\"} 1",
+            "water_controller_line_to_send_latency_seconds_bucket{le=\"0.0025\"} 2",
+            "water_controller_line_to_send_latency_seconds_bucket{le=\"1\"} 2",
+            "water_controller_line_to_send_latency_seconds_bucket{le=\"+Inf\"} 3",
+            "water_controller_line_to_send_latency_seconds_sum 3.0023",
+            "water_controller_line_to_send_latency_seconds_count 3",
+        ] {
+            assert!(lines.contains(&expected), "missing {expected:?} in\n{text}");
+        }
+    }
+}
@@ -1,59 +1,140 @@
-use std::{io, time::Duration};
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
-use tokio::sync::broadcast;
-use tracing::{error, info, warn};
+use tokio::sync::watch;
 
-use crate::{serial::SerialReader, websocket::server::run_websocket_server};
+use tracing::{error, info, warn};
 
-const READ_TIMEOUT_MS: u64 = 100;
-const RETRY_INTERVAL_MS: u64 = 100;
+use crate::{
+    config::{ConfigReloader, RelayConfig},
+    hub::{Hub, RuntimeSettings, RuntimeSettingsPatch},
+    logger::LogLevelHandle,
+    source::{
+        InputSource, InputSourceConfig,
+        capture::{CaptureWriter, RecordingSource},
+    },
+    websocket::server::run_websocket_server,
+};
 
-/// シリアルポートからのデータの読み取りが秒間 100 回行われる場合に、ブロードキャストチャネルのサイズを設定する
-const BROADCAST_CHANNEL_SIZE: usize = 100;
+/// 長押しなど、時間の経過だけで認識できるジェスチャーを判定する間隔
+const GESTURE_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
-/// シリアル通信と WebSocket サーバを並行実行する
+/// 入力源（シリアル通信またはシミュレータ）の読み取りと WebSocket サーバを並行実行する
 ///
 /// ## 引数
 ///
-/// - `port_name`: シリアルポート名
-/// - `baud_rate`: ボーレート
-/// - `ws_host`: WebSocket サーバのホストアドレス
-/// - `ws_port`: WebSocket サーバのポート番号
+/// - `source`: 入力源の設定
+/// - `record`: 読み取った生の行を記録するキャプチャファイルのパス（省略時は記録しない）
+/// - `config`: リレーサーバの設定
+/// - `reloader`: 設定ファイルの監視（省略時は設定を変更しない）
+/// - `log_level`: 設定ファイルの変更に合わせてログレベルを切り替えるハンドル
 pub async fn run_loop(
-    port_name: &str,
-    baud_rate: u32,
-    ws_host: &str,
-    ws_port: u16,
+    source: InputSourceConfig,
+    record: Option<PathBuf>,
+    config: RelayConfig,
+    reloader: Option<ConfigReloader>,
+    log_level: Option<LogLevelHandle>,
 ) -> io::Result<()> {
-    // ブロードキャストチャネルを作成
-    let (broadcast_tx, _) = broadcast::channel(BROADCAST_CHANNEL_SIZE);
+    // 入力源と WebSocket サーバで共有するハブ（ブロードキャストチャネルを含む）を作成
+    let hub = Hub::new(
+        config.websocket.broadcast_channel_size,
+        RuntimeSettings {
+            broadcast_mode: config.websocket.broadcast_mode,
+            lagged_policy: config.websocket.lagged_policy,
+            filter: config.filter,
+            raw_sensor: config.websocket.raw_sensor,
+            diagnostics: config.websocket.diagnostics,
+            axis: config.axis,
+            gesture: config.gesture,
+            ..Default::default()
+        },
+    );
+
+    // キャプチャファイルは入力源の再接続をまたいで共有する
+    let capture_writer = record.as_deref().map(CaptureWriter::create).transpose()?;
+
+    // 設定ファイルの変更は watch チャネルで各タスクに伝える
+    let ws_config = config.websocket.clone();
+    let (config_tx, config_rx) = watch::channel(config);
+    if let Some(reloader) = reloader {
+        tokio::spawn(reloader.watch(config_tx));
+        tokio::spawn(apply_config_changes(
+            config_rx.clone(),
+            hub.clone(),
+            log_level,
+        ));
+    }
+
+    // 入力がなくても長押しを認識できるよう、一定間隔でジェスチャーを判定する
+    tokio::spawn(poll_gestures(hub.clone()));
+
+    // シリアル通信の設定が変わったら入力源を開き直す
+    let generation = Arc::new(AtomicU64::new(0));
+    tokio::spawn(watch_serial_config(config_rx.clone(), generation.clone()));
 
     // シリアル読み取りタスクを起動
     let serial_task = {
-        let broadcast_tx = broadcast_tx.clone();
-        let port_name = port_name.to_string();
+        let hub = hub.clone();
+        let config_rx = config_rx.clone();
 
         tokio::task::spawn_blocking(move || -> io::Result<()> {
             loop {
-                // シリアルポートを開く
-                info!(port = %port_name, baud = baud_rate, "Opening serial port");
-                let timeout = Duration::from_millis(READ_TIMEOUT_MS);
-                let mut reader = match SerialReader::open(&port_name, baud_rate, timeout) {
-                    Ok(reader) => reader,
-                    Err(e) => {
-                        warn!(error = %e, "Failed to open serial port, retrying...");
-                        std::thread::sleep(Duration::from_millis(RETRY_INTERVAL_MS));
-                        continue;
-                    }
+                let opened_generation = generation.load(Ordering::SeqCst);
+                let (source, mut decoder, serial_config) = {
+                    let config = config_rx.borrow();
+                    // 設定ファイルでシリアル通信の設定が変更された後は、ファイルの値で開き直す
+                    let source = if opened_generation == 0 {
+                        source.clone()
+                    } else {
+                        source.with_serial_config(&config.serial)
+                    };
+                    let decoder = source.decoder(&config.serial);
+                    (source, decoder, config.serial.clone())
                 };
-                info!(port = %port_name, "Serial port ready! Entering read loop...");
+                let retry_interval = serial_config.retry_interval();
+
+                // 入力源（シリアルポートまたはシミュレータ）を開く
+                info!(source = %source, "Opening input source");
+                let reader: Box<dyn InputSource> =
+                    match source.open(&serial_config, hub.frame_metrics()) {
+                        Ok(reader) => match &capture_writer {
+                            Some(writer) => Box::new(RecordingSource::new(reader, writer.clone())),
+                            None => reader,
+                        },
+                        Err(e) => {
+                            warn!(error = %e, "Failed to open input source, retrying...");
+                            std::thread::sleep(retry_interval);
+                            continue;
+                        }
+                    };
+                let mut reader =
+                    RestartableSource::new(reader, generation.clone(), opened_generation);
+                info!(source = %source, "Input source ready! Entering read loop...");
+                hub.source_status().set_opened(
+                    source.to_string(),
+                    reader.port_name(),
+                    source.baud_rate(),
+                );
 
                 // シリアルポートからの読み取りループを開始
-                match reader.run_read_loop(broadcast_tx.clone()) {
+                let result = reader.run_read_loop(&hub, decoder.as_mut());
+                hub.source_status().set_closed();
+                match result {
                     Ok(()) => break,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                        info!("Serial settings changed, reopening input source");
+                        continue;
+                    }
                     Err(e) => {
                         error!(error = %e, "Serial read loop failed, retrying...");
-                        std::thread::sleep(Duration::from_millis(RETRY_INTERVAL_MS));
+                        std::thread::sleep(retry_interval);
                         continue;
                     }
                 }
@@ -65,15 +146,15 @@ pub async fn run_loop(
 
     // WebSocket サーバタスクを起動
     let ws_task: tokio::task::JoinHandle<Result<(), io::Error>> = {
-        let broadcast_tx = broadcast_tx.clone();
-        let ws_host = ws_host.to_string();
+        let hub = hub.clone();
         tokio::spawn(async move {
             loop {
-                match run_websocket_server(&ws_host, ws_port, broadcast_tx.clone()).await {
+                match run_websocket_server(&ws_config, hub.clone()).await {
                     Ok(_) => break,
                     Err(e) => {
                         error!(error = %e, "WebSocket server failed, retrying...");
-                        tokio::time::sleep(Duration::from_millis(RETRY_INTERVAL_MS)).await;
+                        let retry_interval = config_rx.borrow().serial.retry_interval();
+                        tokio::time::sleep(retry_interval).await;
                     }
                 }
             }
@@ -118,3 +199,136 @@ pub async fn run_loop(
 
     Ok(())
 }
+
+/// 一定間隔で時間の経過だけで認識できるジェスチャーを判定する
+async fn poll_gestures(hub: Hub) {
+    let mut interval = tokio::time::interval(GESTURE_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        hub.poll_gestures();
+    }
+}
+
+/// 設定ファイルの変更のうち、実行中に反映できるもの（配信方法・受信が追いつかないクライアントの扱い・フィルタ・raw-sensor / diagnostic / axis-input / gesture の配信・ログレベル）を反映する
+///
+/// WebSocket サーバのホスト・ポートとブロードキャストチャネルのサイズは、再起動するまで反映されない。
+/// シリアル通信の設定は [`watch_serial_config`] が入力源を開き直して反映する。
+async fn apply_config_changes(
+    mut config_rx: watch::Receiver<RelayConfig>,
+    hub: Hub,
+    log_level: Option<LogLevelHandle>,
+) {
+    let mut current = config_rx.borrow_and_update().clone();
+    while config_rx.changed().await.is_ok() {
+        let next = config_rx.borrow_and_update().clone();
+
+        let mut patch = RuntimeSettingsPatch::default();
+        if next.websocket.broadcast_mode != current.websocket.broadcast_mode {
+            patch.broadcast_mode = Some(next.websocket.broadcast_mode);
+        }
+        if next.websocket.lagged_policy != current.websocket.lagged_policy {
+            patch.lagged_policy = Some(next.websocket.lagged_policy);
+        }
+        if next.filter != current.filter {
+            patch.filter = Some(next.filter);
+        }
+        if next.websocket.raw_sensor != current.websocket.raw_sensor {
+            patch.raw_sensor = Some(next.websocket.raw_sensor);
+        }
+        if next.websocket.diagnostics != current.websocket.diagnostics {
+            patch.diagnostics = Some(next.websocket.diagnostics);
+        }
+        if next.axis != current.axis {
+            patch.axis = Some(next.axis);
+        }
+        if next.gesture != current.gesture {
+            patch.gesture = Some(next.gesture);
+        }
+        if patch != RuntimeSettingsPatch::default() {
+            hub.apply_settings(patch);
+        }
+
+        if next.logging != current.logging
+            && let Some(handle) = &log_level
+        {
+            match handle.set(next.logging.level.into()) {
+                Ok(()) => info!(level = ?next.logging.level, "Log level updated"),
+                Err(e) => warn!(error = %e, "Failed to update log level"),
+            }
+        }
+
+        if next.websocket.host != current.websocket.host
+            || next.websocket.port != current.websocket.port
+            || next.websocket.broadcast_channel_size != current.websocket.broadcast_channel_size
+            || next.websocket.heartbeat != current.websocket.heartbeat
+            || next.websocket.ready_timeout_ms != current.websocket.ready_timeout_ms
+        {
+            warn!(
+                "WebSocket host/port/broadcastChannelSize/heartbeat/readyTimeoutMs changes take effect after restart"
+            );
+        }
+
+        current = next;
+    }
+}
+
+/// シリアル通信の設定が変わったら世代番号を進め、読み取り中の入力源を中断させる
+///
+/// シリアルポートは行が届かない間も読み取りタイムアウト（`readTimeoutMs`）ごとに世代番号を確認するため、
+/// ポートが無応答でも `readTimeoutMs` 以内に開き直す。
+async fn watch_serial_config(
+    mut config_rx: watch::Receiver<RelayConfig>,
+    generation: Arc<AtomicU64>,
+) {
+    let mut current = config_rx.borrow_and_update().serial.clone();
+    while config_rx.changed().await.is_ok() {
+        let next = config_rx.borrow_and_update().serial.clone();
+        if next != current {
+            generation.fetch_add(1, Ordering::SeqCst);
+            current = next;
+        }
+    }
+}
+
+/// 世代番号が進んだら `Interrupted` エラーを返して読み取りループを抜ける入力源
+struct RestartableSource {
+    inner: Box<dyn InputSource>,
+    generation: Arc<AtomicU64>,
+    opened_generation: u64,
+}
+
+impl RestartableSource {
+    fn new(
+        mut inner: Box<dyn InputSource>,
+        generation: Arc<AtomicU64>,
+        opened_generation: u64,
+    ) -> Self {
+        // 行の受信を待っている間も、世代番号が進んだら読み取りを中断させる
+        let current = generation.clone();
+        inner.set_interrupt(Arc::new(move || {
+            current.load(Ordering::SeqCst) != opened_generation
+        }));
+        Self {
+            inner,
+            generation,
+            opened_generation,
+        }
+    }
+}
+
+impl InputSource for RestartableSource {
+    fn read_line(&mut self) -> io::Result<String> {
+        if self.generation.load(Ordering::SeqCst) != self.opened_generation {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "serial settings changed",
+            ));
+        }
+        self.inner.read_line()
+    }
+
+    fn port_name(&self) -> Option<String> {
+        self.inner.port_name()
+    }
+}
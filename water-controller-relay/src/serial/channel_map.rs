@@ -0,0 +1,279 @@
+//! フィールドと方向の対応（チャネルマップ）
+//!
+//! 13 フィールド形式の各フィールドが、ボタンとどの方向・レベル（Low/Middle/High）に対応するかを設定で指定する。
+//! 展示会場でトレイを回転・反転して設置した場合も、再コンパイルせずに方向を合わせられる。
+//!
+//! ## JSON 例
+//!
+//! ```json
+//! {
+//!   "button": 0,
+//!   "up": [1, 2, 3],
+//!   "right": [4, 5, 6],
+//!   "down": [7, 8, 9],
+//!   "left": [10, 11, 12],
+//!   "mirror": false,
+//!   "rotation": 90
+//! }
+//! ```
+//!
+//! 方向ごとの配列は Low/Middle/High の順のフィールド番号。`mirror` で左右を入れ替えてから、
+//! `rotation`（時計回りの角度）だけ方向を回転する。
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    edge::Direction,
+    serial::input::{
+        ButtonInput, ControllerInput, ControllerValue, ELECTRODE_COUNT, FIELD_COUNT,
+        ParseInputError, RawElectrode, RawSensorReadings, SerialInput,
+        controller_value_from_triple, parse_button,
+    },
+};
+
+/// フィールドと方向の対応
+///
+/// 既定値はこれまでのファームウェアとの対応（front→down, right→left, back→right, left→up）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct ChannelMap {
+    /// ボタンのフィールド番号
+    pub button: usize,
+    /// 左方向の Low/Middle/High のフィールド番号
+    pub left: [usize; 3],
+    /// 右方向の Low/Middle/High のフィールド番号
+    pub right: [usize; 3],
+    /// 上方向の Low/Middle/High のフィールド番号
+    pub up: [usize; 3],
+    /// 下方向の Low/Middle/High のフィールド番号
+    pub down: [usize; 3],
+    /// トレイを裏返して設置した場合に左右を入れ替える
+    pub mirror: bool,
+    /// トレイを回転して設置した角度（時計回り。0/90/180/270）
+    pub rotation: u16,
+}
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self {
+            button: 0,
+            down: [1, 2, 3],
+            left: [4, 5, 6],
+            right: [7, 8, 9],
+            up: [10, 11, 12],
+            mirror: false,
+            rotation: 0,
+        }
+    }
+}
+
+impl ChannelMap {
+    /// 設定値の整合性を検証する
+    ///
+    /// フィールド番号は 0〜12 の範囲で重複しないこと、回転角度は 90 の倍数であることを確認する。
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.rotation, 0 | 90 | 180 | 270) {
+            return Err(format!(
+                "channelMap.rotation must be 0, 90, 180 or 270 (got {})",
+                self.rotation
+            ));
+        }
+
+        let mut used = [false; FIELD_COUNT];
+        let fields = std::iter::once(("button", self.button)).chain(
+            Direction::ALL
+                .into_iter()
+                .flat_map(|direction| self.fields(direction).map(|idx| (name(direction), idx))),
+        );
+        for (label, idx) in fields {
+            if idx >= FIELD_COUNT {
+                return Err(format!(
+                    "channelMap.{label} field #{idx} is out of range (expected 0-{})",
+                    FIELD_COUNT - 1
+                ));
+            }
+            if used[idx] {
+                return Err(format!(
+                    "channelMap field #{idx} is assigned more than once"
+                ));
+            }
+            used[idx] = true;
+        }
+        Ok(())
+    }
+
+    /// 13 フィールドの値（センサー値は 0/1 を検証済み）を入力状態に変換する
+    pub fn apply(&self, values: &[i32; FIELD_COUNT]) -> Result<SerialInput, ParseInputError> {
+        self.apply_with_raw(values, None)
+    }
+
+    /// 13 フィールドの値と、フィールド 1〜12 の電極の静電容量を入力状態に変換する
+    pub fn apply_with_raw(
+        &self,
+        values: &[i32; FIELD_COUNT],
+        raw: Option<&[RawElectrode; ELECTRODE_COUNT]>,
+    ) -> Result<SerialInput, ParseInputError> {
+        let button: ButtonInput = parse_button(values[self.button])?;
+        let mut readings = raw.map(|_| RawSensorReadings::default());
+
+        let mut controller = ControllerInput {
+            left: ControllerValue::Noinput(0),
+            right: ControllerValue::Noinput(0),
+            up: ControllerValue::Noinput(0),
+            down: ControllerValue::Noinput(0),
+        };
+        for direction in Direction::ALL {
+            let [low, middle, high] = self.fields(direction);
+            let value = controller_value_from_triple(
+                values[low],
+                values[middle],
+                values[high],
+                low,
+                middle,
+                high,
+            )?;
+            let oriented = self.orient(direction);
+            *slot(&mut controller, oriented) = value;
+
+            if let (Some(raw), Some(readings)) = (raw, readings.as_mut()) {
+                // ボタンのフィールドは電極ではないため、ボタンに割り当てたフィールドの値は 0 のまま
+                let electrode = |idx: usize| idx.checked_sub(1).map(|e| raw[e]).unwrap_or_default();
+                *raw_slot(readings, oriented) =
+                    [electrode(low), electrode(middle), electrode(high)];
+            }
+        }
+
+        Ok(SerialInput {
+            button,
+            controller,
+            raw: readings,
+        })
+    }
+
+    fn fields(&self, direction: Direction) -> [usize; 3] {
+        match direction {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+        }
+    }
+
+    /// トレイ上の方向を、反転・回転を反映した配信上の方向に変換する
+    fn orient(&self, direction: Direction) -> Direction {
+        let direction = match (self.mirror, direction) {
+            (true, Direction::Left) => Direction::Right,
+            (true, Direction::Right) => Direction::Left,
+            (_, other) => other,
+        };
+        (0..self.rotation / 90).fold(direction, |direction, _| direction.clockwise())
+    }
+}
+
+fn slot(controller: &mut ControllerInput, direction: Direction) -> &mut ControllerValue {
+    match direction {
+        Direction::Left => &mut controller.left,
+        Direction::Right => &mut controller.right,
+        Direction::Up => &mut controller.up,
+        Direction::Down => &mut controller.down,
+    }
+}
+
+fn raw_slot(readings: &mut RawSensorReadings, direction: Direction) -> &mut [RawElectrode; 3] {
+    match direction {
+        Direction::Left => &mut readings.left,
+        Direction::Right => &mut readings.right,
+        Direction::Up => &mut readings.up,
+        Direction::Down => &mut readings.down,
+    }
+}
+
+fn name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ファームウェアのコメントの名前どおりの対応（front→up, right→right, back→down, left→left）
+    fn spec_map() -> ChannelMap {
+        ChannelMap {
+            up: [1, 2, 3],
+            right: [4, 5, 6],
+            down: [7, 8, 9],
+            left: [10, 11, 12],
+            ..Default::default()
+        }
+    }
+
+    fn levels(input: &SerialInput) -> [u8; 4] {
+        let c = &input.controller;
+        [
+            c.up.level(),
+            c.right.level(),
+            c.down.level(),
+            c.left.level(),
+        ]
+    }
+
+    #[test]
+    fn test_rotation_and_mirror() {
+        // テスト項目: 回転・反転を指定すると、配信上の方向がトレイ上の方向から回転・反転する
+        // given (前提条件): up=Low, right=Middle, down=High, left=Noinput
+        let values = [0, 1, 0, 0, 1, 1, 0, 1, 1, 1, 0, 0, 0];
+        let rotated = ChannelMap {
+            rotation: 90,
+            ..spec_map()
+        };
+        let mirrored = ChannelMap {
+            mirror: true,
+            ..spec_map()
+        };
+
+        // when (操作):
+        let plain = spec_map().apply(&values).unwrap();
+        let rotated = rotated.apply(&values).unwrap();
+        let mirrored = mirrored.apply(&values).unwrap();
+
+        // then (期待する結果): [up, right, down, left]
+        assert_eq!(levels(&plain), [1, 2, 3, 0]);
+        assert_eq!(levels(&rotated), [0, 1, 2, 3]);
+        assert_eq!(levels(&mirrored), [1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_map() {
+        // テスト項目: 範囲外・重複したフィールド番号と 90 の倍数以外の回転角度はエラーになる
+        // given (前提条件):
+        let out_of_range = ChannelMap {
+            up: [10, 11, 13],
+            ..Default::default()
+        };
+        let duplicated = ChannelMap {
+            button: 12,
+            ..Default::default()
+        };
+        let bad_rotation = ChannelMap {
+            rotation: 45,
+            ..Default::default()
+        };
+
+        // when (操作):
+        let results = [
+            out_of_range.validate(),
+            duplicated.validate(),
+            bad_rotation.validate(),
+        ];
+
+        // then (期待する結果):
+        assert!(ChannelMap::default().validate().is_ok());
+        assert!(spec_map().validate().is_ok());
+        assert!(results.iter().all(Result::is_err), "{results:?}");
+    }
+}
@@ -0,0 +1,200 @@
+//! ファームウェアの行形式ごとのデコーダ
+//!
+//! ファームウェアの版によって出力する行の形式が異なるため、起動時に形式を選んでデコーダを切り替える。
+//! どの形式も、13 フィールド形式と同じ並び（ボタン + front/right/back/left × Low/Middle/High）に値を詰め直してから
+//! チャネルマップで入力状態に変換する。そのため、エラーメッセージのフィールド番号は 13 フィールド形式での位置になる。
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::serial::{
+    channel_map::ChannelMap,
+    input::{
+        FIELD_COUNT, ParseInputError, SerialInput, ensure_binary_controller_value,
+        parse_csv_fields, parse_input_line_with,
+    },
+};
+
+/// プロトタイプのファームウェアが出力するフィールド数（4 方向 × Low/Middle）
+const PROTO8_FIELD_COUNT: usize = 8;
+/// MPR121 のビットマスクで使うビット（センサー 12 個 + ボタン）
+const MPR121_BITMASK_MASK: u32 = (1 << FIELD_COUNT) - 1;
+
+/// ファームウェアの 1 行を入力状態に変換する
+pub trait LineDecoder: Send {
+    /// 1 行をデコードする
+    ///
+    /// 入力状態を含まない行（タッチの通知や空行など）は `Ok(None)` を返す。
+    fn decode(&mut self, line: &str) -> Result<Option<SerialInput>, ParseInputError>;
+}
+
+/// ファームウェアの行形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineFormat {
+    /// 13 フィールドの CSV（`WaterControllerFirmwareMock`）
+    #[default]
+    Csv13,
+    /// 8 フィールドの CSV と "N touched" / "N released" 行（`WaterControllerFirmwareProto`）
+    Proto8,
+    /// MPR121 の `touched()` の値（10 進数または `0x` 付きの 16 進数）
+    Mpr121,
+}
+
+impl LineFormat {
+    /// 形式に対応するデコーダを作る
+    ///
+    /// ## 引数
+    ///
+    /// - `channel_map`: 13 フィールド形式の各フィールドと方向の対応
+    pub fn decoder(self, channel_map: ChannelMap) -> Box<dyn LineDecoder> {
+        match self {
+            Self::Csv13 => Box::new(Csv13Decoder { channel_map }),
+            Self::Proto8 => Box::new(Proto8Decoder { channel_map }),
+            Self::Mpr121 => Box::new(Mpr121Decoder { channel_map }),
+        }
+    }
+}
+
+/// 13 フィールドの CSV（`button,frontLow,frontMiddle,frontHigh,...,leftHigh`）のデコーダ
+pub struct Csv13Decoder {
+    pub channel_map: ChannelMap,
+}
+
+impl LineDecoder for Csv13Decoder {
+    fn decode(&mut self, line: &str) -> Result<Option<SerialInput>, ParseInputError> {
+        parse_input_line_with(line, &self.channel_map).map(Some)
+    }
+}
+
+/// プロトタイプのファームウェアの行のデコーダ
+///
+/// 8 フィールドの CSV は front/right/back/left の順に Low/Middle の 2 つずつで、ボタンと High はない。
+/// ファームウェアはタッチ状態が変わるたびに "N touched" / "N released" 行に続けて CSV を出力し、
+/// 変化がなければ空行を出力するため、CSV 以外の行は読み飛ばす。
+pub struct Proto8Decoder {
+    pub channel_map: ChannelMap,
+}
+
+impl LineDecoder for Proto8Decoder {
+    fn decode(&mut self, line: &str) -> Result<Option<SerialInput>, ParseInputError> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.ends_with(" touched") || trimmed.ends_with(" released") {
+            return Ok(None);
+        }
+
+        let fields = parse_csv_fields(trimmed, PROTO8_FIELD_COUNT)?;
+        let mut values = [0i32; FIELD_COUNT];
+        for (idx, value) in fields.into_iter().enumerate() {
+            // 方向ごとの Low/Middle を 13 フィールド形式の位置に詰め直す（High は 0 のまま）
+            let field_index = 1 + (idx / 2) * 3 + idx % 2;
+            ensure_binary_controller_value(value, field_index)?;
+            values[field_index] = value;
+        }
+        self.channel_map.apply(&values).map(Some)
+    }
+}
+
+/// MPR121 の `touched()` のビットマスクのデコーダ
+///
+/// ビット 0〜11 が 13 フィールド形式のフィールド 1〜12（front Low〜left High）に、ビット 12 がボタンに対応する。
+pub struct Mpr121Decoder {
+    pub channel_map: ChannelMap,
+}
+
+impl LineDecoder for Mpr121Decoder {
+    fn decode(&mut self, line: &str) -> Result<Option<SerialInput>, ParseInputError> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+
+        let parsed = match trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+        {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => trimmed.parse::<u32>(),
+        };
+        let bitmask = parsed.map_err(|source| ParseInputError::ParseInt {
+            index: 0,
+            value: trimmed.to_string(),
+            source,
+        })?;
+        if bitmask & !MPR121_BITMASK_MASK != 0 {
+            return Err(ParseInputError::InvalidBitmask(bitmask));
+        }
+
+        let mut values = [0i32; FIELD_COUNT];
+        values[0] = i32::from(bitmask & (1 << (FIELD_COUNT - 1)) != 0);
+        for (bit, value) in values[1..].iter_mut().enumerate() {
+            *value = i32::from(bitmask & (1 << bit) != 0);
+        }
+        self.channel_map.apply(&values).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::input::parse_input_line;
+
+    #[test]
+    fn test_proto8_skips_touch_notifications() {
+        // テスト項目: プロトタイプ形式では通知行と空行を読み飛ばし、CSV 行を入力状態に変換する
+        // given (前提条件):
+        let mut decoder = LineFormat::Proto8.decoder(ChannelMap::default());
+
+        // when (操作):
+        let touched = decoder.decode("3 touched").unwrap();
+        let empty = decoder.decode("").unwrap();
+        let input = decoder.decode("1,1,0,0,0,0,1,0").unwrap().unwrap();
+
+        // then (期待する結果):
+        assert!(touched.is_none());
+        assert!(empty.is_none());
+        assert_eq!(
+            input,
+            parse_input_line("0,1,1,0,0,0,0,0,0,0,1,0,0").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_proto8_rejects_invalid_combination() {
+        // テスト項目: プロトタイプ形式でも Low なしの Middle はエラーになる
+        // given (前提条件):
+        let mut decoder = Proto8Decoder {
+            channel_map: ChannelMap::default(),
+        };
+
+        // when (操作):
+        let result = decoder.decode("0,1,0,0,0,0,0,0");
+
+        // then (期待する結果):
+        assert!(matches!(
+            result,
+            Err(ParseInputError::InvalidControllerTripleCombination { low_index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_mpr121_bitmask() {
+        // テスト項目: MPR121 のビットマスクが 13 フィールド形式と同じ入力状態に変換される
+        // given (前提条件):
+        let mut decoder = LineFormat::Mpr121.decoder(ChannelMap::default());
+
+        // when (操作):
+        let hex = decoder.decode("0x1e07").unwrap().unwrap();
+        let decimal = decoder.decode("7687").unwrap().unwrap();
+        let out_of_range = decoder.decode("0x2000");
+
+        // then (期待する結果):
+        let expected = parse_input_line("1,1,1,1,0,0,0,0,0,0,1,1,1").unwrap();
+        assert_eq!(hex, expected);
+        assert_eq!(decimal, expected);
+        assert!(matches!(
+            out_of_range,
+            Err(ParseInputError::InvalidBitmask(0x2000))
+        ));
+    }
+}
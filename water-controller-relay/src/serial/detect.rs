@@ -0,0 +1,296 @@
+//! シリアルポートの自動検出
+//!
+//! 接続されているシリアルポートから、USB のベンダー ID・プロダクト ID・シリアル番号・ポート名のパターンが
+//! 一致するものを選ぶ。抜き差しでポート名が変わっても、再接続のたびに検出し直すことで追従できる。
+
+use std::{fmt, io};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serialport::{SerialPortInfo, SerialPortType};
+use tracing::{debug, warn};
+
+/// 検出するシリアルポートの条件
+///
+/// 指定した条件をすべて満たすポートを選ぶ。VID / PID / シリアル番号を指定した場合は USB ポートのみが対象になる。
+///
+/// ## JSON 例
+///
+/// ```json
+/// { "vid": "0x2341", "pid": "0x0043", "name": "/dev/cu.usbmodem*" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct PortMatcher {
+    /// USB ベンダー ID
+    #[serde(
+        serialize_with = "serialize_usb_id",
+        deserialize_with = "deserialize_usb_id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vid: Option<u16>,
+    /// USB プロダクト ID
+    #[serde(
+        serialize_with = "serialize_usb_id",
+        deserialize_with = "deserialize_usb_id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pid: Option<u16>,
+    /// USB シリアル番号
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+    /// ポート名のパターン（`*` は任意の文字列、`?` は任意の 1 文字）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl PortMatcher {
+    /// ポートが条件をすべて満たすか
+    pub fn matches(&self, port: &SerialPortInfo) -> bool {
+        if let Some(pattern) = &self.name
+            && !glob_match(pattern, &port.port_name)
+        {
+            return false;
+        }
+        if self.vid.is_none() && self.pid.is_none() && self.serial_number.is_none() {
+            return true;
+        }
+
+        let SerialPortType::UsbPort(usb) = &port.port_type else {
+            return false;
+        };
+        self.vid.is_none_or(|vid| vid == usb.vid)
+            && self.pid.is_none_or(|pid| pid == usb.pid)
+            && self
+                .serial_number
+                .as_ref()
+                .is_none_or(|serial| usb.serial_number.as_ref() == Some(serial))
+    }
+
+    /// 条件に一致するポートを選ぶ
+    ///
+    /// 複数一致した場合は、macOS の `/dev/cu.*`（着信を待たずに開ける）を優先し、次に名前順で最初のものを選ぶ。
+    pub fn select<'a>(&self, ports: &'a [SerialPortInfo]) -> Option<&'a SerialPortInfo> {
+        let mut candidates: Vec<&SerialPortInfo> =
+            ports.iter().filter(|port| self.matches(port)).collect();
+        candidates.sort_by_key(|port| (!port.port_name.contains("/cu."), port.port_name.clone()));
+        if candidates.len() > 1 {
+            warn!(
+                candidates = ?candidates.iter().map(|p| &p.port_name).collect::<Vec<_>>(),
+                "Multiple serial ports match, using the first one"
+            );
+        }
+        candidates.first().copied()
+    }
+
+    /// 接続されているシリアルポートから条件に一致するポートを検出し、ポート名を返す
+    pub fn detect(&self) -> io::Result<String> {
+        let ports = serialport::available_ports().map_err(io::Error::other)?;
+        debug!(count = ports.len(), matcher = %self, "Detecting serial port");
+        self.select(&ports)
+            .map(|port| port.port_name.clone())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no serial port matches {self}"),
+                )
+            })
+    }
+}
+
+impl fmt::Display for PortMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions = Vec::new();
+        if let Some(vid) = self.vid {
+            conditions.push(format!("vid={vid:04x}"));
+        }
+        if let Some(pid) = self.pid {
+            conditions.push(format!("pid={pid:04x}"));
+        }
+        if let Some(serial) = &self.serial_number {
+            conditions.push(format!("serial={serial}"));
+        }
+        if let Some(name) = &self.name {
+            conditions.push(format!("name={name}"));
+        }
+        if conditions.is_empty() {
+            write!(f, "any port")
+        } else {
+            write!(f, "{}", conditions.join(" "))
+        }
+    }
+}
+
+/// USB の ID（16 進数。`0x` は省略可）をパースする
+pub fn parse_usb_id(value: &str) -> Result<u16, String> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u16::from_str_radix(digits, 16).map_err(|e| format!("invalid USB id '{value}': {e}"))
+}
+
+fn serialize_usb_id<S: Serializer>(id: &Option<u16>, serializer: S) -> Result<S::Ok, S::Error> {
+    match id {
+        Some(id) => serializer.serialize_str(&format!("0x{id:04x}")),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// 16 進数の文字列（`"0x2341"`）または数値（`9025`）を受け付ける
+fn deserialize_usb_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum UsbId {
+        Number(u16),
+        Text(String),
+    }
+
+    match Option::<UsbId>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(UsbId::Number(id)) => Ok(Some(id)),
+        Some(UsbId::Text(text)) => parse_usb_id(&text).map(Some).map_err(de::Error::custom),
+    }
+}
+
+/// `*`（任意の文字列）と `?`（任意の 1 文字）だけに対応した簡易的なパターンマッチ
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // 直前の `*` の位置と、その `*` に対応させたテキストの位置
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use serialport::UsbPortInfo;
+
+    use super::*;
+
+    fn usb_port(name: &str, vid: u16, pid: u16, serial: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid,
+                serial_number: serial.map(str::to_string),
+                manufacturer: Some("Arduino (www.arduino.cc)".to_string()),
+                product: None,
+            }),
+        }
+    }
+
+    fn ports() -> Vec<SerialPortInfo> {
+        vec![
+            SerialPortInfo {
+                port_name: "/dev/cu.Bluetooth-Incoming-Port".to_string(),
+                port_type: SerialPortType::PciPort,
+            },
+            usb_port("/dev/tty.usbmodem2101", 0x2341, 0x0043, Some("A")),
+            usb_port("/dev/cu.usbmodem2101", 0x2341, 0x0043, Some("A")),
+            usb_port("/dev/cu.usbserial-10", 0x1a86, 0x7523, None),
+        ]
+    }
+
+    #[test]
+    fn test_select_by_vid_pid_prefers_callout_device() {
+        // テスト項目: VID / PID で一致したポートのうち /dev/cu.* が選ばれる
+        // given (前提条件):
+        let matcher = PortMatcher {
+            vid: Some(0x2341),
+            pid: Some(0x0043),
+            ..Default::default()
+        };
+
+        // when (操作):
+        let ports = ports();
+        let selected = matcher.select(&ports);
+
+        // then (期待する結果):
+        assert_eq!(selected.unwrap().port_name, "/dev/cu.usbmodem2101");
+    }
+
+    #[test]
+    fn test_serial_number_and_name_glob() {
+        // テスト項目: シリアル番号とポート名のパターンで絞り込める
+        // given (前提条件):
+        let by_serial = PortMatcher {
+            serial_number: Some("B".to_string()),
+            ..Default::default()
+        };
+        let by_name = PortMatcher {
+            name: Some("/dev/cu.usbserial-*".to_string()),
+            ..Default::default()
+        };
+
+        // when (操作):
+        let ports = ports();
+        let serial_result = by_serial.select(&ports);
+        let name_result = by_name.select(&ports);
+
+        // then (期待する結果):
+        assert!(serial_result.is_none());
+        assert_eq!(name_result.unwrap().port_name, "/dev/cu.usbserial-10");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        // テスト項目: `*` と `?` を含むパターンが一致判定される
+        // given (前提条件):
+        let cases = [
+            ("/dev/cu.usbmodem*", "/dev/cu.usbmodem1101", true),
+            ("/dev/tty*", "/dev/cu.usbmodem1101", false),
+            ("COM?", "COM3", true),
+            ("COM?", "COM10", false),
+            ("*modem*01", "/dev/cu.usbmodem1101", true),
+        ];
+
+        // when (操作):
+        let results: Vec<bool> = cases
+            .iter()
+            .map(|(pattern, text, _)| glob_match(pattern, text))
+            .collect();
+
+        // then (期待する結果):
+        let expected: Vec<bool> = cases.iter().map(|(_, _, expected)| *expected).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_usb_id_accepts_hex_string_and_number() {
+        // テスト項目: VID / PID は 16 進数の文字列と数値のどちらでも指定できる
+        // given (前提条件):
+        let json = r#"{"vid": "0x2341", "pid": 67}"#;
+
+        // when (操作):
+        let matcher: PortMatcher = serde_json::from_str(json).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(matcher.vid, Some(0x2341));
+        assert_eq!(matcher.pid, Some(0x0043));
+        assert_eq!(parse_usb_id("2341"), Ok(0x2341));
+        assert!(parse_usb_id("zz").is_err());
+    }
+}
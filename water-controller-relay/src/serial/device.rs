@@ -0,0 +1,470 @@
+//! シリアルデバイスの一覧
+//!
+//! 接続可能なシリアルポートを、テキスト・JSON・表のいずれかの形式で出力する。
+//! `--probe` を指定した場合は各ポートを短時間開き、ファームウェア形式の行を出力しているかを調べる。
+
+use std::{
+    fmt,
+    io::{self, Read},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serialport::{SerialPortInfo, SerialPortType};
+
+use crate::serial::{channel_map::ChannelMap, decoder::LineFormat};
+
+/// ポートを調べる時間の既定値（Arduino は開いた直後にリセットされるため、最初の行まで 1〜2 秒かかる）
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 2500;
+/// 調べた結果に含める受信行の例の最大数
+const PROBE_SAMPLE_LINES: usize = 3;
+
+/// デバイス一覧の出力形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DeviceListFormat {
+    /// 1 ポート 1 行のテキスト
+    #[default]
+    Text,
+    /// JSON 配列
+    Json,
+    /// 列を揃えた表
+    Table,
+}
+
+/// ポートを調べる設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeOptions {
+    /// ボーレート
+    pub baud_rate: u32,
+    /// ファームウェアの行形式
+    pub line_format: LineFormat,
+    /// フィールドと方向の対応
+    pub channel_map: ChannelMap,
+    /// 行を受信する時間
+    pub timeout: Duration,
+}
+
+/// シリアルポートの情報
+///
+/// ## JSON 例
+///
+/// ```json
+/// {
+///   "name": "/dev/cu.usbmodem1101",
+///   "type": "usb",
+///   "vid": "0x2341",
+///   "pid": "0x0043",
+///   "manufacturer": "Arduino (www.arduino.cc)",
+///   "product": null,
+///   "serialNumber": "0353534333535160C0A3",
+///   "probe": { "status": "ok", "linesRead": 18, "linesParsed": 18, "sample": ["0,0,0,0,0,0,0,0,0,0,0,0,0"] }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub port_type: &'static str,
+    pub vid: Option<String>,
+    pub pid: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    /// `--probe` を指定した場合のみ出力する
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<ProbeResult>,
+}
+
+impl From<&SerialPortInfo> for DeviceInfo {
+    fn from(info: &SerialPortInfo) -> Self {
+        let mut device = Self {
+            name: info.port_name.clone(),
+            port_type: match info.port_type {
+                SerialPortType::UsbPort(_) => "usb",
+                SerialPortType::PciPort => "pci",
+                SerialPortType::BluetoothPort => "bluetooth",
+                SerialPortType::Unknown => "unknown",
+            },
+            vid: None,
+            pid: None,
+            manufacturer: None,
+            product: None,
+            serial_number: None,
+            probe: None,
+        };
+        if let SerialPortType::UsbPort(usb) = &info.port_type {
+            device.vid = Some(format!("0x{:04x}", usb.vid));
+            device.pid = Some(format!("0x{:04x}", usb.pid));
+            device.manufacturer = usb.manufacturer.clone();
+            device.product = usb.product.clone();
+            device.serial_number = usb.serial_number.clone();
+        }
+        device
+    }
+}
+
+/// ポートを調べた結果の分類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProbeStatus {
+    /// ファームウェア形式の行を受信した
+    Ok,
+    /// 行は受信したが、ファームウェア形式ではなかった
+    Unparseable,
+    /// 時間内に 1 行も受信しなかった
+    NoData,
+    /// ポートを開けなかった
+    Error,
+}
+
+impl fmt::Display for ProbeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Ok => "ok",
+            Self::Unparseable => "unparseable",
+            Self::NoData => "no-data",
+            Self::Error => "error",
+        };
+        write!(f, "{text}")
+    }
+}
+
+/// ポートを調べた結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub status: ProbeStatus,
+    /// 受信した行数
+    pub lines_read: usize,
+    /// 入力状態にデコードできた行数
+    pub lines_parsed: usize,
+    /// 受信した行の例（先頭から最大 3 行）
+    pub sample: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ProbeResult {
+    /// 受信した行から結果をまとめる
+    ///
+    /// 入力状態を含まない行（タッチの通知など）は数えない。
+    pub fn from_lines(lines: &[String], line_format: LineFormat, channel_map: ChannelMap) -> Self {
+        let mut decoder = line_format.decoder(channel_map);
+        let mut read = Vec::new();
+        let mut lines_parsed = 0;
+        for line in lines {
+            match decoder.decode(line) {
+                Ok(None) => continue,
+                Ok(Some(_)) => lines_parsed += 1,
+                Err(_) => {}
+            }
+            read.push(line);
+        }
+        let lines = read;
+        let status = if lines.is_empty() {
+            ProbeStatus::NoData
+        } else if lines_parsed == 0 {
+            ProbeStatus::Unparseable
+        } else {
+            ProbeStatus::Ok
+        };
+        Self {
+            status,
+            lines_read: lines.len(),
+            lines_parsed,
+            sample: lines
+                .iter()
+                .take(PROBE_SAMPLE_LINES)
+                .map(|line| line.to_string())
+                .collect(),
+            error: None,
+        }
+    }
+
+    fn from_error(err: impl fmt::Display) -> Self {
+        Self {
+            status: ProbeStatus::Error,
+            lines_read: 0,
+            lines_parsed: 0,
+            sample: Vec::new(),
+            error: Some(err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ProbeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(error) => write!(f, "{} ({error})", self.status),
+            None => write!(
+                f,
+                "{} ({}/{} lines parsed)",
+                self.status, self.lines_parsed, self.lines_read
+            ),
+        }
+    }
+}
+
+/// ポートを開き、`options.timeout` の間に受信した行を調べる
+///
+/// 開いた直後の行は途中から受信している可能性があるため、最初の改行までは読み捨てる。
+pub fn probe_port(port_name: &str, options: ProbeOptions) -> ProbeResult {
+    let mut port = match serialport::new(port_name, options.baud_rate)
+        .timeout(Duration::from_millis(100))
+        .open()
+    {
+        Ok(port) => port,
+        Err(err) => return ProbeResult::from_error(err),
+    };
+
+    let deadline = Instant::now() + options.timeout;
+    let mut lines = Vec::new();
+    let mut buf = Vec::new();
+    let mut synced = false;
+    let mut byte = [0u8; 1];
+    while Instant::now() < deadline {
+        match port.read(&mut byte) {
+            Ok(0) => continue,
+            Ok(_) => match byte[0] {
+                b'\n' => {
+                    if synced {
+                        lines.push(String::from_utf8_lossy(&buf).into_owned());
+                    }
+                    synced = true;
+                    buf.clear();
+                }
+                b'\r' => continue,
+                other => buf.push(other),
+            },
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => continue,
+            Err(err) => return ProbeResult::from_error(err),
+        }
+    }
+    ProbeResult::from_lines(&lines, options.line_format, options.channel_map)
+}
+
+/// 接続可能なシリアルポートを列挙する
+///
+/// ## 引数
+///
+/// - `probe`: 指定した場合は各ポートを並行して開き、ファームウェア形式の行を出力しているかを調べる
+pub fn collect_devices(probe: Option<ProbeOptions>) -> io::Result<Vec<DeviceInfo>> {
+    let ports = serialport::available_ports().map_err(io::Error::other)?;
+    let mut devices: Vec<DeviceInfo> = ports.iter().map(DeviceInfo::from).collect();
+
+    if let Some(options) = probe {
+        let results: Vec<ProbeResult> = thread::scope(|scope| {
+            let handles: Vec<_> = devices
+                .iter()
+                .map(|device| scope.spawn(move || probe_port(&device.name, options)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| ProbeResult::from_error("probe thread panicked"))
+                })
+                .collect()
+        });
+        for (device, result) in devices.iter_mut().zip(results) {
+            device.probe = Some(result);
+        }
+    }
+
+    Ok(devices)
+}
+
+/// 接続可能なシリアルポートを指定した形式で標準出力に出力する
+pub fn list_serial_devices(
+    format: DeviceListFormat,
+    probe: Option<ProbeOptions>,
+) -> io::Result<()> {
+    let devices = collect_devices(probe)?;
+
+    match format {
+        DeviceListFormat::Text => {
+            println!("Listing available serial ports:");
+            if devices.is_empty() {
+                println!("No serial ports detected");
+                println!("(ポートが見つかりません)");
+            }
+            for device in &devices {
+                println!("{}", render_text(device));
+            }
+        }
+        DeviceListFormat::Json => {
+            let json = serde_json::to_string_pretty(&devices).map_err(io::Error::other)?;
+            println!("{json}");
+        }
+        DeviceListFormat::Table => print!("{}", render_table(&devices)),
+    }
+
+    Ok(())
+}
+
+fn render_text(device: &DeviceInfo) -> String {
+    let mut text = format!("name: {}, type={}", device.name, device.port_type);
+    if let (Some(vid), Some(pid)) = (&device.vid, &device.pid) {
+        text.push_str(&format!(", vid={vid}, pid={pid}"));
+    }
+    for (label, value) in [
+        ("manufacturer", &device.manufacturer),
+        ("product", &device.product),
+        ("serial", &device.serial_number),
+    ] {
+        if let Some(value) = value {
+            text.push_str(&format!(", {label}={value}"));
+        }
+    }
+    if let Some(probe) = &device.probe {
+        text.push_str(&format!(", probe={probe}"));
+    }
+    text
+}
+
+/// 列の幅を揃えた表にする（値がない項目は `-`）
+fn render_table(devices: &[DeviceInfo]) -> String {
+    let probed = devices.iter().any(|device| device.probe.is_some());
+    let mut header = vec![
+        "NAME",
+        "TYPE",
+        "VID",
+        "PID",
+        "MANUFACTURER",
+        "PRODUCT",
+        "SERIAL",
+    ];
+    if probed {
+        header.push("PROBE");
+    }
+
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let mut rows: Vec<Vec<String>> = vec![header.iter().map(|h| h.to_string()).collect()];
+    for device in devices {
+        let mut row = vec![
+            device.name.clone(),
+            device.port_type.to_string(),
+            or_dash(&device.vid),
+            or_dash(&device.pid),
+            or_dash(&device.manufacturer),
+            or_dash(&device.product),
+            or_dash(&device.serial_number),
+        ];
+        if probed {
+            row.push(or_dash(&device.probe.as_ref().map(ToString::to_string)));
+        }
+        rows.push(row);
+    }
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|col| {
+            rows.iter()
+                .map(|row| row[col].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serialport::UsbPortInfo;
+
+    use super::*;
+
+    fn arduino() -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: "/dev/cu.usbmodem1101".to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x2341,
+                pid: 0x0043,
+                serial_number: Some("0353534333535160C0A3".to_string()),
+                manufacturer: Some("Arduino (www.arduino.cc)".to_string()),
+                product: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_device_info_json() {
+        // テスト項目: USB ポートの情報が VID / PID を 16 進数の文字列にした JSON になる
+        // given (前提条件):
+        let info = arduino();
+
+        // when (操作):
+        let json = serde_json::to_value(DeviceInfo::from(&info)).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "/dev/cu.usbmodem1101",
+                "type": "usb",
+                "vid": "0x2341",
+                "pid": "0x0043",
+                "manufacturer": "Arduino (www.arduino.cc)",
+                "product": null,
+                "serialNumber": "0353534333535160C0A3"
+            })
+        );
+    }
+
+    #[test]
+    fn test_probe_result_from_lines() {
+        // テスト項目: 受信した行がファームウェア形式かどうかで結果が分類される
+        // given (前提条件):
+        let firmware = vec!["0,0,0,0,0,0,0,0,0,0,0,0,0".to_string(), "boot".to_string()];
+        let other = vec!["hello".to_string()];
+
+        // when (操作):
+        let ok = ProbeResult::from_lines(&firmware, LineFormat::Csv13, ChannelMap::default());
+        let unparseable = ProbeResult::from_lines(&other, LineFormat::Csv13, ChannelMap::default());
+        let no_data = ProbeResult::from_lines(&[], LineFormat::Csv13, ChannelMap::default());
+
+        // then (期待する結果):
+        assert_eq!(ok.status, ProbeStatus::Ok);
+        assert_eq!((ok.lines_read, ok.lines_parsed), (2, 1));
+        assert_eq!(unparseable.status, ProbeStatus::Unparseable);
+        assert_eq!(no_data.status, ProbeStatus::NoData);
+    }
+
+    #[test]
+    fn test_render_table_aligns_columns() {
+        // テスト項目: 表の列が揃い、値がない項目は `-` になる
+        // given (前提条件):
+        let mut usb = DeviceInfo::from(&arduino());
+        usb.probe = Some(ProbeResult::from_lines(
+            &["0,0,0,0,0,0,0,0,0,0,0,0,0".to_string()],
+            LineFormat::Csv13,
+            ChannelMap::default(),
+        ));
+        let pci = DeviceInfo::from(&SerialPortInfo {
+            port_name: "/dev/ttyS0".to_string(),
+            port_type: SerialPortType::PciPort,
+        });
+
+        // when (操作):
+        let table = render_table(&[usb, pci]);
+
+        // then (期待する結果):
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("NAME                  TYPE  VID"));
+        assert!(lines[1].ends_with("ok (1/1 lines parsed)"));
+        assert!(lines[2].starts_with("/dev/ttyS0            pci   -"));
+    }
+}
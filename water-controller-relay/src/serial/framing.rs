@@ -0,0 +1,398 @@
+//! シリアル通信のバイナリフレーム
+//!
+//! テキストの行だけでは、途中でバイトが欠けても気づけず、誤った値を読み取ることがある。
+//! バイナリフレームでは 1 行をフレームに包み、CRC で破損を、シーケンス番号で取りこぼしを検出する。
+//!
+//! ## フレーム形式
+//!
+//! ```text
+//! 0xA5 | seq (u8) | len (u8) | payload (len バイト) | crc (u16, ビッグエンディアン)
+//! ```
+//!
+//! - `payload` は設定した行形式（`csv13` など）の 1 行（改行なし）
+//! - `crc` は `seq` / `len` / `payload` の CRC-16/CCITT-FALSE（多項式 0x1021、初期値 0xFFFF）
+//! - `seq` はフレームごとに 1 ずつ増やし、255 の次は 0 に戻す
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// フレームの先頭を示す同期バイト
+pub const SYNC_BYTE: u8 = 0xA5;
+/// 同期バイト・シーケンス番号・長さのバイト数
+const HEADER_LEN: usize = 3;
+/// CRC のバイト数
+const CRC_LEN: usize = 2;
+/// シーケンス番号の差がこれ以上の場合は、取りこぼしではなく巻き戻り（重複・順序の入れ替わり・ファームウェアの再起動）とみなす
+const SEQUENCE_RESET_THRESHOLD: u8 = 128;
+
+/// シリアル通信の伝送方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framing {
+    /// 改行区切りのテキスト
+    #[default]
+    Text,
+    /// 同期バイト・シーケンス番号・CRC 付きのバイナリフレーム
+    Binary,
+}
+
+/// バイナリフレームの受信状況の累計
+///
+/// 入力源を開き直しても累計し続けるため、ハブで共有する。
+#[derive(Debug, Default)]
+pub struct FrameMetrics {
+    frames: AtomicU64,
+    corrupt_frames: AtomicU64,
+    dropped_bytes: AtomicU64,
+    sequence_gaps: AtomicU64,
+    lost_frames: AtomicU64,
+    sequence_resets: AtomicU64,
+    invalid_payloads: AtomicU64,
+}
+
+/// バイナリフレームの受信状況
+///
+/// ## JSON 例
+///
+/// ```json
+/// { "frames": 1200, "corruptFrames": 1, "droppedBytes": 7, "sequenceGaps": 2, "lostFrames": 3, "sequenceResets": 0, "invalidPayloads": 0 }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameMetricsSnapshot {
+    /// 正しく受信したフレーム数
+    pub frames: u64,
+    /// CRC が一致しなかったフレーム数
+    pub corrupt_frames: u64,
+    /// 同期バイトを探すために読み捨てたバイト数
+    pub dropped_bytes: u64,
+    /// シーケンス番号が飛んだ回数
+    pub sequence_gaps: u64,
+    /// シーケンス番号の飛びから推定した、取りこぼしたフレーム数
+    pub lost_frames: u64,
+    /// シーケンス番号が重複した・巻き戻った回数（取りこぼしには数えない）
+    pub sequence_resets: u64,
+    /// CRC は一致したが、ペイロードが UTF-8 でなかったため読み捨てたフレーム数
+    pub invalid_payloads: u64,
+}
+
+impl FrameMetrics {
+    pub fn snapshot(&self) -> FrameMetricsSnapshot {
+        FrameMetricsSnapshot {
+            frames: self.frames.load(Ordering::Relaxed),
+            corrupt_frames: self.corrupt_frames.load(Ordering::Relaxed),
+            dropped_bytes: self.dropped_bytes.load(Ordering::Relaxed),
+            sequence_gaps: self.sequence_gaps.load(Ordering::Relaxed),
+            lost_frames: self.lost_frames.load(Ordering::Relaxed),
+            sequence_resets: self.sequence_resets.load(Ordering::Relaxed),
+            invalid_payloads: self.invalid_payloads.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 受信したフレーム
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub seq: u8,
+    pub payload: Vec<u8>,
+}
+
+/// 受信したバイト列からフレームを取り出す
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    last_seq: Option<u8>,
+    metrics: Arc<FrameMetrics>,
+}
+
+impl FrameDecoder {
+    pub fn new(metrics: Arc<FrameMetrics>) -> Self {
+        Self {
+            buf: Vec::new(),
+            last_seq: None,
+            metrics,
+        }
+    }
+
+    /// 受信したバイト列を追加する
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// 完全なフレームが揃っていれば取り出す
+    ///
+    /// CRC が一致しない場合は同期バイトだけを読み捨てて、次の同期バイトから探し直す
+    /// （破損したフレームの途中に正しいフレームが始まっている場合に備える）。
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            // 同期バイトまでを読み捨てる
+            let start = match self.buf.iter().position(|b| *b == SYNC_BYTE) {
+                Some(start) => start,
+                None => {
+                    self.drop_bytes(self.buf.len());
+                    return None;
+                }
+            };
+            self.drop_bytes(start);
+
+            if self.buf.len() < HEADER_LEN {
+                return None;
+            }
+            let len = usize::from(self.buf[2]);
+            let frame_len = HEADER_LEN + len + CRC_LEN;
+            if self.buf.len() < frame_len {
+                return None;
+            }
+
+            let body = &self.buf[1..HEADER_LEN + len];
+            let expected = u16::from_be_bytes([self.buf[frame_len - 2], self.buf[frame_len - 1]]);
+            if crc16(body) != expected {
+                self.metrics.corrupt_frames.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    seq = self.buf[1],
+                    len, "Dropped corrupt serial frame (CRC mismatch)"
+                );
+                self.drop_bytes(1);
+                continue;
+            }
+
+            let frame = Frame {
+                seq: self.buf[1],
+                payload: self.buf[HEADER_LEN..HEADER_LEN + len].to_vec(),
+            };
+            self.buf.drain(..frame_len);
+            self.record_sequence(frame.seq);
+            return Some(frame);
+        }
+    }
+
+    /// 完全なフレームが揃っていれば、ペイロードを 1 行として取り出す
+    ///
+    /// ペイロードが UTF-8 でないフレームは、フレームの区切りは正しいので接続を開き直さずに読み捨てる。
+    pub fn next_line(&mut self) -> Option<String> {
+        loop {
+            let frame = self.next_frame()?;
+            match String::from_utf8(frame.payload) {
+                Ok(line) => return Some(line),
+                Err(err) => {
+                    self.metrics
+                        .invalid_payloads
+                        .fetch_add(1, Ordering::Relaxed);
+                    warn!(
+                        seq = frame.seq,
+                        error = %err.utf8_error(),
+                        "Dropped serial frame with non-UTF-8 payload"
+                    );
+                }
+            }
+        }
+    }
+
+    fn drop_bytes(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.metrics
+            .dropped_bytes
+            .fetch_add(count as u64, Ordering::Relaxed);
+        self.buf.drain(..count);
+    }
+
+    /// シーケンス番号から取りこぼしを数える
+    ///
+    /// 番号が同じか、差が [`SEQUENCE_RESET_THRESHOLD`] 以上離れている場合は、重複したフレームか
+    /// ファームウェアの再起動で番号が 0 に戻ったとみなし、取りこぼしには数えずにその番号から数え直す。
+    fn record_sequence(&mut self, seq: u8) {
+        self.metrics.frames.fetch_add(1, Ordering::Relaxed);
+        if let Some(last) = self.last_seq {
+            let lost = seq.wrapping_sub(last.wrapping_add(1));
+            if lost >= SEQUENCE_RESET_THRESHOLD {
+                self.metrics.sequence_resets.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    last,
+                    actual = seq,
+                    "Serial frame sequence went back (duplicate frame or firmware restart)"
+                );
+            } else if lost > 0 {
+                self.metrics.sequence_gaps.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .lost_frames
+                    .fetch_add(u64::from(lost), Ordering::Relaxed);
+                warn!(
+                    expected = last.wrapping_add(1),
+                    actual = seq,
+                    lost,
+                    "Serial frame sequence gap"
+                );
+            }
+        }
+        self.last_seq = Some(seq);
+    }
+}
+
+/// 1 行をフレームに包む
+///
+/// ファームウェアと同じ形式のフレームを、シミュレータやテストで生成するために使う。
+/// `payload` が 255 バイトを超える場合は `None` を返す。
+pub fn encode_frame(seq: u8, payload: &[u8]) -> Option<Vec<u8>> {
+    let len = u8::try_from(payload.len()).ok()?;
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len() + CRC_LEN);
+    frame.extend_from_slice(&[SYNC_BYTE, seq, len]);
+    frame.extend_from_slice(payload);
+    let crc = crc16(&frame[1..]);
+    frame.extend_from_slice(&crc.to_be_bytes());
+    Some(frame)
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |mut crc, byte| {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &[u8] = b"0,1,0,0,0,0,0,0,0,0,0,0,0";
+
+    #[test]
+    fn test_crc16_check_value() {
+        // テスト項目: CRC-16/CCITT-FALSE の検査値（"123456789" → 0x29B1）と一致する
+        // given (前提条件):
+        let input = b"123456789";
+
+        // when (操作):
+        let crc = crc16(input);
+
+        // then (期待する結果):
+        assert_eq!(crc, 0x29B1);
+    }
+
+    #[test]
+    fn test_decoder_resyncs_after_noise_and_corruption() {
+        // テスト項目: 雑音と破損したフレームを読み捨て、後続の正しいフレームを取り出す
+        // given (前提条件):
+        let metrics = Arc::new(FrameMetrics::default());
+        let mut decoder = FrameDecoder::new(metrics.clone());
+        let mut corrupt = encode_frame(1, LINE).unwrap();
+        corrupt[5] ^= 0xFF;
+        let mut bytes = vec![0x00, 0x42];
+        bytes.extend(encode_frame(0, LINE).unwrap());
+        bytes.extend(corrupt);
+        bytes.extend(encode_frame(2, LINE).unwrap());
+
+        // when (操作): 1 バイトずつ受信する
+        let mut frames = Vec::new();
+        for byte in bytes {
+            decoder.push(&[byte]);
+            while let Some(frame) = decoder.next_frame() {
+                frames.push(frame);
+            }
+        }
+
+        // then (期待する結果):
+        assert_eq!(frames.iter().map(|f| f.seq).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(frames[1].payload, LINE);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.frames, 2);
+        assert_eq!(snapshot.corrupt_frames, 1);
+        assert!(snapshot.dropped_bytes >= 2);
+        assert_eq!((snapshot.sequence_gaps, snapshot.lost_frames), (1, 1));
+    }
+
+    #[test]
+    fn test_next_line_skips_frame_with_non_utf8_payload() {
+        // テスト項目: ペイロードが UTF-8 でないフレームは数えて読み捨て、後続のフレームを行として取り出す
+        // given (前提条件):
+        let metrics = Arc::new(FrameMetrics::default());
+        let mut decoder = FrameDecoder::new(metrics.clone());
+        decoder.push(&encode_frame(0, &[0xFF, 0xFE]).unwrap());
+        decoder.push(&encode_frame(1, LINE).unwrap());
+
+        // when (操作):
+        let line = decoder.next_line();
+        let rest = decoder.next_line();
+
+        // then (期待する結果):
+        assert_eq!(line.as_deref().map(str::as_bytes), Some(LINE));
+        assert_eq!(rest, None);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.frames, 2);
+        assert_eq!(snapshot.invalid_payloads, 1);
+        assert_eq!(snapshot.corrupt_frames, 0);
+    }
+
+    #[test]
+    fn test_duplicate_sequence_is_not_counted_as_lost() {
+        // テスト項目: 同じシーケンス番号のフレームが続いても、取りこぼしではなく巻き戻りとして数える
+        // given (前提条件):
+        let metrics = Arc::new(FrameMetrics::default());
+        let mut decoder = FrameDecoder::new(metrics.clone());
+
+        // when (操作):
+        for seq in [10, 11, 11, 12] {
+            decoder.push(&encode_frame(seq, LINE).unwrap());
+            assert!(decoder.next_frame().is_some());
+        }
+
+        // then (期待する結果):
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.frames, 4);
+        assert_eq!((snapshot.sequence_gaps, snapshot.lost_frames), (0, 0));
+        assert_eq!(snapshot.sequence_resets, 1);
+    }
+
+    #[test]
+    fn test_sequence_reset_after_firmware_restart_is_not_counted_as_lost() {
+        // テスト項目: ファームウェアの再起動でシーケンス番号が 0 に戻っても取りこぼしに数えず、0 から数え直す
+        // given (前提条件):
+        let metrics = Arc::new(FrameMetrics::default());
+        let mut decoder = FrameDecoder::new(metrics.clone());
+
+        // when (操作):
+        for seq in [40, 41, 0, 1, 3] {
+            decoder.push(&encode_frame(seq, LINE).unwrap());
+            assert!(decoder.next_frame().is_some());
+        }
+
+        // then (期待する結果): 再起動後の 1 → 3 の飛びだけを取りこぼしとして数える
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.sequence_resets, 1);
+        assert_eq!((snapshot.sequence_gaps, snapshot.lost_frames), (1, 1));
+    }
+
+    #[test]
+    fn test_sequence_wraps_without_gap() {
+        // テスト項目: シーケンス番号が 255 から 0 に戻っても飛びとして数えない
+        // given (前提条件):
+        let metrics = Arc::new(FrameMetrics::default());
+        let mut decoder = FrameDecoder::new(metrics.clone());
+
+        // when (操作):
+        for seq in [254, 255, 0, 3] {
+            decoder.push(&encode_frame(seq, LINE).unwrap());
+            assert!(decoder.next_frame().is_some());
+        }
+
+        // then (期待する結果):
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.frames, 4);
+        assert_eq!((snapshot.sequence_gaps, snapshot.lost_frames), (1, 2));
+        assert_eq!(snapshot.dropped_bytes, 0);
+    }
+}
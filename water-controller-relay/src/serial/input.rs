@@ -1,30 +1,61 @@
-use std::{error::Error, fmt, io, num::ParseIntError};
+use std::{error::Error, fmt, num::ParseIntError};
 
+use schemars::JsonSchema;
 use serde::Serialize;
 
-const FIELD_COUNT: usize = 13;
+use crate::serial::channel_map::ChannelMap;
 
-pub fn list_serial_devices() -> io::Result<()> {
-    println!("Listing available serial ports:");
-    let ports = serialport::available_ports().map_err(io::Error::other)?;
+/// ファームウェアが出力する行のフィールド数（ボタン 1 + 4 方向 × Low/Middle/High）
+pub(crate) const FIELD_COUNT: usize = 13;
+/// 電極の数（ボタン以外のフィールド数）
+pub(crate) const ELECTRODE_COUNT: usize = FIELD_COUNT - 1;
 
-    if ports.is_empty() {
-        println!("No serial ports detected");
-        println!("(ポートが見つかりません)");
-    } else {
-        for p in ports {
-            println!("name: {}, type={:?}", p.port_name, p.port_type);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialInput {
+    pub button: ButtonInput,
+    pub controller: ControllerInput,
+    /// 電極ごとの静電容量（拡張形式の行を受信した場合のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<RawSensorReadings>,
+}
+
+impl SerialInput {
+    /// 何も入力されていない状態（ボタン未押下・全方向 Noinput）
+    pub fn idle() -> Self {
+        Self {
+            button: ButtonInput { is_pushed: false },
+            controller: ControllerInput {
+                left: ControllerValue::Noinput(0),
+                right: ControllerValue::Noinput(0),
+                up: ControllerValue::Noinput(0),
+                down: ControllerValue::Noinput(0),
+            },
+            raw: None,
         }
     }
+}
 
-    Ok(())
+/// 電極 1 つの静電容量（MPR121 の filtered data と baseline value）
+///
+/// 水に触れると `filtered` が `baseline` より小さくなり、その差が大きいほど強く触れている。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RawElectrode {
+    pub filtered: u16,
+    pub baseline: u16,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// 方向ごとの Low/Middle/High の電極の静電容量
+///
+/// チャネルマップの対応・回転・反転を反映した、配信上の方向ごとに並べる。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SerialInput {
-    pub button: ButtonInput,
-    pub controller: ControllerInput,
+pub struct RawSensorReadings {
+    pub left: [RawElectrode; 3],
+    pub right: [RawElectrode; 3],
+    pub up: [RawElectrode; 3],
+    pub down: [RawElectrode; 3],
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -63,6 +94,13 @@ pub enum ParseInputError {
         source: ParseIntError,
     },
     InvalidButtonValue(i32),
+    /// MPR121 のビットマスクに未使用のビットが立っている
+    InvalidBitmask(u32),
+    /// 静電容量の値が 0〜65535 の範囲外
+    InvalidRawValue {
+        index: usize,
+        value: i32,
+    },
     InvalidControllerValue {
         index: usize,
         value: i32,
@@ -77,6 +115,58 @@ pub enum ParseInputError {
     },
 }
 
+impl ParseInputError {
+    /// エラーの種類の一覧（[`ParseInputError::kind`] が返す値）
+    pub const KINDS: [&'static str; 7] = [
+        "field-count",
+        "parse-int",
+        "invalid-button-value",
+        "invalid-bitmask",
+        "invalid-raw-value",
+        "invalid-controller-value",
+        "invalid-controller-triple-combination",
+    ];
+
+    /// エラーの種類（集計やメトリクスのラベルに使う）
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::FieldCount { .. } => "field-count",
+            Self::ParseInt { .. } => "parse-int",
+            Self::InvalidButtonValue(_) => "invalid-button-value",
+            Self::InvalidBitmask(_) => "invalid-bitmask",
+            Self::InvalidRawValue { .. } => "invalid-raw-value",
+            Self::InvalidControllerValue { .. } => "invalid-controller-value",
+            Self::InvalidControllerTripleCombination { .. } => {
+                "invalid-controller-triple-combination"
+            }
+        }
+    }
+
+    /// 不正な値が入っていたフィールドの位置（0 始まり。フィールド数の誤りなど、位置を特定できない場合は `None`）
+    ///
+    /// 方向の 3 つのフィールドの組み合わせが不正な場合は、前提となるフィールドが 0 なのに 1 になっているフィールドの位置を返す
+    /// （例: Middle が 0 で High が 1 なら High）。
+    pub fn field_index(&self) -> Option<usize> {
+        match self {
+            Self::ParseInt { index, .. }
+            | Self::InvalidRawValue { index, .. }
+            | Self::InvalidControllerValue { index, .. } => Some(*index),
+            Self::InvalidControllerTripleCombination {
+                middle_index,
+                high_index,
+                middle_value,
+                high_value,
+                ..
+            } => Some(if *high_value == 1 && *middle_value != 1 {
+                *high_index
+            } else {
+                *middle_index
+            }),
+            Self::FieldCount { .. } | Self::InvalidButtonValue(_) | Self::InvalidBitmask(_) => None,
+        }
+    }
+}
+
 impl fmt::Display for ParseInputError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -93,6 +183,15 @@ impl fmt::Display for ParseInputError {
             Self::InvalidButtonValue(value) => {
                 write!(f, "invalid button value: {value} (expected 0 or 1)")
             }
+            Self::InvalidRawValue { index, value } => {
+                write!(
+                    f,
+                    "invalid raw field #{index} value: {value} (expected 0-65535)"
+                )
+            }
+            Self::InvalidBitmask(value) => {
+                write!(f, "invalid bitmask: {value:#06x} (expected bits 0-12 only)")
+            }
             Self::InvalidControllerValue { index, value } => {
                 write!(
                     f,
@@ -125,59 +224,123 @@ impl Error for ParseInputError {
     }
 }
 
+/// 13 フィールドの CSV 行を既定のチャネルマップで入力状態に変換する
 pub fn parse_input_line(line: &str) -> Result<SerialInput, ParseInputError> {
+    parse_input_line_with(line, &ChannelMap::default())
+}
+
+/// 13 フィールドの CSV 行を、指定したチャネルマップで入力状態に変換する
+///
+/// 13 フィールドに続けて、`;` 区切りで電極 12 個分の filtered と baseline を付けた拡張形式も受け付ける。
+///
+/// ```text
+/// 0,1,1,0,0,0,0,0,0,0,0,0,0;480,495,530,...;530,531,529,...
+/// ```
+pub fn parse_input_line_with(
+    line: &str,
+    channel_map: &ChannelMap,
+) -> Result<SerialInput, ParseInputError> {
+    let (line, raw) = match line.split_once(';') {
+        Some((line, raw)) => (line, Some(parse_raw_sections(raw)?)),
+        None => (line, None),
+    };
+
+    // 各フィールドを i32 に変換し、センサー値は 0/1 以外を弾く（ボタンの値はチャネルマップの適用時に検証する）
+    let fields = parse_csv_fields(line, FIELD_COUNT)?;
+    let mut values = [0i32; FIELD_COUNT];
+    for (idx, value) in fields.into_iter().enumerate() {
+        if idx != channel_map.button {
+            ensure_binary_controller_value(value, idx)?;
+        }
+        values[idx] = value;
+    }
+
+    channel_map.apply_with_raw(&values, raw.as_ref())
+}
+
+/// 拡張形式の `filtered;baseline` を電極ごとの静電容量に変換する
+///
+/// エラーメッセージのフィールド番号は、13 フィールドに続けて数えた位置（filtered が 13〜24、baseline が 25〜36）。
+fn parse_raw_sections(raw: &str) -> Result<[RawElectrode; ELECTRODE_COUNT], ParseInputError> {
+    let sections: Vec<&str> = raw.split(';').collect();
+    let [filtered, baseline] = sections.as_slice() else {
+        return Err(ParseInputError::FieldCount {
+            expected: FIELD_COUNT + ELECTRODE_COUNT * 2,
+            actual: FIELD_COUNT + sections.len() * ELECTRODE_COUNT,
+        });
+    };
+
+    let mut electrodes = [RawElectrode::default(); ELECTRODE_COUNT];
+    for (section_idx, section) in [filtered, baseline].into_iter().enumerate() {
+        let offset = FIELD_COUNT + section_idx * ELECTRODE_COUNT;
+        let values = parse_csv_fields(section, ELECTRODE_COUNT).map_err(|err| match err {
+            ParseInputError::FieldCount { actual, .. } => ParseInputError::FieldCount {
+                expected: FIELD_COUNT + ELECTRODE_COUNT * 2,
+                actual: offset + actual,
+            },
+            ParseInputError::ParseInt {
+                index,
+                value,
+                source,
+            } => ParseInputError::ParseInt {
+                index: offset + index,
+                value,
+                source,
+            },
+            other => other,
+        })?;
+        for (idx, value) in values.into_iter().enumerate() {
+            let value = u16::try_from(value).map_err(|_| ParseInputError::InvalidRawValue {
+                index: offset + idx,
+                value,
+            })?;
+            if section_idx == 0 {
+                electrodes[idx].filtered = value;
+            } else {
+                electrodes[idx].baseline = value;
+            }
+        }
+    }
+    Ok(electrodes)
+}
+
+/// カンマ区切りの行を `expected` 個の整数に変換する
+pub(crate) fn parse_csv_fields(line: &str, expected: usize) -> Result<Vec<i32>, ParseInputError> {
     // 余分な空白や改行を取り除き、空行であれば即エラー
     let trimmed = line.trim();
     if trimmed.is_empty() {
         return Err(ParseInputError::FieldCount {
-            expected: FIELD_COUNT,
+            expected,
             actual: 0,
         });
     }
 
-    // CSV が 9 フィールド揃っているかを確認
+    // CSV のフィールドが揃っているかを確認
     let tokens: Vec<&str> = trimmed.split(',').collect();
-    if tokens.len() != FIELD_COUNT {
+    if tokens.len() != expected {
         return Err(ParseInputError::FieldCount {
-            expected: FIELD_COUNT,
+            expected,
             actual: tokens.len(),
         });
     }
 
-    // 各フィールドを i32 に変換し、センサー値は 0/1 以外を弾く
-    let mut values = [0i32; FIELD_COUNT];
-    for (idx, token) in tokens.iter().enumerate() {
-        let value = token
-            .trim()
-            .parse::<i32>()
-            .map_err(|source| ParseInputError::ParseInt {
-                index: idx,
-                value: token.trim().to_string(),
-                source,
-            })?;
-
-        if idx > 0 {
-            ensure_binary_controller_value(value, idx)?;
-        }
-        values[idx] = value;
-    }
-
-    // button フィールド (0/1) を bool に変換
-    let button = parse_button(values[0])?;
-
-    // front/right/back/left それぞれの Low/Middle/High トリプルを評価
-    // フォーマット: button,frontLow,frontMiddle,frontHigh,rightLow,rightMiddle,rightHigh,backLow,backMiddle,backHigh,leftLow,leftMiddle,leftHigh
-    let controller = ControllerInput {
-        down: controller_value_from_triple(values[1], values[2], values[3], 1, 2, 3)?,
-        left: controller_value_from_triple(values[4], values[5], values[6], 4, 5, 6)?,
-        right: controller_value_from_triple(values[7], values[8], values[9], 7, 8, 9)?,
-        up: controller_value_from_triple(values[10], values[11], values[12], 10, 11, 12)?,
-    };
-
-    Ok(SerialInput { button, controller })
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(idx, token)| {
+            token
+                .trim()
+                .parse::<i32>()
+                .map_err(|source| ParseInputError::ParseInt {
+                    index: idx,
+                    value: token.trim().to_string(),
+                    source,
+                })
+        })
+        .collect()
 }
 
-fn parse_button(value: i32) -> Result<ButtonInput, ParseInputError> {
+pub(crate) fn parse_button(value: i32) -> Result<ButtonInput, ParseInputError> {
     match value {
         0 => Ok(ButtonInput { is_pushed: false }),
         1 => Ok(ButtonInput { is_pushed: true }),
@@ -185,7 +348,10 @@ fn parse_button(value: i32) -> Result<ButtonInput, ParseInputError> {
     }
 }
 
-fn ensure_binary_controller_value(value: i32, index: usize) -> Result<(), ParseInputError> {
+pub(crate) fn ensure_binary_controller_value(
+    value: i32,
+    index: usize,
+) -> Result<(), ParseInputError> {
     match value {
         0 | 1 => Ok(()),
         other => Err(ParseInputError::InvalidControllerValue {
@@ -195,7 +361,7 @@ fn ensure_binary_controller_value(value: i32, index: usize) -> Result<(), ParseI
     }
 }
 
-fn controller_value_from_triple(
+pub(crate) fn controller_value_from_triple(
     low: i32,
     middle: i32,
     high: i32,
@@ -241,6 +407,58 @@ fn controller_value_from_triple(
     Ok(value)
 }
 
+/// `SerialInput` をファームウェアと同じ 13 フィールドの CSV 行に変換する
+///
+/// 既定のチャネルマップでの `parse_input_line` の逆変換。シミュレータやテストで、実機と同じ形式の行を生成するために使う。
+pub fn format_input_line(input: &SerialInput) -> String {
+    let mut values = [0u8; FIELD_COUNT];
+    values[0] = u8::from(input.button.is_pushed);
+
+    // parse_input_line と同じ並び（front→down, right→left, back→right, left→up）で書き戻す
+    let triples = [
+        &input.controller.down,
+        &input.controller.left,
+        &input.controller.right,
+        &input.controller.up,
+    ];
+    for (triple_idx, value) in triples.iter().enumerate() {
+        let level = value.level();
+        for offset in 0..3 {
+            values[1 + triple_idx * 3 + offset] = u8::from(level > offset as u8);
+        }
+    }
+
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl ControllerValue {
+    /// 入力レベル（0: Noinput, 1: Low, 2: Middle, 3: High）から値を生成する
+    ///
+    /// 3 より大きいレベルは High として扱う。
+    pub fn from_level(level: u8) -> Self {
+        match level {
+            0 => ControllerValue::Noinput(0),
+            1 => ControllerValue::Low(1),
+            2 => ControllerValue::Middle(1),
+            _ => ControllerValue::High(1),
+        }
+    }
+
+    /// 入力レベル（0: Noinput, 1: Low, 2: Middle, 3: High）を返す
+    pub fn level(&self) -> u8 {
+        match self {
+            ControllerValue::Noinput(_) => 0,
+            ControllerValue::Low(_) => 1,
+            ControllerValue::Middle(_) => 2,
+            ControllerValue::High(_) => 3,
+        }
+    }
+}
+
 impl fmt::Display for SerialInput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -310,9 +528,17 @@ mod tests {
         right: low=1, middle=1, high=1
         back: low=1, middle=1, high=0
         left: low=1, middle=1, high=1
+        channel map: front→up, right→right, back→down, left→left
         */
         let line = "1,1,0,0,1,1,1,1,1,0,1,1,1";
-        let input = parse_input_line(line).unwrap();
+        let channel_map = ChannelMap {
+            up: [1, 2, 3],
+            right: [4, 5, 6],
+            down: [7, 8, 9],
+            left: [10, 11, 12],
+            ..Default::default()
+        };
+        let input = parse_input_line_with(line, &channel_map).unwrap();
 
         assert!(input.button.is_pushed);
         assert_eq!(input.controller.up, ControllerValue::Low(1));
@@ -321,6 +547,79 @@ mod tests {
         assert_eq!(input.controller.left, ControllerValue::High(1));
     }
 
+    #[test]
+    fn remapped_button_field_is_validated_as_button() {
+        /* case
+        channel map: ボタンをフィールド 12、上方向の High をフィールド 0 に入れ替える
+        field 0: 2 (センサー値として 0/1 以外) / field 12: 2 (ボタンとして 0/1 以外)
+        */
+        let channel_map = ChannelMap {
+            button: 12,
+            up: [10, 11, 0],
+            ..Default::default()
+        };
+        assert!(channel_map.validate().is_ok());
+
+        let both = parse_input_line_with("2,0,0,0,0,0,0,0,0,0,0,0,2", &channel_map).unwrap_err();
+        let button_only =
+            parse_input_line_with("0,0,0,0,0,0,0,0,0,0,0,0,2", &channel_map).unwrap_err();
+
+        match both {
+            ParseInputError::InvalidControllerValue { index, value } => {
+                assert_eq!(index, 0);
+                assert_eq!(value, 2);
+            }
+            other => panic!("unexpected error variant: {other}"),
+        }
+        assert!(
+            matches!(button_only, ParseInputError::InvalidButtonValue(2)),
+            "unexpected error variant: {button_only}"
+        );
+    }
+
+    #[test]
+    fn parse_mixed_levels_with_default_channel_map() {
+        /* case
+        button: 1
+        front: low=1, middle=0, high=0
+        right: low=1, middle=1, high=1
+        back: low=1, middle=1, high=0
+        left: low=1, middle=1, high=1
+        channel map: 既定（front→down, right→left, back→right, left→up）
+        */
+        let line = "1,1,0,0,1,1,1,1,1,0,1,1,1";
+        let input = parse_input_line(line).unwrap();
+
+        assert!(input.button.is_pushed);
+        assert_eq!(input.controller.down, ControllerValue::Low(1));
+        assert_eq!(input.controller.left, ControllerValue::High(1));
+        assert_eq!(input.controller.right, ControllerValue::Middle(1));
+        assert_eq!(input.controller.up, ControllerValue::High(1));
+    }
+
+    #[test]
+    fn format_input_line_round_trips() {
+        /* case
+        button: 1
+        down: Low, left: High, right: Middle, up: Noinput
+        */
+        let input = SerialInput {
+            button: ButtonInput { is_pushed: true },
+            controller: ControllerInput {
+                left: ControllerValue::High(1),
+                right: ControllerValue::Middle(1),
+                up: ControllerValue::Noinput(0),
+                down: ControllerValue::Low(1),
+            },
+            raw: None,
+        };
+
+        let line = format_input_line(&input);
+
+        assert_eq!(line, "1,1,0,0,1,1,1,1,1,0,0,0,0");
+        assert_eq!(parse_input_line(&line).unwrap(), input);
+    }
+
     #[test]
     fn invalid_button_value_rejected() {
         /* case
@@ -357,6 +656,7 @@ mod tests {
         */
         let line = "0,0,0,0,0,2,0,0,0,0,0,0,0";
         let err = parse_input_line(line).unwrap_err();
+        assert_eq!(err.field_index(), Some(5));
         match err {
             ParseInputError::InvalidControllerValue { index, value } => {
                 assert_eq!(index, 5);
@@ -373,6 +673,7 @@ mod tests {
         */
         let line = "0,0,0,0,0,0,1,0,0,0,0,0,0";
         let err = parse_input_line(line).unwrap_err();
+        assert_eq!(err.field_index(), Some(6));
         match err {
             ParseInputError::InvalidControllerTripleCombination {
                 low_index,
@@ -400,6 +701,7 @@ mod tests {
         */
         let line = "0,0,0,0,0,0,0,0,0,0,0,1,0";
         let err = parse_input_line(line).unwrap_err();
+        assert_eq!(err.field_index(), Some(11));
         match err {
             ParseInputError::InvalidControllerTripleCombination {
                 low_index,
@@ -419,4 +721,74 @@ mod tests {
             other => panic!("unexpected error variant: {other}"),
         }
     }
+
+    #[test]
+    fn parse_extended_line_with_raw_readings() {
+        /* case
+        down: Low
+        filtered: 400, 401, ..., 411 / baseline: 500, 501, ..., 511
+        */
+        let filtered: Vec<String> = (400..412).map(|v: i32| v.to_string()).collect();
+        let baseline: Vec<String> = (500..512).map(|v: i32| v.to_string()).collect();
+        let line = format!(
+            "0,1,0,0,0,0,0,0,0,0,0,0,0;{};{}",
+            filtered.join(","),
+            baseline.join(",")
+        );
+
+        let input = parse_input_line(&line).unwrap();
+        let raw = input.raw.as_ref().unwrap();
+
+        assert_eq!(input.controller.down, ControllerValue::Low(1));
+        assert_eq!(
+            raw.down[0],
+            RawElectrode {
+                filtered: 400,
+                baseline: 500
+            }
+        );
+        // 既定のチャネルマップではフィールド 10〜12 が上方向
+        assert_eq!(
+            raw.up[2],
+            RawElectrode {
+                filtered: 411,
+                baseline: 511
+            }
+        );
+        assert!(
+            parse_input_line("0,0,0,0,0,0,0,0,0,0,0,0,0")
+                .unwrap()
+                .raw
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn invalid_raw_readings_are_rejected() {
+        /* case
+        baseline が 11 個しかない / filtered の 2 つ目が負の値
+        */
+        let zeros = "0,0,0,0,0,0,0,0,0,0,0,0,0";
+        let twelve = "0,0,0,0,0,0,0,0,0,0,0,0";
+        let eleven = "0,0,0,0,0,0,0,0,0,0,0";
+        let negative = "0,-1,0,0,0,0,0,0,0,0,0,0";
+
+        let short = parse_input_line(&format!("{zeros};{twelve};{eleven}")).unwrap_err();
+        let out_of_range = parse_input_line(&format!("{zeros};{negative};{twelve}")).unwrap_err();
+
+        match short {
+            ParseInputError::FieldCount { expected, actual } => {
+                assert_eq!(expected, 37);
+                assert_eq!(actual, 36);
+            }
+            other => panic!("unexpected error variant: {other}"),
+        }
+        match out_of_range {
+            ParseInputError::InvalidRawValue { index, value } => {
+                assert_eq!(index, 14);
+                assert_eq!(value, -1);
+            }
+            other => panic!("unexpected error variant: {other}"),
+        }
+    }
 }
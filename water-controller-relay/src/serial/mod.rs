@@ -1,3 +1,8 @@
+pub mod channel_map;
+pub mod decoder;
+pub mod detect;
+pub mod device;
+pub mod framing;
 pub mod input;
 pub mod reader;
 
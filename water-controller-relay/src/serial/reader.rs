@@ -1,17 +1,22 @@
 use std::{
     io::{self, Read},
+    sync::Arc,
     time::Duration,
 };
 
 use serialport::SerialPort;
-use tokio::sync::broadcast;
-use tracing::{debug, warn};
 
-use super::input::parse_input_line;
-use crate::websocket::message::{ButtonInputMessage, ControllerInputMessage};
+use crate::{
+    serial::framing::{FrameDecoder, FrameMetrics, Framing},
+    source::{InputSource, InterruptCheck},
+};
 
 pub struct SerialReader {
     port: Box<dyn SerialPort>,
+    /// バイナリフレームで受信する場合のデコーダ（テキストの場合は `None`）
+    frames: Option<FrameDecoder>,
+    /// 読み取りタイムアウトごとに確認する中断の判定
+    interrupt: Option<InterruptCheck>,
 }
 
 impl SerialReader {
@@ -25,10 +30,37 @@ impl SerialReader {
                 ))
             })?;
 
-        Ok(Self { port })
+        Ok(Self {
+            port,
+            frames: None,
+            interrupt: None,
+        })
+    }
+
+    /// 伝送方式を指定してシリアルポートを開く
+    ///
+    /// ## 引数
+    ///
+    /// - `metrics`: バイナリフレームの受信状況を累計する先（テキストでは使わない）
+    pub fn open_with_framing(
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+        framing: Framing,
+        metrics: Arc<FrameMetrics>,
+    ) -> io::Result<Self> {
+        let mut reader = Self::open(port_name, baud_rate, timeout)?;
+        if framing == Framing::Binary {
+            reader.frames = Some(FrameDecoder::new(metrics));
+        }
+        Ok(reader)
     }
 
     pub fn read_line(&mut self) -> io::Result<String> {
+        if self.frames.is_some() {
+            return self.read_frame();
+        }
+
         let mut buf = Vec::new();
         let mut byte = [0u8; 1];
 
@@ -41,7 +73,8 @@ impl SerialReader {
                     other => buf.push(other),
                 },
                 Err(ref err) if err.kind() == io::ErrorKind::TimedOut => {
-                    // タイムアウトは想定済みなので読み取り継続
+                    // タイムアウトは想定済みなので、中断を求められていなければ読み取り継続
+                    self.check_interrupt()?;
                     continue;
                 }
                 Err(err) => return Err(err),
@@ -52,69 +85,53 @@ impl SerialReader {
             .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.utf8_error()))
     }
 
-    /// シリアル読み取りループ（ブロッキング処理）
-    ///
-    /// シリアルポートからデータを読み取り、パースして WebSocket ブロードキャストチャネルに送信する。
-    pub fn run_read_loop(&mut self, broadcast_tx: broadcast::Sender<String>) -> io::Result<()> {
+    /// 次のフレームを受信し、ペイロードを 1 行として返す
+    fn read_frame(&mut self) -> io::Result<String> {
+        let mut chunk = [0u8; 64];
+
         loop {
-            // WebSocket 接続の有無に関わらず、常にシリアルを読み取る必要がある。
-            // 理由：シリアルバッファの溢れを防ぎ、再接続時に古いデータを送信しないため。
-            // 詳細：docs/notes/20251113_serial-broadcast-strategy.md
-            let line = match self.read_line() {
-                Ok(line) => line,
-                Err(err) => {
-                    // 'Broken pipe' エラーは、シリアルポートが閉じられたことを意味する。
-                    // この場合は、エラーを返して再接続を試みる。
-                    warn!(error = %err, "Failed to read serial line");
-                    return Err(err);
-                }
-            };
-            debug!(%line, "Received raw serial line");
-
-            match parse_input_line(&line) {
-                Ok(input) => {
-                    debug!(input = %input, "Parsed input successfully");
-                    debug!("{input}");
-
-                    // button-input メッセージを送信
-                    let button_msg = ButtonInputMessage::new(&input.button);
-                    match serde_json::to_string(&button_msg) {
-                        Ok(json) => {
-                            // 接続中のクライアントがいる場合のみブロードキャスト
-                            if broadcast_tx.receiver_count() > 0 {
-                                debug!(message = %json, "Broadcasting button-input");
-                                if let Err(e) = broadcast_tx.send(json) {
-                                    warn!(error = %e, "Failed to broadcast button-input");
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            warn!(error = %e, "Failed to serialize button-input");
-                        }
-                    }
+            if let Some(line) = self.frames.as_mut().and_then(FrameDecoder::next_line) {
+                return Ok(line);
+            }
 
-                    // controller-input メッセージを送信
-                    let controller_msg = ControllerInputMessage::new(&input.controller);
-                    match serde_json::to_string(&controller_msg) {
-                        Ok(json) => {
-                            // 接続中のクライアントがいる場合のみブロードキャスト
-                            if broadcast_tx.receiver_count() > 0 {
-                                debug!(message = %json, "Broadcasting controller-input");
-                                if let Err(e) = broadcast_tx.send(json) {
-                                    warn!(error = %e, "Failed to broadcast controller-input");
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            warn!(error = %e, "Failed to serialize controller-input");
-                        }
+            match self.port.read(&mut chunk) {
+                Ok(0) => continue,
+                Ok(n) => {
+                    if let Some(frames) = self.frames.as_mut() {
+                        frames.push(&chunk[..n]);
                     }
                 }
-                Err(err) => {
-                    warn!(error = %err, raw_line = %line, "Failed to parse serial line");
-                    eprintln!("failed to parse line '{line}': {err}");
+                Err(ref err) if err.kind() == io::ErrorKind::TimedOut => {
+                    self.check_interrupt()?;
+                    continue;
                 }
+                Err(err) => return Err(err),
             }
         }
     }
+
+    /// 中断を求められていれば `Interrupted` エラーを返す（受信途中の行は捨てる）
+    fn check_interrupt(&self) -> io::Result<()> {
+        match &self.interrupt {
+            Some(interrupt) if interrupt() => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "serial read interrupted",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl InputSource for SerialReader {
+    fn read_line(&mut self) -> io::Result<String> {
+        SerialReader::read_line(self)
+    }
+
+    fn port_name(&self) -> Option<String> {
+        self.port.name()
+    }
+
+    fn set_interrupt(&mut self, interrupt: InterruptCheck) {
+        self.interrupt = Some(interrupt);
+    }
 }
@@ -0,0 +1,351 @@
+//! シリアルセッションの記録・再生
+//!
+//! 入力源から読み取った生の行を、単調増加するタイムスタンプ付きでキャプチャファイルに記録し、
+//! 記録したファイルを元のタイミング（または速度を変えて）で再生する。
+//!
+//! ## キャプチャファイルの形式
+//!
+//! ```txt
+//! # water-controller-relay capture v1
+//! 0<TAB>0,0,0,0,0,0,0,0,0,0,0,0,0
+//! 105<TAB>0,1,0,0,0,0,0,0,0,0,0,0,0
+//! ```
+//!
+//! 1 行目はヘッダ。以降は「記録開始からの経過ミリ秒」と「生の行」をタブ文字区切りで並べる。
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, info};
+
+use super::{InputSource, InterruptCheck};
+
+const CAPTURE_HEADER: &str = "# water-controller-relay capture v1";
+
+/// 繰り返し再生で、1 周の再生にかける最短の時間
+///
+/// すべてのエントリの経過時間が 0 のキャプチャ（1 行だけのものなど）でも、待たずに同じ行を流し続けないようにする。
+const MIN_LOOP_DURATION: Duration = Duration::from_millis(10);
+
+/// キャプチャファイルの 1 エントリ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureEntry {
+    /// 記録開始からの経過時間
+    pub elapsed: Duration,
+    /// 入力源から読み取った生の行
+    pub line: String,
+}
+
+/// キャプチャファイルへの書き込み
+///
+/// 入力源の再接続をまたいで同じファイルに記録し続けるため、`Arc<Mutex<_>>` で共有する。
+pub struct CaptureWriter {
+    writer: BufWriter<File>,
+    started_at: Instant,
+}
+
+impl CaptureWriter {
+    /// キャプチャファイルを作成する（既存のファイルは上書きする）
+    pub fn create(path: &Path) -> io::Result<Arc<Mutex<Self>>> {
+        let file = File::create(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("failed to create capture file {}: {err}", path.display()),
+            )
+        })?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{CAPTURE_HEADER}")?;
+        writer.flush()?;
+        info!(path = %path.display(), "Recording input lines to capture file");
+
+        Ok(Arc::new(Mutex::new(Self {
+            writer,
+            started_at: Instant::now(),
+        })))
+    }
+
+    /// 1 行を記録する
+    ///
+    /// 異常終了時にも記録が残るよう、1 行ごとにフラッシュする。
+    pub fn record(&mut self, line: &str) -> io::Result<()> {
+        let elapsed_ms = self.started_at.elapsed().as_millis();
+        writeln!(self.writer, "{elapsed_ms}\t{line}")?;
+        self.writer.flush()
+    }
+}
+
+/// 読み取った行をキャプチャファイルに記録しながら中継する入力源
+pub struct RecordingSource {
+    inner: Box<dyn InputSource>,
+    writer: Arc<Mutex<CaptureWriter>>,
+}
+
+impl RecordingSource {
+    pub fn new(inner: Box<dyn InputSource>, writer: Arc<Mutex<CaptureWriter>>) -> Self {
+        Self { inner, writer }
+    }
+}
+
+impl InputSource for RecordingSource {
+    fn read_line(&mut self) -> io::Result<String> {
+        let line = self.inner.read_line()?;
+        self.writer
+            .lock()
+            .map_err(|_| io::Error::other("capture writer lock poisoned"))?
+            .record(&line)?;
+        Ok(line)
+    }
+
+    fn port_name(&self) -> Option<String> {
+        self.inner.port_name()
+    }
+
+    fn set_interrupt(&mut self, interrupt: InterruptCheck) {
+        self.inner.set_interrupt(interrupt);
+    }
+}
+
+/// キャプチャファイルを読み込む
+pub fn read_capture(path: &Path) -> io::Result<Vec<CaptureEntry>> {
+    let file = File::open(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("failed to open capture file {}: {err}", path.display()),
+        )
+    })?;
+
+    let mut entries = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (elapsed_ms, raw) = line.split_once('\t').ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("capture line #{} has no timestamp: '{line}'", idx + 1),
+            )
+        })?;
+        let elapsed_ms = elapsed_ms.parse::<u64>().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("capture line #{} has invalid timestamp: {err}", idx + 1),
+            )
+        })?;
+
+        entries.push(CaptureEntry {
+            elapsed: Duration::from_millis(elapsed_ms),
+            line: raw.to_string(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// キャプチャファイルを再生する入力源
+pub struct ReplaySource {
+    entries: Vec<CaptureEntry>,
+    speed: f64,
+    looped: bool,
+    position: usize,
+    started_at: Instant,
+}
+
+impl ReplaySource {
+    /// キャプチャファイルを開く
+    ///
+    /// ## 引数
+    ///
+    /// - `path`: キャプチャファイルのパス
+    /// - `speed`: 再生速度の倍率（1.0 で記録時と同じタイミング）
+    /// - `looped`: 末尾まで再生したら先頭に戻るかどうか
+    pub fn open(path: &Path, speed: f64, looped: bool) -> io::Result<Self> {
+        let entries = read_capture(path)?;
+        info!(
+            path = %path.display(),
+            entries = entries.len(),
+            speed,
+            looped,
+            "Replaying capture file"
+        );
+        Ok(Self::new(entries, speed, looped))
+    }
+
+    pub fn new(entries: Vec<CaptureEntry>, speed: f64, looped: bool) -> Self {
+        Self {
+            entries,
+            speed,
+            looped,
+            position: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn scaled(&self, elapsed: Duration) -> Duration {
+        elapsed.div_f64(self.speed)
+    }
+}
+
+impl InputSource for ReplaySource {
+    fn read_line(&mut self) -> io::Result<String> {
+        if self.position >= self.entries.len() {
+            if !self.looped || self.entries.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "capture replay finished",
+                ));
+            }
+            debug!("Capture replay reached the end, looping");
+            self.position = 0;
+            self.started_at = Instant::now().max(self.started_at + MIN_LOOP_DURATION);
+        }
+
+        let entry = &self.entries[self.position];
+        let due = self.started_at + self.scaled(entry.elapsed);
+        let now = Instant::now();
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+
+        let line = self.entries[self.position].line.clone();
+        self.position += 1;
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::source::simulator::{Simulator, SimulatorMode};
+
+    fn temp_capture_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "water-controller-relay-{}-{name}.capture",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_recording_source_writes_timestamped_lines() {
+        // テスト項目: RecordingSource が読み取った行をタイムスタンプ付きで記録し、read_capture で読み戻せる
+        // given (前提条件):
+        let path = temp_capture_path("record");
+        let keys = io::Cursor::new("w\nb\n");
+        let simulator = Simulator::with_keyboard(
+            SimulatorMode::Keyboard,
+            Duration::ZERO,
+            None,
+            Some(Box::new(keys)),
+        );
+        let writer = CaptureWriter::create(&path).unwrap();
+        let mut source = RecordingSource::new(Box::new(simulator), writer);
+
+        // when (操作):
+        let first = source.read_line().unwrap();
+        let second = source.read_line().unwrap();
+        let entries = read_capture(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // then (期待する結果):
+        let lines: Vec<&str> = entries.iter().map(|e| e.line.as_str()).collect();
+        assert_eq!(lines, vec![first.as_str(), second.as_str()]);
+        assert!(entries[0].elapsed <= entries[1].elapsed);
+    }
+
+    #[test]
+    fn test_read_capture_rejects_line_without_timestamp() {
+        // テスト項目: タイムスタンプのない行を含むキャプチャファイルは InvalidData エラーになる
+        // given (前提条件):
+        let path = temp_capture_path("invalid");
+        std::fs::write(&path, format!("{CAPTURE_HEADER}\n0,0,0\n")).unwrap();
+
+        // when (操作):
+        let err = read_capture(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_replay_source_scales_timing_and_finishes() {
+        // テスト項目: ReplaySource は速度倍率に応じて間隔を縮め、末尾で EOF を返す
+        // given (前提条件):
+        let entries = vec![
+            CaptureEntry {
+                elapsed: Duration::ZERO,
+                line: "first".to_string(),
+            },
+            CaptureEntry {
+                elapsed: Duration::from_millis(200),
+                line: "second".to_string(),
+            },
+        ];
+        let mut source = ReplaySource::new(entries, 4.0, false);
+
+        // when (操作):
+        let started = Instant::now();
+        let first = source.read_line().unwrap();
+        let second = source.read_line().unwrap();
+        let elapsed = started.elapsed();
+        let eof = source.read_line().unwrap_err();
+
+        // then (期待する結果):
+        assert_eq!((first.as_str(), second.as_str()), ("first", "second"));
+        assert!(elapsed >= Duration::from_millis(50));
+        assert!(elapsed < Duration::from_millis(200));
+        assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_replay_source_loops() {
+        // テスト項目: looped な ReplaySource は末尾まで再生すると先頭に戻る
+        // given (前提条件):
+        let entries = vec![
+            CaptureEntry {
+                elapsed: Duration::ZERO,
+                line: "a".to_string(),
+            },
+            CaptureEntry {
+                elapsed: Duration::ZERO,
+                line: "b".to_string(),
+            },
+        ];
+        let mut source = ReplaySource::new(entries, 1.0, true);
+
+        // when (操作):
+        let lines: Vec<String> = (0..5).map(|_| source.read_line().unwrap()).collect();
+
+        // then (期待する結果):
+        assert_eq!(lines, vec!["a", "b", "a", "b", "a"]);
+    }
+
+    #[test]
+    fn test_looped_replay_of_zero_duration_capture_waits_between_loops() {
+        // テスト項目: 経過時間が 0 のエントリだけのキャプチャを繰り返し再生しても、1 周ごとに最短の時間を空ける
+        // given (前提条件):
+        let entries = vec![CaptureEntry {
+            elapsed: Duration::ZERO,
+            line: "only".to_string(),
+        }];
+        let mut source = ReplaySource::new(entries, 1.0, true);
+
+        // when (操作):
+        let started = Instant::now();
+        for _ in 0..6 {
+            source.read_line().unwrap();
+        }
+        let elapsed = started.elapsed();
+
+        // then (期待する結果): 最初の 1 行の後に 5 周分待つ
+        assert!(elapsed >= MIN_LOOP_DURATION * 5, "{elapsed:?}");
+    }
+}
@@ -0,0 +1,226 @@
+//! 入力ソースモジュール
+//!
+//! シリアルポートやシミュレータなど、ファームウェア形式の行を供給する入力源を抽象化する
+
+pub mod capture;
+pub mod simulator;
+pub mod status;
+
+use std::{
+    fmt, io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::SerialConfig,
+    hub::{Hub, InputOrigin},
+    serial::{
+        SerialReader,
+        channel_map::ChannelMap,
+        decoder::{LineDecoder, LineFormat},
+        detect::PortMatcher,
+        framing::FrameMetrics,
+    },
+};
+use capture::ReplaySource;
+use simulator::{Simulator, SimulatorMode};
+
+/// 読み取りを中断するかどうかの判定
+///
+/// 行が届かない間も呼ばれる（シリアルポートでは読み取りタイムアウトごと）。`true` を返すと、
+/// 入力源は `io::ErrorKind::Interrupted` を返して読み取りを中断する。
+pub type InterruptCheck = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// ファームウェア形式の行を 1 行ずつ供給する入力源
+pub trait InputSource: Send {
+    /// 次の 1 行を読み取る（ブロッキング処理）
+    ///
+    /// 改行文字は含まない。入力源が終端に達した場合は `io::ErrorKind::UnexpectedEof` を返す。
+    fn read_line(&mut self) -> io::Result<String>;
+
+    /// 読み取っているシリアルポートの名前（シリアルポート以外の入力源では `None`）
+    fn port_name(&self) -> Option<String> {
+        None
+    }
+
+    /// 行が届かない間も読み取りを中断できるように、中断の判定を設定する
+    ///
+    /// 既定では何もしない（行を待ち続けることのない入力源は、次の行を返した後に中断すればよい）。
+    fn set_interrupt(&mut self, _interrupt: InterruptCheck) {}
+
+    /// 読み取りループ（ブロッキング処理）
+    ///
+    /// 入力源からデータを読み取り、`decoder` でデコードしてハブ経由で WebSocket クライアントに配信する。
+    /// 入力源が終端に達した場合は `Ok(())` を返す。
+    fn run_read_loop(&mut self, hub: &Hub, decoder: &mut dyn LineDecoder) -> io::Result<()> {
+        loop {
+            // WebSocket 接続の有無に関わらず、常にシリアルを読み取る必要がある。
+            // 理由：シリアルバッファの溢れを防ぎ、再接続時に古いデータを送信しないため。
+            // 詳細：docs/notes/20251113_serial-broadcast-strategy.md
+            let line = match self.read_line() {
+                Ok(line) => line,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    debug!("Input source reached end of stream");
+                    return Ok(());
+                }
+                Err(err) => {
+                    // 'Broken pipe' エラーは、シリアルポートが閉じられたことを意味する。
+                    // この場合は、エラーを返して再接続を試みる。
+                    warn!(error = %err, "Failed to read serial line");
+                    return Err(err);
+                }
+            };
+            debug!(%line, "Received raw serial line");
+            hub.source_status().record_line(Instant::now());
+
+            match decoder.decode(&line) {
+                Ok(Some(input)) => {
+                    debug!(input = %input, "Parsed input successfully");
+                    debug!("{input}");
+                    hub.publish_input(input, InputOrigin::Source);
+                }
+                Ok(None) => debug!(raw_line = %line, "Skipped line without input state"),
+                Err(err) => {
+                    warn!(error = %err, raw_line = %line, "Failed to parse serial line");
+                    hub.report_parse_error(&err, &line);
+                }
+            }
+        }
+    }
+}
+
+/// 入力源の設定
+///
+/// `run_loop` はこの設定から入力源を開き、失敗した場合は開き直す。
+#[derive(Debug, Clone)]
+pub enum InputSourceConfig {
+    /// シリアルポートから読み取る
+    Serial { port: String, baud: u32 },
+    /// 条件に一致するシリアルポートを開くたびに検出して読み取る
+    DetectedSerial { matcher: PortMatcher, baud: u32 },
+    /// 実機なしでシミュレータが生成した行を使う
+    Simulator {
+        mode: SimulatorMode,
+        interval: Duration,
+        seed: Option<u64>,
+    },
+    /// キャプチャファイルを再生する
+    Replay {
+        path: PathBuf,
+        speed: f64,
+        looped: bool,
+    },
+}
+
+impl InputSourceConfig {
+    /// シリアル通信の設定から入力源を作る
+    ///
+    /// 自動検出の条件があれば検出したポートを、なければ `port` を開く。
+    pub fn from_serial_config(serial: &SerialConfig) -> Self {
+        match &serial.detect {
+            Some(matcher) => Self::DetectedSerial {
+                matcher: matcher.clone(),
+                baud: serial.baud_rate,
+            },
+            None => Self::Serial {
+                port: serial.port.clone(),
+                baud: serial.baud_rate,
+            },
+        }
+    }
+
+    /// シリアルポートの入力源であれば、ポートとボーレートを設定ファイルの値に置き換える
+    pub fn with_serial_config(&self, serial: &SerialConfig) -> Self {
+        match self {
+            Self::Serial { .. } | Self::DetectedSerial { .. } => Self::from_serial_config(serial),
+            other => other.clone(),
+        }
+    }
+
+    /// シリアルポートの入力源であればボーレートを返す
+    pub fn baud_rate(&self) -> Option<u32> {
+        match self {
+            Self::Serial { baud, .. } | Self::DetectedSerial { baud, .. } => Some(*baud),
+            _ => None,
+        }
+    }
+
+    /// 入力源の行のデコーダを作る
+    ///
+    /// シミュレータは配信上の方向をそのまま 13 フィールドの CSV にするため、設定した行形式とチャネルマップは
+    /// シリアルポートとキャプチャファイルにだけ適用する。
+    pub fn decoder(&self, serial: &SerialConfig) -> Box<dyn LineDecoder> {
+        match self {
+            Self::Simulator { .. } => LineFormat::Csv13.decoder(ChannelMap::default()),
+            _ => serial.line_format.decoder(serial.channel_map),
+        }
+    }
+
+    /// 入力源を開く
+    ///
+    /// ## 引数
+    ///
+    /// - `serial`: シリアル通信の設定（読み取りタイムアウトと伝送方式を使う。シミュレータでは無視される）
+    /// - `frame_metrics`: バイナリフレームの受信状況を累計する先
+    pub fn open(
+        &self,
+        serial: &SerialConfig,
+        frame_metrics: &Arc<FrameMetrics>,
+    ) -> io::Result<Box<dyn InputSource>> {
+        let open_serial = |port: &str, baud: u32| {
+            SerialReader::open_with_framing(
+                port,
+                baud,
+                serial.read_timeout(),
+                serial.framing,
+                frame_metrics.clone(),
+            )
+        };
+        match self {
+            Self::Serial { port, baud } => Ok(Box::new(open_serial(port, *baud)?)),
+            Self::DetectedSerial { matcher, baud } => {
+                let port = matcher.detect()?;
+                info!(%port, %matcher, "Detected serial port");
+                Ok(Box::new(open_serial(&port, *baud)?))
+            }
+            Self::Simulator {
+                mode,
+                interval,
+                seed,
+            } => Ok(Box::new(Simulator::new(*mode, *interval, *seed))),
+            Self::Replay {
+                path,
+                speed,
+                looped,
+            } => Ok(Box::new(ReplaySource::open(path, *speed, *looped)?)),
+        }
+    }
+}
+
+impl fmt::Display for InputSourceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial { port, baud } => write!(f, "serial({port} @ {baud} baud)"),
+            Self::DetectedSerial { matcher, baud } => {
+                write!(f, "serial(detect {matcher} @ {baud} baud)")
+            }
+            Self::Simulator { mode, interval, .. } => {
+                write!(f, "simulator({mode:?}, every {}ms)", interval.as_millis())
+            }
+            Self::Replay {
+                path,
+                speed,
+                looped,
+            } => write!(
+                f,
+                "replay({}, x{speed}{})",
+                path.display(),
+                if *looped { ", looped" } else { "" }
+            ),
+        }
+    }
+}
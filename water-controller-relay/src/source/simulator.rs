@@ -0,0 +1,283 @@
+//! 入力シミュレータ
+//!
+//! 水コントローラの実機なしでサーバを動かすために、ファームウェアと同じ 13 フィールドの行を生成する
+
+use std::{
+    io::{self, BufRead, BufReader},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tracing::info;
+
+use super::InputSource;
+use crate::serial::input::{ControllerValue, SerialInput, format_input_line};
+
+/// random モードで 1 ステップごとに方向のレベルが変化する確率
+const RANDOM_LEVEL_CHANGE_PROBABILITY: f64 = 0.3;
+/// random モードで 1 ステップごとにボタンの状態が反転する確率
+const RANDOM_BUTTON_TOGGLE_PROBABILITY: f64 = 0.05;
+
+/// シミュレーションモード
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SimulatorMode {
+    /// 上→右→下→左の順にレベルを上げ下げし、最後にボタンを押して離すパターンを繰り返す
+    Pattern,
+    /// ランダムに方向のレベルとボタンを変化させる
+    Random,
+    /// 標準入力のキー操作で入力を組み立てる（w/a/s/d: 上/左/下/右のレベル切り替え, b: ボタン, 0: リセット）
+    Keyboard,
+}
+
+/// 方向（シミュレータ内部でのみ使う）
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+const PATTERN_DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+/// pattern モードで各方向に対して順に設定するレベル
+const PATTERN_LEVELS: [u8; 6] = [1, 2, 3, 2, 1, 0];
+
+/// シミュレータ入力源
+pub struct Simulator {
+    mode: SimulatorMode,
+    interval: Duration,
+    state: SerialInput,
+    step: usize,
+    rng: StdRng,
+    keyboard: Option<Box<dyn BufRead + Send>>,
+}
+
+impl Simulator {
+    /// シミュレータを生成する
+    ///
+    /// ## 引数
+    ///
+    /// - `mode`: シミュレーションモード
+    /// - `interval`: 行を生成する間隔（keyboard モードでは無視される）
+    /// - `seed`: random モードの乱数シード（省略時はランダム）
+    pub fn new(mode: SimulatorMode, interval: Duration, seed: Option<u64>) -> Self {
+        let keyboard: Option<Box<dyn BufRead + Send>> = match mode {
+            SimulatorMode::Keyboard => Some(Box::new(BufReader::new(io::stdin()))),
+            _ => None,
+        };
+        Self::with_keyboard(mode, interval, seed, keyboard)
+    }
+
+    /// キー入力の読み取り元を指定してシミュレータを生成する
+    pub fn with_keyboard(
+        mode: SimulatorMode,
+        interval: Duration,
+        seed: Option<u64>,
+        keyboard: Option<Box<dyn BufRead + Send>>,
+    ) -> Self {
+        if mode == SimulatorMode::Keyboard {
+            info!("Keyboard simulator: w/a/s/d = up/left/down/right, b = button, 0 = reset");
+        }
+
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+
+        Self {
+            mode,
+            interval,
+            state: SerialInput::idle(),
+            step: 0,
+            rng,
+            keyboard,
+        }
+    }
+
+    /// 次の入力状態を生成する（pattern / random モード）
+    pub fn next_input(&mut self) -> SerialInput {
+        match self.mode {
+            SimulatorMode::Pattern => self.next_pattern_input(),
+            SimulatorMode::Random => self.next_random_input(),
+            SimulatorMode::Keyboard => self.state.clone(),
+        }
+    }
+
+    /// キー操作の 1 行を現在の入力状態に反映する（keyboard モード）
+    pub fn apply_keys(&mut self, keys: &str) -> SerialInput {
+        for key in keys.chars() {
+            match key {
+                'w' | 'W' => self.cycle_level(Direction::Up),
+                'a' | 'A' => self.cycle_level(Direction::Left),
+                's' | 'S' => self.cycle_level(Direction::Down),
+                'd' | 'D' => self.cycle_level(Direction::Right),
+                'b' | 'B' | ' ' => {
+                    self.state.button.is_pushed = !self.state.button.is_pushed;
+                }
+                '0' => self.state = SerialInput::idle(),
+                _ => {}
+            }
+        }
+        self.state.clone()
+    }
+
+    fn next_pattern_input(&mut self) -> SerialInput {
+        let direction_steps = PATTERN_DIRECTIONS.len() * PATTERN_LEVELS.len();
+        // 方向のレベル変化の後に、ボタン押下・解放の 2 ステップを追加する
+        let cycle_len = direction_steps + 2;
+        let step = self.step % cycle_len;
+        self.step = self.step.wrapping_add(1);
+
+        if step < direction_steps {
+            let direction = PATTERN_DIRECTIONS[step / PATTERN_LEVELS.len()];
+            let level = PATTERN_LEVELS[step % PATTERN_LEVELS.len()];
+            self.state.button.is_pushed = false;
+            *self.value_mut(direction) = ControllerValue::from_level(level);
+        } else {
+            self.state.button.is_pushed = step == direction_steps;
+        }
+
+        self.state.clone()
+    }
+
+    fn next_random_input(&mut self) -> SerialInput {
+        if self.rng.random_bool(RANDOM_LEVEL_CHANGE_PROBABILITY) {
+            let direction = PATTERN_DIRECTIONS[self.rng.random_range(0..PATTERN_DIRECTIONS.len())];
+            let level = self.rng.random_range(0..=3);
+            *self.value_mut(direction) = ControllerValue::from_level(level);
+        }
+        if self.rng.random_bool(RANDOM_BUTTON_TOGGLE_PROBABILITY) {
+            self.state.button.is_pushed = !self.state.button.is_pushed;
+        }
+
+        self.state.clone()
+    }
+
+    fn cycle_level(&mut self, direction: Direction) {
+        let value = self.value_mut(direction);
+        *value = ControllerValue::from_level((value.level() + 1) % 4);
+    }
+
+    fn value_mut(&mut self, direction: Direction) -> &mut ControllerValue {
+        let controller = &mut self.state.controller;
+        match direction {
+            Direction::Up => &mut controller.up,
+            Direction::Right => &mut controller.right,
+            Direction::Down => &mut controller.down,
+            Direction::Left => &mut controller.left,
+        }
+    }
+}
+
+impl InputSource for Simulator {
+    fn read_line(&mut self) -> io::Result<String> {
+        let input = match self.keyboard.as_mut() {
+            Some(keyboard) => {
+                let mut keys = String::new();
+                if keyboard.read_line(&mut keys)? == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "keyboard input closed",
+                    ));
+                }
+                self.apply_keys(keys.trim_end_matches(['\r', '\n']))
+            }
+            None => {
+                std::thread::sleep(self.interval);
+                self.next_input()
+            }
+        };
+
+        Ok(format_input_line(&input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial::input::parse_input_line;
+
+    #[test]
+    fn test_pattern_mode_sweeps_up_first() {
+        // テスト項目: pattern モードは上方向のレベルを Low → Middle → High の順に上げる
+        // given (前提条件):
+        let mut simulator =
+            Simulator::with_keyboard(SimulatorMode::Pattern, Duration::ZERO, Some(0), None);
+
+        // when (操作):
+        let levels: Vec<u8> = (0..3)
+            .map(|_| simulator.next_input().controller.up.level())
+            .collect();
+
+        // then (期待する結果):
+        assert_eq!(levels, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_pattern_mode_presses_button_at_end_of_cycle() {
+        // テスト項目: pattern モードは 1 周の最後にボタンを押して離す
+        // given (前提条件):
+        let mut simulator =
+            Simulator::with_keyboard(SimulatorMode::Pattern, Duration::ZERO, Some(0), None);
+        let direction_steps = PATTERN_DIRECTIONS.len() * PATTERN_LEVELS.len();
+
+        // when (操作):
+        let inputs: Vec<SerialInput> = (0..direction_steps + 2)
+            .map(|_| simulator.next_input())
+            .collect();
+
+        // then (期待する結果):
+        assert!(inputs[direction_steps].button.is_pushed);
+        assert!(!inputs[direction_steps + 1].button.is_pushed);
+        assert_eq!(inputs[direction_steps + 1], SerialInput::idle());
+    }
+
+    #[test]
+    fn test_random_mode_is_reproducible_with_seed() {
+        // テスト項目: 同じシードの random モードは同じ行を生成し、すべてパース可能である
+        // given (前提条件):
+        let mut first =
+            Simulator::with_keyboard(SimulatorMode::Random, Duration::ZERO, Some(42), None);
+        let mut second =
+            Simulator::with_keyboard(SimulatorMode::Random, Duration::ZERO, Some(42), None);
+
+        // when (操作):
+        let first_lines: Vec<String> = (0..100).map(|_| first.read_line().unwrap()).collect();
+        let second_lines: Vec<String> = (0..100).map(|_| second.read_line().unwrap()).collect();
+
+        // then (期待する結果):
+        assert_eq!(first_lines, second_lines);
+        for line in &first_lines {
+            assert!(parse_input_line(line).is_ok(), "unparseable line: {line}");
+        }
+    }
+
+    #[test]
+    fn test_keyboard_mode_reads_keys_until_eof() {
+        // テスト項目: keyboard モードはキー入力 1 行ごとに 1 行を生成し、入力終端で EOF を返す
+        // given (前提条件):
+        let keys = io::Cursor::new("ww\nb\nd0\n");
+        let mut simulator = Simulator::with_keyboard(
+            SimulatorMode::Keyboard,
+            Duration::ZERO,
+            None,
+            Some(Box::new(keys)),
+        );
+
+        // when (操作):
+        let lines: Vec<String> = (0..3).map(|_| simulator.read_line().unwrap()).collect();
+        let eof = simulator.read_line().unwrap_err();
+
+        // then (期待する結果):
+        assert_eq!(lines[0], "0,0,0,0,0,0,0,0,0,0,1,1,0");
+        assert_eq!(lines[1], "1,0,0,0,0,0,0,0,0,0,1,1,0");
+        assert_eq!(lines[2], "0,0,0,0,0,0,0,0,0,0,0,0,0");
+        assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,262 @@
+//! 入力源の稼働状況
+//!
+//! 開いている入力源・最後に行を受信した時刻・パースエラーの数を記録する。
+//! HTTP の `/readyz` と `/status`（[`crate::websocket::health`]）で参照する。
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// パースエラーの頻度を数える期間
+const PARSE_ERROR_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default)]
+struct SourceState {
+    source: Option<String>,
+    port: Option<String>,
+    baud_rate: Option<u32>,
+    open: bool,
+    /// 入力源を開いた回数
+    opens: u64,
+    /// 最後に行を受信した時刻（経過時間の計算用と、UNIX エポックからのミリ秒）
+    last_line: Option<(Instant, i64)>,
+    lines: u64,
+    parse_errors: BTreeMap<&'static str, u64>,
+    /// 直近 1 分間にパースに失敗した時刻
+    recent_parse_errors: VecDeque<Instant>,
+}
+
+impl SourceState {
+    /// 直近 1 分間のパースエラーの数（期間外の時刻は取り除く）
+    fn parse_errors_per_minute(&mut self, now: Instant) -> u32 {
+        while let Some(at) = self.recent_parse_errors.front()
+            && now.saturating_duration_since(*at) > PARSE_ERROR_RATE_WINDOW
+        {
+            self.recent_parse_errors.pop_front();
+        }
+        self.recent_parse_errors.len() as u32
+    }
+}
+
+/// 入力源の稼働状況（入力源の再接続をまたいで累計する）
+#[derive(Debug, Default)]
+pub struct SourceStatus {
+    inner: Mutex<SourceState>,
+}
+
+/// 入力源の稼働状況
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "source": "serial(/dev/cu.usbmodem1101 @ 115200 baud)",
+///   "port": "/dev/cu.usbmodem1101",
+///   "baudRate": 115200,
+///   "open": true,
+///   "reconnects": 1,
+///   "lastLineAt": 1765600000000,
+///   "lastLineAgeMs": 85,
+///   "lines": 36000,
+///   "parseErrors": 2,
+///   "parseErrorsPerMinute": 1,
+///   "parseErrorsByKind": { "field-count": 2 }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceStatusSnapshot {
+    /// 入力源の説明（まだ開いていない場合は `null`）
+    pub source: Option<String>,
+    /// シリアルポートの名前（シリアルポート以外の入力源では `null`）
+    pub port: Option<String>,
+    /// シリアルポートのボーレート
+    pub baud_rate: Option<u32>,
+    /// 入力源を開いているか
+    pub open: bool,
+    /// 最初に開いた後に入力源を開き直した回数
+    pub reconnects: u64,
+    /// 最後に行を受信した時刻（UNIX エポックからのミリ秒）
+    pub last_line_at: Option<i64>,
+    /// 最後に行を受信してからの時間（ミリ秒）
+    pub last_line_age_ms: Option<u64>,
+    /// 受信した行の数
+    pub lines: u64,
+    /// パースに失敗した行の数
+    pub parse_errors: u64,
+    /// 直近 1 分間にパースに失敗した行の数
+    pub parse_errors_per_minute: u32,
+    /// パースに失敗した行の数（エラーの種類ごと）
+    pub parse_errors_by_kind: BTreeMap<String, u64>,
+}
+
+impl SourceStatus {
+    /// 入力源を開いたときに呼ぶ
+    pub fn set_opened(&self, source: String, port: Option<String>, baud_rate: Option<u32>) {
+        let mut state = self.lock();
+        state.source = Some(source);
+        state.port = port;
+        state.baud_rate = baud_rate;
+        state.open = true;
+        state.opens += 1;
+    }
+
+    /// 入力源の読み取りが終了したときに呼ぶ
+    pub fn set_closed(&self) {
+        self.lock().open = false;
+    }
+
+    /// 1 行受信したときに呼ぶ
+    pub fn record_line(&self, now: Instant) {
+        let mut state = self.lock();
+        state.lines += 1;
+        state.last_line = Some((now, chrono::Utc::now().timestamp_millis()));
+    }
+
+    /// 行のパースに失敗したときに呼び、直近 1 分間のパースエラーの数を返す
+    ///
+    /// ## 引数
+    ///
+    /// - `kind`: エラーの種類（[`crate::serial::input::ParseInputError::kind`]）
+    /// - `now`: 現在時刻
+    pub fn record_parse_error(&self, kind: &'static str, now: Instant) -> u32 {
+        let mut state = self.lock();
+        *state.parse_errors.entry(kind).or_default() += 1;
+        state.recent_parse_errors.push_back(now);
+        state.parse_errors_per_minute(now)
+    }
+
+    /// 最後に行を受信した時刻
+    pub fn last_line_read_at(&self) -> Option<Instant> {
+        self.lock().last_line.map(|(at, _)| at)
+    }
+
+    /// 入力源を開いていて、`max_silence` 以内に行を受信していれば `Ok`、そうでなければ理由を返す
+    pub fn readiness(&self, now: Instant, max_silence: Duration) -> Result<(), String> {
+        let state = self.lock();
+        if !state.open {
+            return Err("input source is not open".to_string());
+        }
+        match state.last_line {
+            None => Err("no line received yet".to_string()),
+            Some((at, _)) if now.saturating_duration_since(at) > max_silence => Err(format!(
+                "no line received for {}ms",
+                now.saturating_duration_since(at).as_millis()
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn snapshot(&self, now: Instant) -> SourceStatusSnapshot {
+        let mut state = self.lock();
+        SourceStatusSnapshot {
+            source: state.source.clone(),
+            port: state.port.clone(),
+            baud_rate: state.baud_rate,
+            open: state.open,
+            reconnects: state.opens.saturating_sub(1),
+            last_line_at: state.last_line.map(|(_, at)| at),
+            last_line_age_ms: state
+                .last_line
+                .map(|(at, _)| now.saturating_duration_since(at).as_millis() as u64),
+            lines: state.lines,
+            parse_errors: state.parse_errors.values().sum(),
+            parse_errors_per_minute: state.parse_errors_per_minute(now),
+            parse_errors_by_kind: state
+                .parse_errors
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SourceState> {
+        // 更新中にパニックしても、保持している値は常に整合しているのでそのまま使う
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_requires_open_source_and_recent_line() {
+        // テスト項目: 入力源を開いていて、直近に行を受信している場合のみ準備完了とみなす
+        // given (前提条件):
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let max_silence = Duration::from_millis(1000);
+        let status = SourceStatus::default();
+
+        // when (操作):
+        let before_open = status.readiness(at(0), max_silence);
+        status.set_opened(
+            "serial(/dev/ttyACM0 @ 115200 baud)".to_string(),
+            None,
+            Some(115_200),
+        );
+        let before_line = status.readiness(at(0), max_silence);
+        status.record_line(at(100));
+        let receiving = status.readiness(at(1100), max_silence);
+        let silent = status.readiness(at(1200), max_silence);
+        status.set_closed();
+        let closed = status.readiness(at(100), max_silence);
+
+        // then (期待する結果):
+        assert_eq!(before_open, Err("input source is not open".to_string()));
+        assert_eq!(before_line, Err("no line received yet".to_string()));
+        assert_eq!(receiving, Ok(()));
+        assert_eq!(silent, Err("no line received for 1100ms".to_string()));
+        assert_eq!(closed, Err("input source is not open".to_string()));
+    }
+
+    #[test]
+    fn test_snapshot_counts_lines_and_parse_errors_by_kind() {
+        // テスト項目: 受信した行とパースエラーの数を、再接続をまたいで種類ごとに累計し、直近 1 分間のエラーの数を数える
+        // given (前提条件):
+        let start = Instant::now();
+        let status = SourceStatus::default();
+        status.set_opened(
+            "serial(/dev/ttyACM0 @ 115200 baud)".to_string(),
+            Some("/dev/ttyACM0".to_string()),
+            Some(115_200),
+        );
+
+        // when (操作):
+        status.record_line(start);
+        status.record_parse_error("field-count", start);
+        status.set_closed();
+        status.set_opened(
+            "serial(/dev/ttyACM0 @ 115200 baud)".to_string(),
+            Some("/dev/ttyACM0".to_string()),
+            Some(115_200),
+        );
+        status.record_line(start);
+        status.record_parse_error("field-count", start + Duration::from_secs(30));
+        let rate = status.record_parse_error("parse-int", start + Duration::from_secs(61));
+        let snapshot = status.snapshot(start + Duration::from_secs(61));
+
+        // then (期待する結果):
+        assert!(snapshot.open);
+        assert_eq!(snapshot.reconnects, 1);
+        assert_eq!(snapshot.port.as_deref(), Some("/dev/ttyACM0"));
+        assert_eq!(snapshot.baud_rate, Some(115_200));
+        assert_eq!(snapshot.lines, 2);
+        assert_eq!(snapshot.last_line_age_ms, Some(61_000));
+        assert_eq!(snapshot.parse_errors, 3);
+        // 最初のエラーは 1 分以上前なので頻度に含めない
+        assert_eq!(rate, 2);
+        assert_eq!(snapshot.parse_errors_per_minute, 2);
+        assert_eq!(
+            snapshot.parse_errors_by_kind,
+            BTreeMap::from([("field-count".to_string(), 2), ("parse-int".to_string(), 1)])
+        );
+    }
+}
@@ -197,6 +197,11 @@ impl AppState {
 #[serde(tag = "type")]
 #[allow(non_snake_case)]
 pub enum WsMessage {
+    #[serde(rename = "hello")]
+    Hello {
+        protocolVersion: u32,
+        serverVersion: String,
+    },
     #[serde(rename = "button-input")]
     ButtonInput { isPushed: bool },
     #[serde(rename = "controller-input")]
@@ -206,4 +211,46 @@ pub enum WsMessage {
         up: u8,
         down: u8,
     },
+    #[serde(rename = "axis-input")]
+    AxisInput { x: f64, y: f64 },
+    #[serde(rename = "button-pressed")]
+    ButtonPressed,
+    #[serde(rename = "button-released")]
+    ButtonReleased,
+    #[serde(rename = "level-changed")]
+    LevelChanged { direction: String, from: u8, to: u8 },
+    #[serde(rename = "gesture")]
+    Gesture { gesture: String },
+    #[serde(rename = "state-snapshot")]
+    StateSnapshot {
+        isPushed: bool,
+        left: u8,
+        right: u8,
+        up: u8,
+        down: u8,
+    },
+    #[serde(rename = "lagged")]
+    Lagged { skipped: u64 },
+    #[serde(rename = "raw-sensor")]
+    RawSensor {
+        left: [RawElectrode; 3],
+        right: [RawElectrode; 3],
+        up: [RawElectrode; 3],
+        down: [RawElectrode; 3],
+    },
+    #[serde(rename = "diagnostic")]
+    Diagnostic {
+        kind: String,
+        message: String,
+        fieldIndex: Option<usize>,
+        rawLine: String,
+        ratePerMinute: u32,
+    },
+}
+
+/// raw-sensor メッセージの電極 1 つ分の静電容量
+#[derive(Debug, Deserialize)]
+pub struct RawElectrode {
+    pub filtered: u16,
+    pub baseline: u16,
 }
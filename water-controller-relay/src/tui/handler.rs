@@ -1,9 +1,10 @@
 //! イベントハンドラー
 
 use crossterm::event::KeyCode;
-use tracing::warn;
+use tracing::{debug, warn};
 
 use super::app::{AppState, Tab, WsMessage};
+use crate::websocket::message::PROTOCOL_VERSION;
 
 /// キーイベント処理
 ///
@@ -113,6 +114,88 @@ pub fn handle_ws_message(app_state: &mut AppState, msg: String) {
                 button_state, left, right, up, down
             ));
         }
+        Ok(WsMessage::Hello {
+            protocolVersion,
+            serverVersion,
+        }) => {
+            app_state.add_log(format!(
+                "Hello: relay v{serverVersion} (protocol {protocolVersion})"
+            ));
+            if protocolVersion != PROTOCOL_VERSION {
+                warn!(
+                    server = protocolVersion,
+                    client = PROTOCOL_VERSION,
+                    "Relay protocol version mismatch"
+                );
+            }
+        }
+        Ok(WsMessage::AxisInput { x, y }) => {
+            app_state.add_log(format!("Axis: x={x:+.2} y={y:+.2}"));
+        }
+        Ok(WsMessage::ButtonPressed) => {
+            app_state.add_log("Edge: button pressed".to_string());
+        }
+        Ok(WsMessage::ButtonReleased) => {
+            app_state.add_log("Edge: button released".to_string());
+        }
+        Ok(WsMessage::LevelChanged {
+            direction,
+            from,
+            to,
+        }) => {
+            app_state.add_log(format!("Edge: {direction} {from} -> {to}"));
+        }
+        Ok(WsMessage::Gesture { gesture }) => {
+            app_state.add_log(format!("Gesture: {gesture}"));
+        }
+        Ok(WsMessage::StateSnapshot {
+            isPushed,
+            left,
+            right,
+            up,
+            down,
+        }) => {
+            app_state.button_pushed = isPushed;
+            app_state.controller.left = left;
+            app_state.controller.right = right;
+            app_state.controller.up = up;
+            app_state.controller.down = down;
+            let button_state = if isPushed { "PUSHED" } else { "RELEASED" };
+            app_state.add_log(format!(
+                "Snapshot: {:<8} | Controller: L={} R={} U={} D={}",
+                button_state, left, right, up, down
+            ));
+        }
+        Ok(WsMessage::Lagged { skipped }) => {
+            warn!(
+                skipped,
+                "Relay skipped messages because the TUI lagged behind"
+            );
+            app_state.add_log(format!("Lagged: skipped {skipped} messages"));
+        }
+        Ok(WsMessage::RawSensor {
+            left,
+            right,
+            up,
+            down,
+        }) => {
+            // 入力のたびに届くため、画面のログには出さない
+            debug!(?left, ?right, ?up, ?down, "Raw sensor readings");
+        }
+        Ok(WsMessage::Diagnostic {
+            kind,
+            message,
+            fieldIndex,
+            rawLine,
+            ratePerMinute,
+        }) => {
+            // 入力が届かないときに、配線の不良などでパースに失敗していることを区別できるようにする
+            warn!(%kind, %message, %rawLine, ratePerMinute, "Relay failed to parse a serial line");
+            let field = fieldIndex.map_or_else(String::new, |index| format!(" field #{index}"));
+            app_state.add_log(format!(
+                "Diagnostic: {kind}{field} ({ratePerMinute}/min) '{rawLine}'"
+            ));
+        }
         Err(e) => {
             warn!("Failed to parse message: {} (error: {})", msg, e);
             app_state.add_log(format!("Parse error: {}", msg));
@@ -0,0 +1,119 @@
+//! 接続中の WebSocket クライアントの一覧
+//!
+//! 接続ごとにアドレス・エンコーディング・接続時刻・ハートビートの往復時間を記録する。
+//! `get-clients` コマンドで参照できる。
+
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::websocket::encoding::Encoding;
+
+/// 接続中のクライアントの情報
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "id": 3,
+///   "address": "127.0.0.1:53122",
+///   "encoding": "json",
+///   "connectedAt": 1765600000000,
+///   "rttMs": 0.42
+/// }
+/// ```
+///
+/// `rttMs` はハートビートの Ping から Pong までの直近の往復時間（ミリ秒）。まだ測っていない場合は `null`。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientInfo {
+    /// 接続ごとの番号（リレーサーバの起動から連番）
+    pub id: u64,
+    /// クライアントのアドレス
+    pub address: Option<String>,
+    /// この接続のエンコーディング
+    pub encoding: String,
+    /// 接続した時刻（UNIX エポックからのミリ秒）
+    pub connected_at: i64,
+    /// 直近の往復時間（ミリ秒）
+    pub rtt_ms: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u64,
+    clients: BTreeMap<u64, ClientInfo>,
+}
+
+/// 接続中のクライアントの一覧（複製しても同じ一覧を共有する）
+#[derive(Debug, Clone, Default)]
+pub struct ClientRegistry {
+    inner: Arc<Mutex<Registry>>,
+}
+
+impl ClientRegistry {
+    /// クライアントを登録する。返したハンドルを破棄すると一覧から取り除く
+    pub fn register(&self, address: Option<SocketAddr>, encoding: Encoding) -> ClientHandle {
+        let mut registry = self.lock();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.clients.insert(
+            id,
+            ClientInfo {
+                id,
+                address: address.map(|address| address.to_string()),
+                encoding: encoding.name().to_string(),
+                connected_at: chrono::Utc::now().timestamp_millis(),
+                rtt_ms: None,
+            },
+        );
+        ClientHandle {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// 接続中のクライアントを接続順に返す
+    pub fn list(&self) -> Vec<ClientInfo> {
+        self.lock().clients.values().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        // 更新中にパニックしても、保持している値は常に整合しているのでそのまま使う
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 一覧に登録したクライアントのハンドル
+#[derive(Debug)]
+pub struct ClientHandle {
+    registry: ClientRegistry,
+    id: u64,
+}
+
+impl ClientHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// ハートビートの往復時間を記録する
+    pub fn set_rtt(&self, rtt: Duration) {
+        if let Some(client) = self.registry.lock().clients.get_mut(&self.id) {
+            // マイクロ秒単位に丸める
+            client.rtt_ms = Some((rtt.as_secs_f64() * 1_000_000.0).round() / 1000.0);
+        }
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.registry.lock().clients.remove(&self.id);
+    }
+}
@@ -0,0 +1,411 @@
+//! クライアントからリレーサーバへのコマンド
+//!
+//! WebSocket クライアントが送信するテキストフレームを型付きのコマンドとして解釈し、返信を生成する。
+//! バイナリのエンコーディングで接続したクライアントは、同じ構造のコマンドをバイナリフレームで送信してもよい
+//! （詳細は [`crate::websocket::encoding`]）。返信は接続のエンコーディングで送信する。
+//!
+//! ## JSON 入力例
+//!
+//! ```json
+//! { "type": "ping", "id": 1 }
+//! { "type": "get-state", "id": 2 }
+//! { "type": "set-config", "id": 3, "config": { "paused": true, "broadcastMode": "changes" } }
+//! { "type": "simulate-input", "id": 4, "isPushed": true, "left": 3 }
+//! { "type": "subscribe", "id": 5, "topics": ["button", "gesture"], "maxRate": 10 }
+//! { "type": "get-clients", "id": 6 }
+//! ```
+//!
+//! `id` は省略可能で、返信メッセージにそのまま含めて返す。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+use crate::{
+    hub::{Hub, InputOrigin, RuntimeSettingsPatch},
+    serial::input::{ControllerValue, SerialInput},
+    websocket::{
+        encoding::Encoding,
+        message::{
+            AckMessage, ClientsMessage, ConfigMessage, ErrorMessage, PongMessage, StateMessage,
+            SubscriptionMessage,
+        },
+        subscription::Subscription,
+    },
+};
+
+/// 対応しているコマンドの `type` 一覧
+pub const COMMAND_TYPES: &[&str] = &[
+    "ping",
+    "get-state",
+    "set-config",
+    "simulate-input",
+    "subscribe",
+    "get-clients",
+];
+
+/// クライアントからのリクエスト
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClientRequest {
+    /// 返信に含めて返すリクエスト ID（任意）
+    #[serde(default)]
+    pub id: Option<Value>,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+/// クライアントからのコマンド
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientCommand {
+    /// 疎通確認。`pong` を返す
+    Ping,
+    /// 最新の入力状態と実行時設定を取得する。`state` を返す
+    GetState,
+    /// 実行時設定を変更する。`config` を返す
+    SetConfig { config: RuntimeSettingsPatch },
+    /// 入力を注入する。省略したフィールドは最新の入力状態を引き継ぐ。`ack` を返す
+    SimulateInput(SimulatedInput),
+    /// この接続で受信するトピックと配信頻度の上限を変更する。`subscription` を返す
+    Subscribe(Subscription),
+    /// 接続中のクライアントの一覧（ハートビートの往復時間を含む）を取得する。`clients` を返す
+    GetClients,
+}
+
+/// `simulate-input` コマンドのパラメータ
+///
+/// レベルは 0: Noinput, 1: Low, 2: Middle, 3: High
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedInput {
+    pub is_pushed: Option<bool>,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub up: Option<u8>,
+    pub down: Option<u8>,
+}
+
+impl SimulatedInput {
+    /// 基準となる入力に上書きして、注入する入力を組み立てる
+    fn apply_to(&self, base: Option<SerialInput>) -> Result<SerialInput, String> {
+        let mut input = base.unwrap_or_else(SerialInput::idle);
+
+        if let Some(is_pushed) = self.is_pushed {
+            input.button.is_pushed = is_pushed;
+        }
+        let levels = [
+            ("left", self.left, &mut input.controller.left),
+            ("right", self.right, &mut input.controller.right),
+            ("up", self.up, &mut input.controller.up),
+            ("down", self.down, &mut input.controller.down),
+        ];
+        for (name, level, value) in levels {
+            match level {
+                Some(level @ 0..=3) => *value = ControllerValue::from_level(level),
+                Some(level) => {
+                    return Err(format!("{name} must be between 0 and 3 (got {level})"));
+                }
+                None => {}
+            }
+        }
+
+        Ok(input)
+    }
+}
+
+/// コマンドの解釈に失敗した理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandError {
+    /// エラーコード（`invalid-json` / `invalid-frame` / `unknown-command` / `invalid-params`）
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// テキストフレームをリクエストとして解釈する
+///
+/// 失敗した場合も、可能であればリクエスト ID を返す。
+pub fn parse_request(text: &str) -> Result<ClientRequest, (Option<Value>, CommandError)> {
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        (
+            None,
+            CommandError {
+                code: "invalid-json",
+                message: e.to_string(),
+            },
+        )
+    })?;
+    parse_request_value(value)
+}
+
+/// デコード済みのフレームをリクエストとして解釈する
+///
+/// 失敗した場合も、可能であればリクエスト ID を返す。
+pub fn parse_request_value(value: Value) -> Result<ClientRequest, (Option<Value>, CommandError)> {
+    let id = value.get("id").cloned();
+
+    match value.get("type").and_then(Value::as_str) {
+        Some(command_type) if COMMAND_TYPES.contains(&command_type) => {}
+        Some(command_type) => {
+            return Err((
+                id,
+                CommandError {
+                    code: "unknown-command",
+                    message: format!("unknown command type '{command_type}'"),
+                },
+            ));
+        }
+        None => {
+            return Err((
+                id,
+                CommandError {
+                    code: "unknown-command",
+                    message: "missing command type".to_string(),
+                },
+            ));
+        }
+    }
+
+    serde_json::from_value(value).map_err(|e| {
+        (
+            id,
+            CommandError {
+                code: "invalid-params",
+                message: e.to_string(),
+            },
+        )
+    })
+}
+
+/// テキストフレームのコマンドを処理し、返信メッセージを返す
+///
+/// ## 引数
+///
+/// - `text`: クライアントから受信したテキストフレーム
+/// - `hub`: 入力源と共有するハブ
+/// - `subscription`: この接続の購読設定（`subscribe` コマンドで変更する）
+pub fn handle_command(text: &str, hub: &Hub, subscription: &watch::Sender<Subscription>) -> Value {
+    respond(parse_request(text), hub, subscription)
+}
+
+/// バイナリフレームのコマンドを接続のエンコーディングでデコードして処理し、返信メッセージを返す
+///
+/// ## 引数
+///
+/// - `bytes`: クライアントから受信したバイナリフレーム
+/// - `encoding`: この接続のエンコーディング
+/// - `hub`: 入力源と共有するハブ
+/// - `subscription`: この接続の購読設定（`subscribe` コマンドで変更する）
+pub fn handle_binary_command(
+    bytes: &[u8],
+    encoding: Encoding,
+    hub: &Hub,
+    subscription: &watch::Sender<Subscription>,
+) -> Value {
+    let request = encoding
+        .decode(bytes)
+        .map_err(|message| {
+            (
+                None,
+                CommandError {
+                    code: "invalid-frame",
+                    message,
+                },
+            )
+        })
+        .and_then(parse_request_value);
+    respond(request, hub, subscription)
+}
+
+fn respond(
+    request: Result<ClientRequest, (Option<Value>, CommandError)>,
+    hub: &Hub,
+    subscription: &watch::Sender<Subscription>,
+) -> Value {
+    let request = match request {
+        Ok(request) => request,
+        Err((id, err)) => {
+            warn!(code = err.code, error = %err.message, "Invalid command from client");
+            return to_value(&ErrorMessage::new(id, err.code, err.message));
+        }
+    };
+    debug!(command = ?request.command, "Received command from client");
+
+    let id = request.id;
+    match request.command {
+        ClientCommand::Ping => {
+            to_value(&PongMessage::new(id, chrono::Utc::now().timestamp_millis()))
+        }
+        ClientCommand::GetState => to_value(&StateMessage::new(
+            id,
+            hub.latest_input().as_ref(),
+            hub.settings(),
+            hub.frame_metrics().snapshot(),
+        )),
+        ClientCommand::SetConfig { config } => match config.validate() {
+            Ok(()) => to_value(&ConfigMessage::new(id, hub.apply_settings(config))),
+            Err(message) => to_value(&ErrorMessage::new(id, "invalid-params", message)),
+        },
+        ClientCommand::SimulateInput(simulated) => match simulated.apply_to(hub.latest_input()) {
+            Ok(input) => {
+                hub.publish_input(input, InputOrigin::Client);
+                to_value(&AckMessage::new(id, "simulate-input"))
+            }
+            Err(message) => to_value(&ErrorMessage::new(id, "invalid-params", message)),
+        },
+        ClientCommand::Subscribe(requested) => match requested.validate() {
+            Ok(()) => {
+                subscription.send_replace(requested.clone());
+                to_value(&SubscriptionMessage::new(id, requested))
+            }
+            Err(message) => to_value(&ErrorMessage::new(id, "invalid-params", message)),
+        },
+        ClientCommand::GetClients => to_value(&ClientsMessage::new(id, hub.clients().list())),
+    }
+}
+
+fn to_value<T: Serialize>(message: &T) -> Value {
+    serde_json::to_value(message).unwrap_or_else(|e| {
+        warn!(error = %e, "Failed to serialize reply");
+        serde_json::json!({
+            "type": "error",
+            "code": "internal",
+            "message": "failed to serialize reply",
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        axis::AxisConfig, filter::FilterConfig, gesture::GestureConfig, hub::RuntimeSettings,
+    };
+
+    fn reply(text: &str, hub: &Hub) -> Value {
+        let (subscription, _) = watch::channel(Subscription::default());
+        handle_command(text, hub, &subscription)
+    }
+
+    #[test]
+    fn test_parse_request_with_id() {
+        // テスト項目: id 付きの set-config コマンドをパースできる
+        // given (前提条件):
+        let text = r#"{"type":"set-config","id":7,"config":{"paused":true}}"#;
+
+        // when (操作):
+        let request = parse_request(text).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(request.id, Some(json!(7)));
+        assert_eq!(
+            request.command,
+            ClientCommand::SetConfig {
+                config: RuntimeSettingsPatch {
+                    paused: Some(true),
+                    ..Default::default()
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_ping_replies_pong_with_same_id() {
+        // テスト項目: ping コマンドに同じ id の pong を返す
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+
+        // when (操作):
+        let reply = reply(r#"{"type":"ping","id":"abc"}"#, &hub);
+
+        // then (期待する結果):
+        assert_eq!(reply["type"], "pong");
+        assert_eq!(reply["id"], "abc");
+        assert!(reply["serverTime"].is_i64());
+    }
+
+    #[test]
+    fn test_simulate_input_is_broadcast_and_reflected_in_state() {
+        // テスト項目: simulate-input の入力が配信され、get-state に反映される
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+        let mut rx = hub.subscribe();
+
+        // when (操作):
+        let ack = reply(r#"{"type":"simulate-input","id":1,"left":3}"#, &hub);
+        let state = reply(r#"{"type":"get-state","id":2}"#, &hub);
+
+        // then (期待する結果):
+        assert_eq!(
+            ack,
+            json!({"type": "ack", "id": 1, "command": "simulate-input"})
+        );
+        assert_eq!(
+            serde_json::to_string(&rx.try_recv().unwrap()).unwrap(),
+            r#"{"type":"button-input","isPushed":false}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&rx.try_recv().unwrap()).unwrap(),
+            r#"{"type":"controller-input","left":3,"right":0,"up":0,"down":0}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&rx.try_recv().unwrap()).unwrap(),
+            r#"{"type":"level-changed","direction":"left","from":0,"to":3}"#
+        );
+        assert_eq!(
+            state,
+            json!({
+                "type": "state",
+                "id": 2,
+                "input": {"isPushed": false, "left": 3, "right": 0, "up": 0, "down": 0},
+                "settings": {
+                    "paused": false,
+                    "broadcastMode": "full",
+                    "laggedPolicy": "notify",
+                    "filter": FilterConfig::default(),
+                    "rawSensor": false,
+                    "diagnostics": false,
+                    "axis": AxisConfig::default(),
+                    "gesture": GestureConfig::default()
+                },
+                "frames": {
+                    "frames": 0,
+                    "corruptFrames": 0,
+                    "droppedBytes": 0,
+                    "sequenceGaps": 0,
+                    "lostFrames": 0,
+                    "sequenceResets": 0,
+                    "invalidPayloads": 0
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors_are_classified() {
+        // テスト項目: 不正な JSON / 未知のコマンド / 不正なパラメータをそれぞれのエラーコードで返す
+        // given (前提条件):
+        let hub = Hub::new(16, RuntimeSettings::default());
+
+        // when (操作):
+        let invalid_json = reply("not json", &hub);
+        let unknown = reply(r#"{"type":"reboot","id":1}"#, &hub);
+        let invalid_level = reply(r#"{"type":"simulate-input","id":2,"up":4}"#, &hub);
+        let invalid_config = reply(r#"{"type":"set-config","config":{"foo":1}}"#, &hub);
+        let invalid_filter = reply(
+            r#"{"type":"set-config","config":{"filter":{"window":2,"majority":3}}}"#,
+            &hub,
+        );
+
+        // then (期待する結果):
+        assert_eq!(invalid_json["code"], "invalid-json");
+        assert_eq!(unknown["code"], "unknown-command");
+        assert_eq!(unknown["id"], 1);
+        assert_eq!(invalid_level["code"], "invalid-params");
+        assert_eq!(invalid_level["id"], 2);
+        assert_eq!(invalid_config["code"], "invalid-params");
+        assert_eq!(invalid_filter["code"], "invalid-params");
+    }
+}
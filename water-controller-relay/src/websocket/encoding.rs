@@ -0,0 +1,169 @@
+//! WebSocket メッセージのエンコーディング
+//!
+//! 既定では JSON のテキストフレームで送信する。高い頻度で更新を受け取るクライアントは、パースの負荷を
+//! 減らすためにバイナリのエンコーディング（MessagePack / CBOR）を選べる。バイナリのエンコーディングでは
+//! バイナリフレームで送信し、メッセージの構造（フィールド名を持つマップ）は JSON と同じにする。
+//!
+//! エンコーディングは接続時のクエリパラメータ（`/ws?encoding=msgpack`）か、
+//! `Sec-WebSocket-Protocol` ヘッダ（`msgpack` / `cbor` / `json`）で指定する。両方を指定した場合は同じエンコーディングで
+//! なければならず、食い違う場合はハンドシェイクで返すサブプロトコルと送信するフレームが一致しなくなるため接続を拒否する。
+
+use axum::{extract::ws::Message, http::HeaderValue};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// WebSocket メッセージのエンコーディング
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// JSON（テキストフレーム）
+    #[default]
+    Json,
+    /// MessagePack（バイナリフレーム）
+    MessagePack,
+    /// CBOR（バイナリフレーム）
+    Cbor,
+}
+
+impl Encoding {
+    /// `Sec-WebSocket-Protocol` で受け付けるサブプロトコル名
+    pub const PROTOCOLS: [&'static str; 3] = ["msgpack", "cbor", "json"];
+
+    /// エンコーディング名（クエリパラメータやサブプロトコル名と同じ）
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+            Self::Cbor => "cbor",
+        }
+    }
+
+    /// エンコーディング名からエンコーディングを求める
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "json" => Ok(Self::Json),
+            "msgpack" => Ok(Self::MessagePack),
+            "cbor" => Ok(Self::Cbor),
+            name => Err(format!("unknown encoding '{name}'")),
+        }
+    }
+
+    /// 接続時のクエリパラメータと選択したサブプロトコルから、エンコーディングを決める
+    ///
+    /// ## 引数
+    ///
+    /// - `query`: クエリパラメータの `encoding`
+    /// - `protocol`: `Sec-WebSocket-Protocol` から選択したサブプロトコル
+    ///
+    /// 両方を指定して食い違う場合はエラーを返す
+    pub fn negotiate(query: Option<&str>, protocol: Option<&HeaderValue>) -> Result<Self, String> {
+        let from_query = query.map(Self::parse).transpose()?;
+        let from_protocol = protocol
+            .and_then(|protocol| protocol.to_str().ok())
+            .map(Self::parse)
+            .transpose()?;
+        match (from_query, from_protocol) {
+            (Some(query), Some(protocol)) if query != protocol => Err(format!(
+                "encoding '{}' conflicts with subprotocol '{}'",
+                query.name(),
+                protocol.name()
+            )),
+            (query, protocol) => Ok(query.or(protocol).unwrap_or_default()),
+        }
+    }
+
+    /// メッセージをエンコードして WebSocket のフレームにする
+    pub fn encode<T: Serialize>(self, message: &T) -> Result<Message, String> {
+        match self {
+            Self::Json => serde_json::to_string(message)
+                .map(|json| Message::Text(json.into()))
+                .map_err(|e| e.to_string()),
+            Self::MessagePack => rmp_serde::to_vec_named(message)
+                .map(|bytes| Message::Binary(bytes.into()))
+                .map_err(|e| e.to_string()),
+            Self::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(message, &mut bytes)
+                    .map(|()| Message::Binary(bytes.into()))
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// クライアントから受信したバイナリフレームをデコードする
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Self::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            Self::Cbor => ciborium::from_reader(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        edge::Direction,
+        websocket::message::{LevelChangedMessage, RelayEvent},
+    };
+
+    #[test]
+    fn test_binary_encodings_keep_message_structure() {
+        // テスト項目: MessagePack / CBOR ではバイナリフレームになり、デコードすると JSON と同じ構造になる
+        // given (前提条件):
+        let event = RelayEvent::LevelChanged(LevelChangedMessage::new(Direction::Left, 0, 2));
+        let expected = json!({"type": "level-changed", "direction": "left", "from": 0, "to": 2});
+
+        for encoding in [Encoding::MessagePack, Encoding::Cbor] {
+            // when (操作):
+            let message = encoding.encode(&event).unwrap();
+
+            // then (期待する結果):
+            let Message::Binary(bytes) = message else {
+                panic!("{encoding:?} must be sent as a binary frame");
+            };
+            assert_eq!(encoding.decode::<Value>(&bytes).unwrap(), expected);
+        }
+        assert!(matches!(
+            Encoding::Json.encode(&event).unwrap(),
+            Message::Text(_)
+        ));
+    }
+
+    #[test]
+    fn test_negotiate_uses_query_or_protocol() {
+        // テスト項目: クエリパラメータかサブプロトコルの指定したほうを使い、どちらもなければ JSON になる
+        // given (前提条件):
+        let cbor = HeaderValue::from_static("cbor");
+
+        // when (操作):
+        let from_query = Encoding::negotiate(Some("msgpack"), None);
+        let from_protocol = Encoding::negotiate(None, Some(&cbor));
+        let both = Encoding::negotiate(Some("cbor"), Some(&cbor));
+        let default = Encoding::negotiate(None, None);
+
+        // then (期待する結果):
+        assert_eq!(from_query, Ok(Encoding::MessagePack));
+        assert_eq!(from_protocol, Ok(Encoding::Cbor));
+        assert_eq!(both, Ok(Encoding::Cbor));
+        assert_eq!(default, Ok(Encoding::Json));
+        assert!(Encoding::negotiate(Some("xml"), None).is_err());
+    }
+
+    #[test]
+    fn test_conflicting_query_and_protocol_is_rejected() {
+        // テスト項目: クエリパラメータとサブプロトコルが食い違う場合はエラーになる
+        // given (前提条件):
+        let msgpack = HeaderValue::from_static("msgpack");
+
+        // when (操作):
+        let result = Encoding::negotiate(Some("json"), Some(&msgpack));
+
+        // then (期待する結果):
+        assert_eq!(
+            result,
+            Err("encoding 'json' conflicts with subprotocol 'msgpack'".to_string())
+        );
+    }
+}
@@ -1,51 +1,248 @@
 //! WebSocket 接続ハンドラ
 
+use std::{net::SocketAddr, time::Instant};
+
 use axum::{
+    body::Bytes,
     extract::{
-        State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Query, State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
     },
-    response::IntoResponse,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures_util::{
+    sink::SinkExt,
+    stream::{SplitSink, StreamExt},
 };
-use futures_util::{sink::SinkExt, stream::StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::{broadcast::error::RecvError, mpsc, watch};
 use tracing::{debug, info, warn};
 
-use crate::websocket::server::AppState;
+use crate::{
+    hub::{BroadcastEvent, CLOSE_CODE_LAGGED, LaggedPolicy},
+    serial::input::SerialInput,
+    websocket::{
+        command::{handle_binary_command, handle_command},
+        encoding::Encoding,
+        heartbeat::{CLOSE_CODE_HEARTBEAT_TIMEOUT, Heartbeat, HeartbeatAction},
+        message::{HelloMessage, LaggedMessage, StateSnapshotMessage},
+        server::AppState,
+        subscription::{MessageGate, Subscription},
+    },
+};
+
+/// クライアントごとの返信キューのサイズ
+const REPLY_CHANNEL_SIZE: usize = 32;
+
+/// クライアントに送信するフレーム
+struct Outgoing {
+    message: Message,
+    /// 元になった行を入力源から受信した時刻（送信までの遅延をメトリクスに記録する）
+    read_at: Option<Instant>,
+}
+
+impl From<Message> for Outgoing {
+    fn from(message: Message) -> Self {
+        Self {
+            message,
+            read_at: None,
+        }
+    }
+}
+
+/// 接続時のクエリパラメータ
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectQuery {
+    /// 受信するトピック（カンマ区切り）
+    pub topics: Option<String>,
+    /// 状態メッセージの 1 秒あたりの最大配信数
+    pub max_rate: Option<u32>,
+    /// メッセージのエンコーディング（`json` / `msgpack` / `cbor`）
+    pub encoding: Option<String>,
+}
 
 /// WebSocket 接続を処理するハンドラ
 ///
 /// ## 動作
 ///
-/// - クライアント接続時にブロードキャストチャネルを subscribe
-/// - シリアルデータを JSON 形式でクライアントに送信
-/// - 一方向配信のみ（クライアントからの受信は無視）
+/// - クライアント接続時にプロトコルのバージョンを `hello` メッセージとして送信し、
+///   ブロードキャストチャネルを subscribe してから現在の入力状態を `state-snapshot` メッセージとして送信
+/// - ブロードキャストされたイベントを購読設定で選別・間引き、クライアントごとのエンコーディングで
+///   シリアライズして送信（詳細は [`crate::websocket::subscription`] / [`crate::websocket::encoding`]）
+/// - 受信が追いつかずブロードキャストチャネルのメッセージを読み飛ばした場合は、実行時設定の
+///   `lagged_policy` に従って最新の状態に追いつくか、接続を閉じる（[`LaggedPolicy`]）
+/// - クライアントからのテキストフレーム（とバイナリフレーム）をコマンドとして処理し、返信を同じ接続に送信
+///   （詳細は [`crate::websocket::command`]）
+///
+/// クエリパラメータの購読設定やエンコーディングが不正な場合は 400 Bad Request を返す。
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
+    Query(query): Query<ConnectQuery>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+) -> Response {
+    let subscription = match Subscription::from_query(query.topics.as_deref(), query.max_rate) {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            warn!(error = %e, "Rejected WebSocket client with invalid subscription");
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+    let ws = ws.protocols(Encoding::PROTOCOLS);
+    let encoding = match Encoding::negotiate(query.encoding.as_deref(), ws.selected_protocol()) {
+        Ok(encoding) => encoding,
+        Err(e) => {
+            warn!(error = %e, "Rejected WebSocket client with invalid encoding");
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, state, address, subscription, encoding))
 }
 
-async fn handle_socket(socket: WebSocket, state: AppState) {
-    info!("WebSocket client connected");
+async fn handle_socket(
+    socket: WebSocket,
+    state: AppState,
+    address: SocketAddr,
+    subscription: Subscription,
+    encoding: Encoding,
+) {
+    // 一覧から取り除くのは、送信タスクが終了してハンドルを破棄したとき
+    let client = state.hub.clients().register(Some(address), encoding);
+    info!(
+        client = client.id(),
+        %address,
+        encoding = encoding.name(),
+        "WebSocket client connected"
+    );
 
     let (mut sender, mut receiver) = socket.split();
 
+    // プロトコルのバージョンを最初に知らせる
+    match encoding.encode(&HelloMessage::new(encoding.name())) {
+        Ok(message) => {
+            if let Err(e) = sender.send(message).await {
+                warn!(error = %e, "Failed to send hello to client");
+                return;
+            }
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to serialize hello");
+        }
+    }
+
     // ブロードキャストチャネルを subscribe
-    let mut rx = state.broadcast_tx.subscribe();
+    let mut rx = state.hub.subscribe();
 
-    // 送信タスク: ブロードキャストチャネルからメッセージを受信してクライアントに送信
+    // subscribe した後にスナップショットを送信する（先に送ると、その間の入力を取りこぼす）
+    let input = state.hub.latest_input().unwrap_or_else(SerialInput::idle);
+    match encoding.encode(&StateSnapshotMessage::new(&input)) {
+        Ok(message) => {
+            debug!(?input, "Sending state snapshot to client");
+            if let Err(e) = sender.send(message).await {
+                warn!(error = %e, "Failed to send state snapshot to client");
+                return;
+            }
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to serialize state-snapshot");
+        }
+    }
+
+    // コマンドへの返信は受信タスクから送信タスクへ渡して、同じ接続に送信する
+    let (reply_tx, mut reply_rx) = mpsc::channel::<Value>(REPLY_CHANNEL_SIZE);
+    // subscribe コマンドで変更した購読設定は、返信とともに受信タスクから送信タスクへ渡す
+    let (subscription_tx, mut subscription_rx) = watch::channel(subscription.clone());
+    // 受信した Pong は、ハートビートの状態を持つ送信タスクへ渡す
+    let (pong_tx, mut pong_rx) = mpsc::channel::<(Bytes, Instant)>(REPLY_CHANNEL_SIZE);
+
+    // 送信タスク: ブロードキャストチャネルのイベントとコマンドへの返信、ハートビートの Ping をクライアントに送信
+    let heartbeat_config = state.heartbeat;
+    let hub = state.hub.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(json) = rx.recv().await {
-            debug!(message = %json, "Broadcasting to client");
-            if let Err(e) = sender.send(Message::Text(json.into())).await {
-                warn!(error = %e, "Failed to send message to client");
-                break;
+        let mut gate = MessageGate::new(subscription);
+        let mut heartbeat = Heartbeat::new(heartbeat_config, Instant::now());
+        loop {
+            // 配信頻度の上限で保留したメッセージは、送れるようになったら送る
+            let next_flush = gate.next_flush();
+            let next_heartbeat = heartbeat.next_deadline();
+            let messages = tokio::select! {
+                result = rx.recv() => match result {
+                    Ok(event) => encode_events(encoding, gate.offer(event, Instant::now())),
+                    Err(RecvError::Lagged(skipped)) => match hub.settings().lagged_policy {
+                        LaggedPolicy::Notify => {
+                            // まだ読んでいないメッセージも読み飛ばし、最新の状態を送り直す
+                            let skipped = skipped + rx.len() as u64;
+                            hub.metrics().record_lagged(skipped, LaggedPolicy::Notify);
+                            rx = rx.resubscribe();
+                            gate.discard_pending();
+                            warn!(client = client.id(), skipped, "Client lagged behind, skipping to latest state");
+                            let input = hub.latest_input().unwrap_or_else(SerialInput::idle);
+                            encode_lagged(encoding, skipped, &input)
+                        }
+                        LaggedPolicy::Disconnect => {
+                            hub.metrics().record_lagged(skipped, LaggedPolicy::Disconnect);
+                            warn!(client = client.id(), skipped, "Client lagged behind, closing connection");
+                            close(&mut sender, CLOSE_CODE_LAGGED, "lagged").await;
+                            return;
+                        }
+                    },
+                    Err(RecvError::Closed) => break,
+                },
+                Some(reply) = reply_rx.recv() => {
+                    // subscribe コマンドの返信より後のメッセージには、変更後の購読設定を適用する
+                    if subscription_rx.has_changed().unwrap_or(false) {
+                        let subscription = subscription_rx.borrow_and_update().clone();
+                        info!(?subscription, "Client subscription updated");
+                        gate.set_subscription(subscription);
+                    }
+                    debug!(message = %reply, "Replying to client");
+                    match encoding.encode(&reply) {
+                        Ok(message) => vec![message.into()],
+                        Err(e) => {
+                            warn!(error = %e, "Failed to encode reply");
+                            Vec::new()
+                        }
+                    }
+                }
+                _ = sleep_until(next_flush), if next_flush.is_some() => {
+                    encode_events(encoding, gate.flush(Instant::now()))
+                }
+                Some((payload, received_at)) = pong_rx.recv() => {
+                    if let Some(rtt) = heartbeat.on_pong(&payload, received_at) {
+                        debug!(client = client.id(), ?rtt, "Heartbeat round trip");
+                        client.set_rtt(rtt);
+                    }
+                    Vec::new()
+                }
+                _ = sleep_until(next_heartbeat), if next_heartbeat.is_some() => {
+                    match heartbeat.poll(Instant::now()) {
+                        Some(HeartbeatAction::Ping(payload)) => vec![Message::Ping(payload.into()).into()],
+                        Some(HeartbeatAction::TimedOut) => {
+                            warn!(client = client.id(), "Client did not answer ping, closing connection");
+                            close(&mut sender, CLOSE_CODE_HEARTBEAT_TIMEOUT, "heartbeat timeout").await;
+                            return;
+                        }
+                        None => Vec::new(),
+                    }
+                }
+            };
+            for Outgoing { message, read_at } in messages {
+                if let Err(e) = sender.send(message).await {
+                    warn!(error = %e, "Failed to send message to client");
+                    return;
+                }
+                if let Some(read_at) = read_at {
+                    hub.metrics().observe_latency(read_at.elapsed());
+                }
             }
         }
     });
 
-    // 受信タスク: クライアントからのメッセージを無視（一方向配信のみ）
+    // 受信タスク: クライアントからのコマンドを処理し、返信を送信タスクに渡す
+    let hub = state.hub;
     let mut recv_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
             match msg {
@@ -56,14 +253,28 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                 Ok(Message::Ping(_)) => {
                     debug!("Received ping from client");
                 }
+                Ok(Message::Pong(payload)) => {
+                    // 応答待ちの Ping は 1 つまでなので、キューがいっぱいになることはない
+                    let _ = pong_tx.try_send((payload, Instant::now()));
+                }
                 Ok(Message::Text(text)) => {
-                    debug!(text = %text, "Received text from client (ignored)");
+                    debug!(text = %text, "Received command from client");
+                    let reply = handle_command(&text, &hub, &subscription_tx);
+                    if reply_tx.send(reply).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(Message::Binary(bytes)) => {
+                    debug!(length = bytes.len(), "Received binary command from client");
+                    let reply = handle_binary_command(&bytes, encoding, &hub, &subscription_tx);
+                    if reply_tx.send(reply).await.is_err() {
+                        break;
+                    }
                 }
                 Err(e) => {
                     warn!(error = %e, "WebSocket error");
                     break;
                 }
-                _ => {}
             }
         }
     });
@@ -86,3 +297,56 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
 
     info!("WebSocket client disconnected");
 }
+
+/// ブロードキャストされたイベントを、クライアントのエンコーディングのフレームにする
+fn encode_events(
+    encoding: Encoding,
+    events: impl IntoIterator<Item = BroadcastEvent>,
+) -> Vec<Outgoing> {
+    events
+        .into_iter()
+        .filter_map(
+            |BroadcastEvent { event, read_at }| match encoding.encode(&event) {
+                Ok(message) => {
+                    debug!(?event, "Sending to client");
+                    Some(Outgoing { message, read_at })
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to encode {}", event.message_type());
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// 読み飛ばしたことを知らせる lagged メッセージと、最新の入力状態の state-snapshot メッセージのフレームにする
+fn encode_lagged(encoding: Encoding, skipped: u64, input: &SerialInput) -> Vec<Outgoing> {
+    let lagged = encoding.encode(&LaggedMessage::new(skipped));
+    let snapshot = encoding.encode(&StateSnapshotMessage::new(input));
+    [lagged, snapshot]
+        .into_iter()
+        .filter_map(|result| {
+            result
+                .inspect_err(|e| warn!(error = %e, "Failed to encode lagged notification"))
+                .ok()
+                .map(Outgoing::from)
+        })
+        .collect()
+}
+
+/// クローズフレームを送信する（送信に失敗しても、どのみち接続を閉じるので無視する）
+async fn close(sender: &mut SplitSink<WebSocket, Message>, code: u16, reason: &'static str) {
+    let _ = sender
+        .send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })))
+        .await;
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    if let Some(deadline) = deadline {
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
@@ -0,0 +1,117 @@
+//! HTTP のヘルスチェックと稼働状況のエンドポイント
+//!
+//! キオスクの監視スクリプトが、リレーサーバや入力源を再起動するかどうかを判断するために使う。
+//!
+//! - `GET /healthz`: プロセスが応答できれば 200 `ok` を返す
+//! - `GET /readyz`: 入力源を開いていて、`websocket.readyTimeoutMs` 以内に行を受信していれば 200 `ready`、
+//!   そうでなければ 503 と理由を返す
+//! - `GET /status`: 稼働状況を JSON（[`StatusReport`]）で返す
+//! - `GET /metrics`: Prometheus のテキスト形式のメトリクス（[`crate::metrics`]）を返す
+
+use std::time::Instant;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{
+    serial::framing::FrameMetricsSnapshot, source::status::SourceStatusSnapshot,
+    websocket::server::AppState,
+};
+
+/// 稼働状況
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "ready": true,
+///   "serverVersion": "0.1.0",
+///   "uptimeMs": 3600000,
+///   "source": {
+///     "source": "serial(/dev/cu.usbmodem1101 @ 115200 baud)",
+///     "port": "/dev/cu.usbmodem1101",
+///     "baudRate": 115200,
+///     "open": true,
+///     "reconnects": 1,
+///     "lastLineAt": 1765600000000,
+///     "lastLineAgeMs": 85,
+///     "lines": 36000,
+///     "parseErrors": 2,
+///     "parseErrorsPerMinute": 1,
+///     "parseErrorsByKind": { "field-count": 2 }
+///   },
+///   "frames": { "frames": 0, "corruptFrames": 0, "droppedBytes": 0, "sequenceGaps": 0, "lostFrames": 0, "sequenceResets": 0, "invalidPayloads": 0 },
+///   "clients": 2
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusReport {
+    /// `/readyz` が 200 を返す状態か
+    pub ready: bool,
+    /// リレーサーバのバージョン
+    pub server_version: String,
+    /// リレーサーバを起動してからの時間（ミリ秒）
+    pub uptime_ms: u64,
+    /// 入力源の稼働状況
+    pub source: SourceStatusSnapshot,
+    /// シリアル通信のバイナリフレームの受信状況
+    pub frames: FrameMetricsSnapshot,
+    /// 接続中の WebSocket クライアントの数
+    pub clients: usize,
+}
+
+impl StatusReport {
+    pub fn collect(state: &AppState, now: Instant) -> Self {
+        let hub = &state.hub;
+        Self {
+            ready: hub
+                .source_status()
+                .readiness(now, state.ready_timeout)
+                .is_ok(),
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_ms: now.saturating_duration_since(hub.started_at()).as_millis() as u64,
+            source: hub.source_status().snapshot(now),
+            frames: hub.frame_metrics().snapshot(),
+            clients: hub.clients().list().len(),
+        }
+    }
+}
+
+/// `GET /healthz`
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// `GET /readyz`
+pub async fn readyz(State(state): State<AppState>) -> Response {
+    match state
+        .hub
+        .source_status()
+        .readiness(Instant::now(), state.ready_timeout)
+    {
+        Ok(()) => (StatusCode::OK, "ready").into_response(),
+        Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, reason).into_response(),
+    }
+}
+
+/// `GET /status`
+pub async fn status(State(state): State<AppState>) -> Json<StatusReport> {
+    Json(StatusReport::collect(&state, Instant::now()))
+}
+
+/// `GET /metrics`
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        crate::metrics::render(&state.hub),
+    )
+}
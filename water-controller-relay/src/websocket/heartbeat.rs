@@ -0,0 +1,192 @@
+//! WebSocket 接続のハートビート
+//!
+//! サーバから一定の間隔で Ping フレームを送信し、期限までに Pong フレームが返ってこない接続を閉じる。
+//! 応答しなくなったレンダラーやスリープしたノート PC の接続が、TCP のタイムアウトまで
+//! ブロードキャストチャネルを subscribe し続けないようにする。
+//!
+//! Ping から Pong までの往復時間はクライアントごとに記録する（[`crate::websocket::clients`]）。
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Pong が期限までに返ってこなかった接続を閉じるときのクローズコード（アプリケーション定義の範囲）
+pub const CLOSE_CODE_HEARTBEAT_TIMEOUT: u16 = 4000;
+
+/// ハートビートの設定
+///
+/// ## JSON 例
+///
+/// ```json
+/// { "intervalMs": 10000, "timeoutMs": 30000 }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct HeartbeatConfig {
+    /// Ping を送信する間隔（ミリ秒）。0 の場合はハートビートを行わない
+    pub interval_ms: u64,
+    /// Ping を送信してから Pong を待つ時間（ミリ秒）。過ぎたら接続を閉じる
+    pub timeout_ms: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 10_000,
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    /// 設定値の整合性を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms > 0 && self.timeout_ms == 0 {
+            return Err("websocket.heartbeat.timeoutMs must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        (self.interval_ms > 0).then(|| Duration::from_millis(self.interval_ms))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// ハートビートで行うこと
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeartbeatAction {
+    /// このペイロードで Ping を送信する
+    Ping(Vec<u8>),
+    /// Pong が期限までに返ってこなかったので接続を閉じる
+    TimedOut,
+}
+
+/// 接続ごとのハートビートの状態
+///
+/// 応答待ちの Ping は常に 1 つまでで、Pong が返ってきてから次の間隔で Ping を送信する。
+#[derive(Debug)]
+pub struct Heartbeat {
+    config: HeartbeatConfig,
+    next_ping: Instant,
+    /// 応答待ちの Ping の番号と送信時刻
+    outstanding: Option<(u64, Instant)>,
+    sequence: u64,
+}
+
+impl Heartbeat {
+    pub fn new(config: HeartbeatConfig, now: Instant) -> Self {
+        Self {
+            next_ping: now + config.interval().unwrap_or_default(),
+            config,
+            outstanding: None,
+            sequence: 0,
+        }
+    }
+
+    /// 次に [`Heartbeat::poll`] を呼ぶべき時刻（ハートビートを行わない場合は `None`）
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.config.interval()?;
+        Some(match self.outstanding {
+            Some((_, sent)) => sent + self.config.timeout(),
+            None => self.next_ping,
+        })
+    }
+
+    /// 時刻を進め、Ping の送信か接続の切断が必要であれば返す
+    pub fn poll(&mut self, now: Instant) -> Option<HeartbeatAction> {
+        let interval = self.config.interval()?;
+        match self.outstanding {
+            Some((_, sent)) if now.duration_since(sent) >= self.config.timeout() => {
+                Some(HeartbeatAction::TimedOut)
+            }
+            None if now >= self.next_ping => {
+                self.sequence += 1;
+                self.outstanding = Some((self.sequence, now));
+                self.next_ping = now + interval;
+                Some(HeartbeatAction::Ping(self.sequence.to_be_bytes().to_vec()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Pong を受信したときに呼び、応答待ちの Ping への Pong であれば往復時間を返す
+    pub fn on_pong(&mut self, payload: &[u8], now: Instant) -> Option<Duration> {
+        let (sequence, sent) = self.outstanding?;
+        if payload != sequence.to_be_bytes() {
+            return None;
+        }
+        self.outstanding = None;
+        Some(now.duration_since(sent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HeartbeatConfig {
+        HeartbeatConfig {
+            interval_ms: 100,
+            timeout_ms: 300,
+        }
+    }
+
+    #[test]
+    fn test_pong_measures_round_trip_and_schedules_next_ping() {
+        // テスト項目: 間隔ごとに Ping を送信し、対応する Pong で往復時間を求める
+        // given (前提条件):
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut heartbeat = Heartbeat::new(config(), start);
+
+        // when (操作):
+        let early = heartbeat.poll(at(50));
+        let ping = heartbeat.poll(at(100));
+        let stale = heartbeat.on_pong(&0u64.to_be_bytes(), at(110));
+        let rtt = heartbeat.on_pong(&1u64.to_be_bytes(), at(120));
+        let next = heartbeat.next_deadline();
+
+        // then (期待する結果):
+        assert_eq!(early, None);
+        assert_eq!(
+            ping,
+            Some(HeartbeatAction::Ping(1u64.to_be_bytes().to_vec()))
+        );
+        assert_eq!(stale, None);
+        assert_eq!(rtt, Some(Duration::from_millis(20)));
+        assert_eq!(next, Some(at(200)));
+    }
+
+    #[test]
+    fn test_missing_pong_times_out() {
+        // テスト項目: Pong が期限までに返ってこなければ切断し、間隔が 0 ならハートビートを行わない
+        // given (前提条件):
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut heartbeat = Heartbeat::new(config(), start);
+        let mut disabled = Heartbeat::new(
+            HeartbeatConfig {
+                interval_ms: 0,
+                ..config()
+            },
+            start,
+        );
+
+        // when (操作):
+        heartbeat.poll(at(100));
+        let waiting = heartbeat.poll(at(350));
+        let deadline = heartbeat.next_deadline();
+        let timed_out = heartbeat.poll(at(400));
+
+        // then (期待する結果):
+        assert_eq!(waiting, None);
+        assert_eq!(deadline, Some(at(400)));
+        assert_eq!(timed_out, Some(HeartbeatAction::TimedOut));
+        assert_eq!(disabled.poll(at(1000)), None);
+        assert_eq!(disabled.next_deadline(), None);
+    }
+}
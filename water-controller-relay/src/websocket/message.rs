@@ -1,8 +1,103 @@
 //! WebSocket メッセージの DTO 定義
+//!
+//! ハブがブロードキャストするメッセージは [`RelayEvent`] にまとめ、型付きのまま各クライアントに届ける。
+//! JSON などへのシリアライズは WebSocket 層でクライアントごとに行う。
 
+use schemars::JsonSchema;
 use serde::Serialize;
+use serde_json::Value;
 
-use crate::serial::input::{ButtonInput, ControllerInput, ControllerValue};
+use crate::{
+    axis::AxisVector,
+    edge::Direction,
+    gesture::Gesture,
+    hub::RuntimeSettings,
+    serial::{
+        framing::FrameMetricsSnapshot,
+        input::{
+            ButtonInput, ControllerInput, ControllerValue, ParseInputError, RawElectrode,
+            RawSensorReadings, SerialInput,
+        },
+    },
+    websocket::{clients::ClientInfo, subscription::Subscription},
+};
+
+/// WebSocket プロトコルのバージョン
+///
+/// メッセージの構造を互換性のない形で変更したときに上げる（フィールドやメッセージの追加では上げない）。
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// ハブがブロードキャストするイベント
+///
+/// シリアライズすると、各バリアントのメッセージと同じ JSON になる。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum RelayEvent {
+    ButtonInput(ButtonInputMessage),
+    ControllerInput(ControllerInputMessage),
+    AxisInput(AxisInputMessage),
+    ButtonEdge(ButtonEdgeMessage),
+    LevelChanged(LevelChangedMessage),
+    Gesture(GestureMessage),
+    RawSensor(RawSensorMessage),
+    Diagnostic(DiagnosticMessage),
+}
+
+impl RelayEvent {
+    /// メッセージの `type`
+    pub fn message_type(&self) -> &str {
+        match self {
+            Self::ButtonInput(message) => &message.message_type,
+            Self::ControllerInput(message) => &message.message_type,
+            Self::AxisInput(message) => &message.message_type,
+            Self::ButtonEdge(message) => &message.message_type,
+            Self::LevelChanged(message) => &message.message_type,
+            Self::Gesture(message) => &message.message_type,
+            Self::RawSensor(message) => &message.message_type,
+            Self::Diagnostic(message) => &message.message_type,
+        }
+    }
+}
+
+/// hello メッセージ（接続直後、`state-snapshot` より前に 1 回だけ送信する）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "hello",
+///   "protocolVersion": 1,
+///   "serverVersion": "0.1.0",
+///   "encoding": "json"
+/// }
+/// ```
+///
+/// クライアントは `protocolVersion` が対応しているバージョンと異なる場合に警告するなどして、
+/// リレーサーバとの食い違いに気付けるようにする。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HelloMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "hello"))]
+    pub message_type: String,
+    /// WebSocket プロトコルのバージョン
+    pub protocol_version: u32,
+    /// リレーサーバのバージョン
+    pub server_version: String,
+    /// この接続のエンコーディング（`json` / `msgpack` / `cbor`）
+    pub encoding: String,
+}
+
+impl HelloMessage {
+    pub fn new(encoding: &str) -> Self {
+        Self {
+            message_type: "hello".to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+            encoding: encoding.to_string(),
+        }
+    }
+}
 
 /// button-input メッセージ
 ///
@@ -14,10 +109,11 @@ use crate::serial::input::{ButtonInput, ControllerInput, ControllerValue};
 ///   "isPushed": true
 /// }
 /// ```
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ButtonInputMessage {
     #[serde(rename = "type")]
+    #[schemars(extend("const" = "button-input"))]
     pub message_type: String,
     pub is_pushed: bool,
 }
@@ -50,14 +146,19 @@ impl ButtonInputMessage {
 /// - 1: Low (低レベル入力)
 /// - 2: Middle (中レベル入力)
 /// - 3: High (高レベル入力)
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ControllerInputMessage {
     #[serde(rename = "type")]
+    #[schemars(extend("const" = "controller-input"))]
     pub message_type: String,
+    #[schemars(range(min = 0, max = 3))]
     pub left: i32,
+    #[schemars(range(min = 0, max = 3))]
     pub right: i32,
+    #[schemars(range(min = 0, max = 3))]
     pub up: i32,
+    #[schemars(range(min = 0, max = 3))]
     pub down: i32,
 }
 
@@ -82,10 +183,594 @@ impl ControllerInputMessage {
     }
 }
 
+/// axis-input メッセージ（方向の入力から求めた 2 次元のベクトル）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "axis-input",
+///   "x": 0.6667,
+///   "y": -0.3333
+/// }
+/// ```
+///
+/// `x` は右、`y` は上が正で、ベクトルの長さは 1 以下。デッドゾーンとカーブを適用済み。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AxisInputMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "axis-input"))]
+    pub message_type: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl AxisInputMessage {
+    pub fn new(vector: AxisVector) -> Self {
+        Self {
+            message_type: "axis-input".to_string(),
+            x: vector.x,
+            y: vector.y,
+        }
+    }
+}
+
+/// 入力状態（`state` メッセージなどに埋め込む）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "isPushed": false,
+///   "left": 0,
+///   "right": 1,
+///   "up": 3,
+///   "down": 2
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InputState {
+    pub is_pushed: bool,
+    #[schemars(range(min = 0, max = 3))]
+    pub left: i32,
+    #[schemars(range(min = 0, max = 3))]
+    pub right: i32,
+    #[schemars(range(min = 0, max = 3))]
+    pub up: i32,
+    #[schemars(range(min = 0, max = 3))]
+    pub down: i32,
+}
+
+impl InputState {
+    pub fn new(input: &SerialInput) -> Self {
+        Self {
+            is_pushed: input.button.is_pushed,
+            left: ControllerInputMessage::value_to_int(&input.controller.left),
+            right: ControllerInputMessage::value_to_int(&input.controller.right),
+            up: ControllerInputMessage::value_to_int(&input.controller.up),
+            down: ControllerInputMessage::value_to_int(&input.controller.down),
+        }
+    }
+}
+
+/// state-snapshot メッセージ（接続直後に送信する現在の入力状態）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "state-snapshot",
+///   "isPushed": false,
+///   "left": 0,
+///   "right": 1,
+///   "up": 3,
+///   "down": 2
+/// }
+/// ```
+///
+/// ファームウェアは入力が変化したときにのみ行を出力するため、接続直後のクライアントが
+/// 次の変化まで古い状態を表示し続けないよう、最後に配信した入力を送信する。
+/// まだ一度も入力を受信していない場合は、入力なしの状態を送信する。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshotMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "state-snapshot"))]
+    pub message_type: String,
+    #[serde(flatten)]
+    pub input: InputState,
+}
+
+impl StateSnapshotMessage {
+    pub fn new(input: &SerialInput) -> Self {
+        Self {
+            message_type: "state-snapshot".to_string(),
+            input: InputState::new(input),
+        }
+    }
+}
+
+/// lagged メッセージ（受信が追いつかず、ブロードキャストされたメッセージを読み飛ばしたことの通知）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "lagged",
+///   "skipped": 42
+/// }
+/// ```
+///
+/// 直後に最新の入力状態を `state-snapshot` として送信するので、クライアントは表示を合わせ直せる。
+/// 読み飛ばしたエッジイベントやジェスチャーは届かない。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LaggedMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "lagged"))]
+    pub message_type: String,
+    /// 読み飛ばしたメッセージの数
+    pub skipped: u64,
+}
+
+impl LaggedMessage {
+    pub fn new(skipped: u64) -> Self {
+        Self {
+            message_type: "lagged".to_string(),
+            skipped,
+        }
+    }
+}
+
+/// button-pressed / button-released メッセージ（ボタンのエッジイベント）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "button-pressed"
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ButtonEdgeMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("enum" = ["button-pressed", "button-released"]))]
+    pub message_type: String,
+}
+
+impl ButtonEdgeMessage {
+    pub fn new(is_pushed: bool) -> Self {
+        let message_type = if is_pushed {
+            "button-pressed"
+        } else {
+            "button-released"
+        };
+        Self {
+            message_type: message_type.to_string(),
+        }
+    }
+}
+
+/// level-changed メッセージ（方向のレベルのエッジイベント）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "level-changed",
+///   "direction": "up",
+///   "from": 1,
+///   "to": 3
+/// }
+/// ```
+///
+/// `direction` は `left` / `right` / `up` / `down` のいずれか。
+/// `from` / `to` は controller-input と同じ 0〜3 の値。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelChangedMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "level-changed"))]
+    pub message_type: String,
+    pub direction: Direction,
+    #[schemars(range(max = 3))]
+    pub from: u8,
+    #[schemars(range(max = 3))]
+    pub to: u8,
+}
+
+impl LevelChangedMessage {
+    pub fn new(direction: Direction, from: u8, to: u8) -> Self {
+        Self {
+            message_type: "level-changed".to_string(),
+            direction,
+            from,
+            to,
+        }
+    }
+}
+
+/// raw-sensor メッセージ（電極ごとの静電容量）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "raw-sensor",
+///   "left": [{ "filtered": 612, "baseline": 640 }, { "filtered": 630, "baseline": 641 }, { "filtered": 639, "baseline": 642 }],
+///   "right": [...],
+///   "up": [...],
+///   "down": [...]
+/// }
+/// ```
+///
+/// 方向ごとの配列は Low/Middle/High の順。`baseline - filtered` が大きいほど強く触れている。
+/// 拡張形式の行を受信し、実行時設定の `rawSensor` が有効な場合のみ配信する。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RawSensorMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "raw-sensor"))]
+    pub message_type: String,
+    pub left: [RawElectrode; 3],
+    pub right: [RawElectrode; 3],
+    pub up: [RawElectrode; 3],
+    pub down: [RawElectrode; 3],
+}
+
+impl RawSensorMessage {
+    pub fn new(raw: &RawSensorReadings) -> Self {
+        Self {
+            message_type: "raw-sensor".to_string(),
+            left: raw.left,
+            right: raw.right,
+            up: raw.up,
+            down: raw.down,
+        }
+    }
+}
+
+/// diagnostic メッセージ（入力源から受信した行のパースエラー）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "diagnostic",
+///   "kind": "invalid-controller-triple-combination",
+///   "message": "invalid controller triple combination (field #4=0, field #5=0, field #6=1): high=1 requires middle=1, middle=1 requires low=1",
+///   "fieldIndex": 6,
+///   "rawLine": "0,0,0,0,0,0,1,0,0,0,0,0,0",
+///   "ratePerMinute": 120
+/// }
+/// ```
+///
+/// トレイの配線の不良などで入力が届かないことを、クライアントが「入力なし」と区別して表示できるようにする。
+/// 実行時設定の `diagnostics` が有効な場合のみ、エラーの種類ごとに 1 秒に 1 回まで配信する。
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "diagnostic"))]
+    pub message_type: String,
+    /// エラーの種類（`field-count` / `parse-int` / `invalid-button-value` / `invalid-bitmask` /
+    /// `invalid-raw-value` / `invalid-controller-value` / `invalid-controller-triple-combination`）
+    pub kind: String,
+    /// エラーの説明
+    pub message: String,
+    /// 不正な値が入っていたフィールドの位置（0 始まり。特定できない場合は `null`）
+    pub field_index: Option<usize>,
+    /// パースに失敗した行
+    pub raw_line: String,
+    /// 直近 1 分間のパースエラーの数（種類を問わない）
+    pub rate_per_minute: u32,
+}
+
+impl DiagnosticMessage {
+    pub fn new(error: &ParseInputError, raw_line: &str, rate_per_minute: u32) -> Self {
+        Self {
+            message_type: "diagnostic".to_string(),
+            kind: error.kind().to_string(),
+            message: error.to_string(),
+            field_index: error.field_index(),
+            raw_line: raw_line.to_string(),
+            rate_per_minute,
+        }
+    }
+}
+
+/// gesture メッセージ（入力の時系列から認識したジェスチャー）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "gesture",
+///   "gesture": "swipe-left"
+/// }
+/// ```
+///
+/// `gesture` の種類:
+/// - `swipe-left` / `swipe-right` / `swipe-up` / `swipe-down`: 反対の方向から続けて触れた
+/// - `stir-clockwise` / `stir-counter-clockwise`: 4 方向を順に触れた
+/// - `double-tap`: ボタンを 2 回続けて押した
+/// - `long-press`: ボタンを押し続けた
+/// - `splash`: 4 方向がほぼ同時に High になった
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GestureMessage {
+    #[serde(rename = "type")]
+    #[schemars(extend("const" = "gesture"))]
+    pub message_type: String,
+    pub gesture: Gesture,
+}
+
+impl GestureMessage {
+    pub fn new(gesture: Gesture) -> Self {
+        Self {
+            message_type: "gesture".to_string(),
+            gesture,
+        }
+    }
+}
+
+/// pong メッセージ（`ping` コマンドへの返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "pong",
+///   "id": 1,
+///   "serverTime": 1765600000000
+/// }
+/// ```
+///
+/// `id` はコマンドで指定された値をそのまま返す（省略時は出力しない）。
+/// `serverTime` はサーバの現在時刻（UNIX エポックからのミリ秒）。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PongMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub server_time: i64,
+}
+
+impl PongMessage {
+    pub fn new(id: Option<Value>, server_time: i64) -> Self {
+        Self {
+            message_type: "pong".to_string(),
+            id,
+            server_time,
+        }
+    }
+}
+
+/// state メッセージ（`get-state` コマンドへの返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "state",
+///   "id": "req-1",
+///   "input": { "isPushed": false, "left": 0, "right": 1, "up": 3, "down": 2 },
+///   "settings": { "paused": false },
+///   "frames": { "frames": 1200, "corruptFrames": 1, "droppedBytes": 7, "sequenceGaps": 2, "lostFrames": 3, "sequenceResets": 0, "invalidPayloads": 0 }
+/// }
+/// ```
+///
+/// まだ一度も入力を受信していない場合、`input` は `null` になる。
+/// `frames` はシリアル通信のバイナリフレームの受信状況（テキストで受信している場合はすべて 0）。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub input: Option<InputState>,
+    pub settings: RuntimeSettings,
+    pub frames: FrameMetricsSnapshot,
+}
+
+impl StateMessage {
+    pub fn new(
+        id: Option<Value>,
+        input: Option<&SerialInput>,
+        settings: RuntimeSettings,
+        frames: FrameMetricsSnapshot,
+    ) -> Self {
+        Self {
+            message_type: "state".to_string(),
+            id,
+            input: input.map(InputState::new),
+            settings,
+            frames,
+        }
+    }
+}
+
+/// config メッセージ（`set-config` コマンドへの返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "config",
+///   "id": 2,
+///   "settings": { "paused": true }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub settings: RuntimeSettings,
+}
+
+impl ConfigMessage {
+    pub fn new(id: Option<Value>, settings: RuntimeSettings) -> Self {
+        Self {
+            message_type: "config".to_string(),
+            id,
+            settings,
+        }
+    }
+}
+
+/// subscription メッセージ（`subscribe` コマンドへの返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "subscription",
+///   "id": 5,
+///   "topics": ["button", "gesture"],
+///   "maxRate": 10
+/// }
+/// ```
+///
+/// 変更後の購読設定を返す。`topics` / `maxRate` が `null` の場合は、すべてのトピック・上限なし。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    #[serde(flatten)]
+    pub subscription: Subscription,
+}
+
+impl SubscriptionMessage {
+    pub fn new(id: Option<Value>, subscription: Subscription) -> Self {
+        Self {
+            message_type: "subscription".to_string(),
+            id,
+            subscription,
+        }
+    }
+}
+
+/// clients メッセージ（`get-clients` コマンドへの返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "clients",
+///   "id": 6,
+///   "clients": [
+///     { "id": 1, "address": "127.0.0.1:53122", "encoding": "json", "connectedAt": 1765600000000, "rttMs": 0.42 }
+///   ]
+/// }
+/// ```
+///
+/// 接続中のクライアントを接続順に返す（詳細は [`ClientInfo`]）。
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientsMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub clients: Vec<ClientInfo>,
+}
+
+impl ClientsMessage {
+    pub fn new(id: Option<Value>, clients: Vec<ClientInfo>) -> Self {
+        Self {
+            message_type: "clients".to_string(),
+            id,
+            clients,
+        }
+    }
+}
+
+/// ack メッセージ（結果を返さないコマンドへの返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "ack",
+///   "id": 3,
+///   "command": "simulate-input"
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AckMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub command: String,
+}
+
+impl AckMessage {
+    pub fn new(id: Option<Value>, command: &str) -> Self {
+        Self {
+            message_type: "ack".to_string(),
+            id,
+            command: command.to_string(),
+        }
+    }
+}
+
+/// error メッセージ（コマンドの処理に失敗した場合の返信）
+///
+/// ## JSON 出力例
+///
+/// ```json
+/// {
+///   "type": "error",
+///   "id": 4,
+///   "code": "unknown-command",
+///   "message": "unknown command type 'reboot'"
+/// }
+/// ```
+///
+/// `code` の種類:
+/// - `invalid-json`: JSON としてパースできない
+/// - `unknown-command`: `type` が未知のコマンド
+/// - `invalid-params`: コマンドのパラメータが不正
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub code: String,
+    pub message: String,
+}
+
+impl ErrorMessage {
+    pub fn new(id: Option<Value>, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            message_type: "error".to_string(),
+            id,
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::serial::input::ControllerValue;
+    use crate::serial::input::{ControllerValue, parse_input_line};
 
     #[test]
     fn test_button_input_message_serialization() {
@@ -144,4 +829,85 @@ mod tests {
         assert_eq!(middle_int, 2);
         assert_eq!(high_int, 3);
     }
+
+    #[test]
+    fn test_state_snapshot_message_serialization() {
+        // テスト項目: StateSnapshotMessage がボタンと方向の値を 1 つの JSON にまとめてシリアライズされる
+        // given (前提条件):
+        let input = SerialInput {
+            button: ButtonInput { is_pushed: true },
+            controller: ControllerInput {
+                left: ControllerValue::Noinput(0),
+                right: ControllerValue::Low(1),
+                up: ControllerValue::High(1),
+                down: ControllerValue::Middle(1),
+            },
+            raw: None,
+        };
+
+        // when (操作):
+        let message = StateSnapshotMessage::new(&input);
+        let json = serde_json::to_string(&message).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(
+            json,
+            r#"{"type":"state-snapshot","isPushed":true,"left":0,"right":1,"up":3,"down":2}"#
+        );
+    }
+
+    #[test]
+    fn test_level_changed_message_serialization() {
+        // テスト項目: LevelChangedMessage が方向名と変化前後のレベル付きでシリアライズされる
+        // given (前提条件):
+        let message = LevelChangedMessage::new(Direction::Up, 1, 3);
+
+        // when (操作):
+        let json = serde_json::to_string(&message).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(
+            json,
+            r#"{"type":"level-changed","direction":"up","from":1,"to":3}"#
+        );
+    }
+
+    #[test]
+    fn test_relay_event_serializes_as_inner_message() {
+        // テスト項目: RelayEvent は中のメッセージと同じ JSON にシリアライズされ、message_type が type と一致する
+        // given (前提条件):
+        let event = RelayEvent::ButtonEdge(ButtonEdgeMessage::new(false));
+
+        // when (操作):
+        let json = serde_json::to_string(&event).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(json, r#"{"type":"button-released"}"#);
+        assert_eq!(event.message_type(), "button-released");
+    }
+
+    #[test]
+    fn test_diagnostic_message_serialization() {
+        // テスト項目: DiagnosticMessage がエラーの種類・フィールドの位置・行・頻度付きでシリアライズされる
+        // given (前提条件):
+        let line = "0,0,0,0,0,x,0,0,0,0,0,0,0";
+        let error = parse_input_line(line).unwrap_err();
+
+        // when (操作):
+        let message = DiagnosticMessage::new(&error, line, 3);
+        let json = serde_json::to_value(&message).unwrap();
+
+        // then (期待する結果):
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "diagnostic",
+                "kind": "parse-int",
+                "message": "failed to parse field #5 ('x'): invalid digit found in string",
+                "fieldIndex": 5,
+                "rawLine": line,
+                "ratePerMinute": 3,
+            })
+        );
+    }
 }
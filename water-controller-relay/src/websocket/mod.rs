@@ -1,5 +1,12 @@
 //! WebSocket サーバモジュール
 
+pub mod clients;
+pub mod command;
+pub mod encoding;
 pub mod handler;
+pub mod health;
+pub mod heartbeat;
 pub mod message;
+pub mod schema;
 pub mod server;
+pub mod subscription;
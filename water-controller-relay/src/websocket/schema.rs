@@ -0,0 +1,381 @@
+//! リレーサーバからクライアントへのメッセージのスキーマ
+//!
+//! メッセージの DTO（[`crate::websocket::message`]）から JSON Schema を生成し、さらに JSON Schema から
+//! TypeScript の型定義を生成する。Electron アプリの型定義（`websocket.generated.ts`）はこの出力をそのまま
+//! 保存したもので、DTO と食い違っていればテストが失敗する。
+//!
+//! 対象は接続中に届くメッセージ（`hello` / `state-snapshot` / ブロードキャストされるイベント）で、
+//! コマンドへの返信は含まない。
+
+use std::fmt::Write as _;
+
+use clap::ValueEnum;
+use schemars::{JsonSchema, schema_for};
+use serde_json::{Map, Value};
+
+use crate::websocket::message::{
+    HelloMessage, LaggedMessage, PROTOCOL_VERSION, RelayEvent, StateSnapshotMessage,
+};
+
+/// TypeScript の型定義で、すべてのメッセージの Union 型に付ける名前
+const UNION_NAME: &str = "WsMessage";
+/// TypeScript の型定義の 1 行の最大文字数（Electron アプリの Prettier の printWidth）
+const PRINT_WIDTH: usize = 100;
+
+/// スキーマの出力形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SchemaFormat {
+    /// JSON Schema（draft 2020-12）
+    #[default]
+    JsonSchema,
+    /// TypeScript の型定義
+    Typescript,
+}
+
+/// リレーサーバからクライアントへのメッセージ（Union 型）
+// スキーマの生成にのみ使う
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum ServerMessage {
+    Hello(HelloMessage),
+    StateSnapshot(StateSnapshotMessage),
+    Lagged(LaggedMessage),
+    Event(RelayEvent),
+}
+
+/// メッセージの JSON Schema を生成する
+///
+/// ルートに `protocolVersion`（[`PROTOCOL_VERSION`]）を含める。
+pub fn json_schema() -> Value {
+    let mut schema = schema_for!(ServerMessage).to_value();
+    if let Value::Object(root) = &mut schema {
+        root.insert("title".to_string(), Value::from(UNION_NAME));
+        root.insert("protocolVersion".to_string(), Value::from(PROTOCOL_VERSION));
+    }
+    schema
+}
+
+/// スキーマを指定した形式で出力する
+pub fn render(format: SchemaFormat) -> String {
+    match format {
+        SchemaFormat::JsonSchema => {
+            let mut json = serde_json::to_string_pretty(&json_schema())
+                .expect("JSON Schema is always serializable");
+            json.push('\n');
+            json
+        }
+        SchemaFormat::Typescript => typescript(),
+    }
+}
+
+/// メッセージの TypeScript の型定義を生成する
+///
+/// `$defs` の定義ごとに `export type` を出力し、ルートの Union 型を [`UNION_NAME`] として出力する。
+/// Electron アプリの Prettier の設定（セミコロンなし・シングルクォート）に合わせる。
+pub fn typescript() -> String {
+    let schema = json_schema();
+    let empty = Map::new();
+    let defs = schema
+        .get("$defs")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    let mut out = String::new();
+    out.push_str("/**\n");
+    out.push_str(" * WebSocket メッセージの型定義（water-controller-relay の DTO から生成）\n");
+    out.push_str(" *\n");
+    out.push_str(
+        " * 直接編集しないこと。water-controller-relay で次のコマンドを実行して更新する:\n",
+    );
+    out.push_str(" * cargo run --bin server -- schema --format typescript --output ../water-controller-app/src/lib/types/websocket.generated.ts\n");
+    out.push_str(" */\n\n");
+    out.push_str(
+        "/**\n * WebSocket プロトコルのバージョン（hello メッセージの protocolVersion）\n */\n",
+    );
+    let _ = writeln!(out, "export const PROTOCOL_VERSION = {PROTOCOL_VERSION}");
+
+    for (name, definition) in defs {
+        out.push('\n');
+        write_doc(&mut out, definition, "");
+        write_alias(&mut out, name, definition);
+    }
+
+    out.push('\n');
+    write_doc(&mut out, &schema, "");
+    write_alias(&mut out, UNION_NAME, &schema);
+    out
+}
+
+/// `export type` を出力する（1 行に収まらない Union 型は 1 行に 1 つずつ並べる）
+fn write_alias(out: &mut String, name: &str, schema: &Value) {
+    let line = format!("export type {name} = {}", ts_type(schema, ""));
+    match union_members(schema) {
+        Some(members) if line.chars().count() > PRINT_WIDTH => {
+            let _ = writeln!(out, "export type {name} =");
+            for member in members {
+                let _ = writeln!(out, "  | {member}");
+            }
+        }
+        _ => {
+            let _ = writeln!(out, "{line}");
+        }
+    }
+}
+
+/// Union 型になるスキーマの各メンバーの型
+fn union_members(schema: &Value) -> Option<Vec<String>> {
+    if schema.get("const").is_some() || schema.get("$ref").is_some() {
+        return None;
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return Some(values.iter().map(ts_literal).collect());
+    }
+    schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(Value::as_array)
+        .map(|variants| {
+            variants
+                .iter()
+                .map(|variant| ts_type(variant, ""))
+                .collect()
+        })
+}
+
+/// `description` の最初の段落を JSDoc として出力する
+fn write_doc(out: &mut String, schema: &Value, indent: &str) {
+    let Some(description) = schema.get("description").and_then(Value::as_str) else {
+        return;
+    };
+    let summary: Vec<&str> = description
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .collect();
+    if summary.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "{indent}/**");
+    for line in summary {
+        let _ = writeln!(out, "{indent} * {line}");
+    }
+    let _ = writeln!(out, "{indent} */");
+}
+
+/// スキーマを TypeScript の型の式にする
+fn ts_type(schema: &Value, indent: &str) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return reference
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference)
+            .to_string();
+    }
+    if let Some(value) = schema.get("const") {
+        return ts_literal(value);
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return ts_union(values.iter().map(ts_literal).collect());
+    }
+    if let Some(variants) = schema
+        .get("anyOf")
+        .or_else(|| schema.get("oneOf"))
+        .and_then(Value::as_array)
+    {
+        return ts_union(
+            variants
+                .iter()
+                .map(|variant| ts_type(variant, indent))
+                .collect(),
+        );
+    }
+    match schema.get("type") {
+        Some(Value::Array(types)) => ts_union(
+            types
+                .iter()
+                .map(|t| {
+                    let mut single = schema.clone();
+                    single["type"] = t.clone();
+                    ts_type(&single, indent)
+                })
+                .collect(),
+        ),
+        Some(Value::String(t)) => match t.as_str() {
+            "string" => "string".to_string(),
+            "integer" | "number" => "number".to_string(),
+            "boolean" => "boolean".to_string(),
+            "null" => "null".to_string(),
+            "array" => ts_array(schema, indent),
+            "object" => ts_object(schema, indent),
+            _ => "unknown".to_string(),
+        },
+        _ => "unknown".to_string(),
+    }
+}
+
+fn ts_literal(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("'{}'", s.replace('\'', "\\'")),
+        other => other.to_string(),
+    }
+}
+
+fn ts_union(members: Vec<String>) -> String {
+    if members.is_empty() {
+        return "never".to_string();
+    }
+    members.join(" | ")
+}
+
+/// 配列の型にする（要素数が固定の場合はタプルにする）
+fn ts_array(schema: &Value, indent: &str) -> String {
+    let items = schema
+        .get("items")
+        .map(|items| ts_type(items, indent))
+        .unwrap_or_else(|| "unknown".to_string());
+    let min = schema.get("minItems").and_then(Value::as_u64);
+    let max = schema.get("maxItems").and_then(Value::as_u64);
+    match (min, max) {
+        (Some(min), Some(max)) if min == max && max <= 8 => {
+            let items = vec![items; max as usize];
+            format!("[{}]", items.join(", "))
+        }
+        _ if items.contains(' ') => format!("Array<{items}>"),
+        _ => format!("{items}[]"),
+    }
+}
+
+/// オブジェクトの型にする（プロパティは `required` の順、つまり Rust の宣言順に並べる）
+fn ts_object(schema: &Value, indent: &str) -> String {
+    let empty = Map::new();
+    let properties = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let mut names: Vec<&str> = required
+        .iter()
+        .copied()
+        .filter(|name| properties.contains_key(*name))
+        .collect();
+    names.extend(
+        properties
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !required.contains(name)),
+    );
+
+    let inner = format!("{indent}  ");
+    let mut out = String::from("{\n");
+    for name in names {
+        let property = &properties[name];
+        write_doc(&mut out, property, &inner);
+        let optional = if required.contains(&name) { "" } else { "?" };
+        let _ = writeln!(
+            out,
+            "{inner}{name}{optional}: {}",
+            ts_type(property, &inner)
+        );
+    }
+    let _ = write!(out, "{indent}}}");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, path::Path};
+
+    use super::*;
+    use crate::{
+        edge::Direction,
+        gesture::Gesture,
+        serial::input::{RawElectrode, RawSensorReadings, SerialInput, parse_input_line},
+        tui::app::WsMessage,
+        websocket::message::{
+            AxisInputMessage, ButtonEdgeMessage, ButtonInputMessage, ControllerInputMessage,
+            DiagnosticMessage, GestureMessage, LevelChangedMessage, RawSensorMessage,
+        },
+    };
+
+    /// スキーマに含まれるすべてのメッセージの `type`
+    fn schema_message_types(schema: &Value) -> BTreeSet<String> {
+        let mut types = BTreeSet::new();
+        for definition in schema["$defs"].as_object().unwrap().values() {
+            let message_type = &definition["properties"]["type"];
+            types.extend(message_type["const"].as_str().map(str::to_string));
+            if let Some(values) = message_type["enum"].as_array() {
+                types.extend(values.iter().filter_map(Value::as_str).map(str::to_string));
+            }
+        }
+        types
+    }
+
+    #[test]
+    fn test_tui_parses_every_message_in_schema() {
+        // テスト項目: スキーマのすべてのメッセージの例を TUI のメッセージの型でパースできる
+        // given (前提条件):
+        let input = SerialInput::idle();
+        let raw = RawSensorReadings {
+            left: [RawElectrode::default(); 3],
+            right: [RawElectrode::default(); 3],
+            up: [RawElectrode::default(); 3],
+            down: [RawElectrode::default(); 3],
+        };
+        let samples = [
+            serde_json::to_value(HelloMessage::new("json")).unwrap(),
+            serde_json::to_value(StateSnapshotMessage::new(&input)).unwrap(),
+            serde_json::to_value(LaggedMessage::new(3)).unwrap(),
+            serde_json::to_value(ButtonInputMessage::new(&input.button)).unwrap(),
+            serde_json::to_value(ControllerInputMessage::new(&input.controller)).unwrap(),
+            serde_json::to_value(AxisInputMessage::new(Default::default())).unwrap(),
+            serde_json::to_value(ButtonEdgeMessage::new(true)).unwrap(),
+            serde_json::to_value(ButtonEdgeMessage::new(false)).unwrap(),
+            serde_json::to_value(LevelChangedMessage::new(Direction::Up, 0, 1)).unwrap(),
+            serde_json::to_value(GestureMessage::new(Gesture::Splash)).unwrap(),
+            serde_json::to_value(RawSensorMessage::new(&raw)).unwrap(),
+            serde_json::to_value(DiagnosticMessage::new(
+                &parse_input_line("1,1").unwrap_err(),
+                "1,1",
+                1,
+            ))
+            .unwrap(),
+        ];
+
+        // when (操作):
+        let sample_types: BTreeSet<String> = samples
+            .iter()
+            .map(|sample| sample["type"].as_str().unwrap().to_string())
+            .collect();
+        let unparsed: Vec<&Value> = samples
+            .iter()
+            .filter(|sample| serde_json::from_value::<WsMessage>((*sample).clone()).is_err())
+            .collect();
+
+        // then (期待する結果): メッセージを追加したら、この例と TUI の WsMessage にも追加する
+        assert_eq!(sample_types, schema_message_types(&json_schema()));
+        assert!(unparsed.is_empty(), "TUI cannot parse {unparsed:?}");
+    }
+
+    #[test]
+    fn test_generated_typescript_is_up_to_date() {
+        // テスト項目: Electron アプリの型定義が DTO から生成した内容と一致する
+        // given (前提条件):
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../water-controller-app/src/lib/types/websocket.generated.ts");
+
+        // when (操作):
+        let saved = std::fs::read_to_string(&path).unwrap();
+
+        // then (期待する結果): 一致しない場合は schema サブコマンドで再生成する
+        assert!(
+            saved == typescript(),
+            "{} is out of date; regenerate it with `cargo run --bin server -- schema --format typescript --output {}`",
+            path.display(),
+            path.display()
+        );
+    }
+}
@@ -1,54 +1,75 @@
 //! WebSocket サーバの実装
 
-use std::io;
+use std::{io, net::SocketAddr, time::Duration};
 
 use axum::{Router, routing::get};
-use tokio::sync::broadcast;
 use tracing::info;
 
-use crate::websocket::handler::websocket_handler;
+use crate::{
+    config::WebSocketConfig,
+    hub::Hub,
+    websocket::{handler::websocket_handler, health, heartbeat::HeartbeatConfig},
+};
 
 /// WebSocket サーバの状態を保持する構造体
 #[derive(Clone)]
 pub struct AppState {
-    /// ブロードキャストチャネルの送信側
+    /// 入力源と共有するハブ
     ///
-    /// シリアル読み取りタスクがこのチャネルにデータを送信し、
-    /// 接続中のすべての WebSocket クライアントがデータを受信する
-    pub broadcast_tx: broadcast::Sender<String>,
+    /// シリアル読み取りタスクがハブのブロードキャストチャネルにデータを送信し、
+    /// 接続中のすべての WebSocket クライアントがデータを受信する。
+    /// クライアントからのコマンドもハブを介して状態の参照・変更を行う
+    pub hub: Hub,
+    /// クライアントへの Ping の間隔と Pong を待つ時間
+    pub heartbeat: HeartbeatConfig,
+    /// `/readyz` で準備完了とみなす、最後に行を受信してからの時間の上限
+    pub ready_timeout: Duration,
 }
 
 /// WebSocket サーバを起動する
 ///
+/// `/ws` の WebSocket に加えて、同じポートでヘルスチェックと稼働状況の HTTP エンドポイント
+/// （`/healthz` / `/readyz` / `/status`。詳細は [`crate::websocket::health`]）を提供する。
+///
 /// ## 引数
 ///
-/// - `host`: バインドするホストアドレス（例: "127.0.0.1"）
-/// - `port`: バインドするポート番号（例: 8080）
-/// - `broadcast_tx`: ブロードキャストチャネルの送信側
+/// - `config`: WebSocket サーバの設定（ホストアドレス・ポート番号・ハートビート・`/readyz` の判定）
+/// - `hub`: 入力源と共有するハブ
 ///
 /// ## エラー
 ///
 /// サーバのバインドまたは起動に失敗した場合にエラーを返す
-pub async fn run_websocket_server(
-    host: &str,
-    port: u16,
-    broadcast_tx: broadcast::Sender<String>,
-) -> io::Result<()> {
-    let state = AppState { broadcast_tx };
+pub async fn run_websocket_server(config: &WebSocketConfig, hub: Hub) -> io::Result<()> {
+    let state = AppState {
+        hub,
+        heartbeat: config.heartbeat,
+        ready_timeout: Duration::from_millis(config.ready_timeout_ms),
+    };
 
     let app = Router::new()
         .route("/ws", get(websocket_handler))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/status", get(health::status))
+        .route("/metrics", get(health::metrics))
         .with_state(state);
 
-    let bind_addr = format!("{}:{}", host, port);
+    let bind_addr = format!("{}:{}", config.host, config.port);
     let listener = tokio::net::TcpListener::bind(&bind_addr)
         .await
         .map_err(|e| io::Error::new(io::ErrorKind::AddrInUse, e))?;
 
     info!("WebSocket server listening on {}", bind_addr);
     info!("Connect to: ws://{}/ws", bind_addr);
+    info!("Status: http://{}/status", bind_addr);
+    info!("Metrics: http://{}/metrics", bind_addr);
 
-    axum::serve(listener, app).await.map_err(io::Error::other)?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .map_err(io::Error::other)?;
 
     Ok(())
 }
@@ -0,0 +1,312 @@
+//! クライアントごとの購読設定
+//!
+//! ブロードキャストされるメッセージのうち、クライアントが受信するトピックと配信頻度の上限を接続ごとに指定する。
+//! 軽量なオーバーレイやロガーが、必要なメッセージだけを受信できるようにする。
+//!
+//! 購読設定は接続時のクエリパラメータ（`/ws?topics=button,gesture&maxRate=10`）か
+//! `subscribe` コマンドで指定する。指定しない場合はすべてのメッセージを制限なく受信する。
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hub::BroadcastEvent;
+
+/// メッセージのトピック
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Topic {
+    /// button-input / button-pressed / button-released
+    Button,
+    /// controller-input / level-changed
+    Controller,
+    /// axis-input
+    Axis,
+    /// gesture
+    Gesture,
+    /// raw-sensor
+    RawSensor,
+    /// diagnostic
+    Diagnostics,
+}
+
+impl Topic {
+    /// メッセージの `type` が属するトピック（コマンドへの返信などはどのトピックにも属さない）
+    pub fn of(message_type: &str) -> Option<Self> {
+        match message_type {
+            "button-input" | "button-pressed" | "button-released" => Some(Self::Button),
+            "controller-input" | "level-changed" => Some(Self::Controller),
+            "axis-input" => Some(Self::Axis),
+            "gesture" => Some(Self::Gesture),
+            "raw-sensor" => Some(Self::RawSensor),
+            "diagnostic" => Some(Self::Diagnostics),
+            _ => None,
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, String> {
+        serde_json::from_value(serde_json::Value::String(name.to_string()))
+            .map_err(|_| format!("unknown topic '{name}'"))
+    }
+}
+
+/// 配信頻度の上限を適用するメッセージ（最新の状態を表すため、間引いても最後の値が届けばよい）
+///
+/// エッジイベントやジェスチャーは間引くと取りこぼすため、上限を適用しない。
+fn is_throttled(message_type: &str) -> bool {
+    matches!(
+        message_type,
+        "button-input" | "controller-input" | "axis-input" | "raw-sensor"
+    )
+}
+
+/// クライアントの購読設定
+///
+/// ## JSON 例
+///
+/// ```json
+/// { "topics": ["button", "gesture"], "maxRate": 10 }
+/// ```
+///
+/// `topics` を省略した場合はすべてのトピック、`maxRate` を省略した場合は上限なし。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    /// 受信するトピック
+    pub topics: Option<Vec<Topic>>,
+    /// 状態メッセージ（button-input / controller-input / axis-input / raw-sensor）の
+    /// メッセージの種類ごとの 1 秒あたりの最大配信数
+    pub max_rate: Option<u32>,
+}
+
+impl Subscription {
+    /// 接続時のクエリパラメータから購読設定を作る
+    ///
+    /// ## 引数
+    ///
+    /// - `topics`: カンマ区切りのトピック名（例: "button,gesture"）
+    /// - `max_rate`: 状態メッセージの 1 秒あたりの最大配信数
+    pub fn from_query(topics: Option<&str>, max_rate: Option<u32>) -> Result<Self, String> {
+        let topics = topics
+            .map(|topics| {
+                topics
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(Topic::parse)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let subscription = Self { topics, max_rate };
+        subscription.validate()?;
+        Ok(subscription)
+    }
+
+    /// 設定値の整合性を検証する
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_rate == Some(0) {
+            return Err("maxRate must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// メッセージの `type` を受信するか
+    pub fn accepts(&self, message_type: &str) -> bool {
+        match (&self.topics, Topic::of(message_type)) {
+            (Some(topics), Some(topic)) => topics.contains(&topic),
+            _ => true,
+        }
+    }
+
+    fn min_interval(&self) -> Option<Duration> {
+        self.max_rate
+            .map(|rate| Duration::from_secs(1) / rate)
+            .filter(|interval| !interval.is_zero())
+    }
+}
+
+/// 購読設定に従ってブロードキャストされたイベントを選別・間引く
+///
+/// 配信頻度の上限を超えた状態メッセージは破棄せずに保留し、間隔が空いたときに最新のものだけを送る
+/// （クライアントが最後の状態を受け取れないままにならないようにする）。
+#[derive(Debug, Default)]
+pub struct MessageGate {
+    subscription: Subscription,
+    last_sent: HashMap<String, Instant>,
+    pending: HashMap<String, BroadcastEvent>,
+}
+
+impl MessageGate {
+    pub fn new(subscription: Subscription) -> Self {
+        Self {
+            subscription,
+            ..Default::default()
+        }
+    }
+
+    /// 購読設定を変更する（保留中のイベントは破棄する）
+    pub fn set_subscription(&mut self, subscription: Subscription) {
+        self.subscription = subscription;
+        self.pending.clear();
+    }
+
+    /// 保留中のイベントを破棄する（state-snapshot で最新の状態を送り直すとき）
+    pub fn discard_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// ブロードキャストされたイベントを受け取り、すぐに送るイベントを返す
+    ///
+    /// ## 引数
+    ///
+    /// - `event`: イベント
+    /// - `now`: 現在時刻
+    pub fn offer(&mut self, event: BroadcastEvent, now: Instant) -> Option<BroadcastEvent> {
+        let message_type = event.message_type();
+        if !self.subscription.accepts(message_type) {
+            return None;
+        }
+        let Some(interval) = self
+            .subscription
+            .min_interval()
+            .filter(|_| is_throttled(message_type))
+        else {
+            return Some(event);
+        };
+
+        match self.last_sent.get(message_type) {
+            Some(last) if now.duration_since(*last) < interval => {
+                self.pending.insert(message_type.to_string(), event);
+                None
+            }
+            _ => {
+                self.last_sent.insert(message_type.to_string(), now);
+                self.pending.remove(message_type);
+                Some(event)
+            }
+        }
+    }
+
+    /// 保留中のイベントのうち、最も早く送れるようになる時刻
+    pub fn next_flush(&self) -> Option<Instant> {
+        let interval = self.subscription.min_interval()?;
+        self.pending
+            .keys()
+            .filter_map(|message_type| self.last_sent.get(message_type))
+            .map(|last| *last + interval)
+            .min()
+    }
+
+    /// 保留中のイベントのうち、送れるようになったものを返す
+    pub fn flush(&mut self, now: Instant) -> Vec<BroadcastEvent> {
+        let Some(interval) = self.subscription.min_interval() else {
+            return self.pending.drain().map(|(_, event)| event).collect();
+        };
+        let ready: Vec<String> = self
+            .pending
+            .keys()
+            .filter(|message_type| {
+                self.last_sent
+                    .get(*message_type)
+                    .is_none_or(|last| now.duration_since(*last) >= interval)
+            })
+            .cloned()
+            .collect();
+        ready
+            .into_iter()
+            .filter_map(|message_type| {
+                let event = self.pending.remove(&message_type)?;
+                self.last_sent.insert(message_type, now);
+                Some(event)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        edge::Direction,
+        serial::input::{ControllerValue, SerialInput},
+        websocket::message::{
+            ButtonEdgeMessage, ControllerInputMessage, LevelChangedMessage, RelayEvent,
+        },
+    };
+
+    fn controller(up: u8) -> BroadcastEvent {
+        let mut controller = SerialInput::idle().controller;
+        controller.up = ControllerValue::from_level(up);
+        RelayEvent::ControllerInput(ControllerInputMessage::new(&controller)).into()
+    }
+
+    fn json(event: Option<BroadcastEvent>) -> Option<String> {
+        event.map(|event| serde_json::to_string(&event).unwrap())
+    }
+
+    #[test]
+    fn test_topics_filter_messages() {
+        // テスト項目: 購読したトピックのメッセージと、どのトピックにも属さないメッセージのみ通す
+        // given (前提条件):
+        let subscription = Subscription::from_query(Some("button, gesture"), None).unwrap();
+        let mut gate = MessageGate::new(subscription.clone());
+        let now = Instant::now();
+
+        // when (操作):
+        let button = gate.offer(
+            RelayEvent::ButtonEdge(ButtonEdgeMessage::new(true)).into(),
+            now,
+        );
+        let controller = gate.offer(controller(1), now);
+
+        // then (期待する結果):
+        assert_eq!(
+            json(button).as_deref(),
+            Some(r#"{"type":"button-pressed"}"#)
+        );
+        assert_eq!(json(controller), None);
+        assert!(subscription.accepts("pong"));
+        assert!(Subscription::from_query(Some("buttons"), None).is_err());
+        assert!(Subscription::from_query(None, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_max_rate_keeps_latest_state_and_passes_events() {
+        // テスト項目: 上限を超えた状態メッセージは最新のものだけを保留して後で送り、エッジイベントは間引かない
+        // given (前提条件): 1 秒あたり 10 回（100 ms 間隔）
+        let mut gate = MessageGate::new(Subscription {
+            topics: None,
+            max_rate: Some(10),
+        });
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let edge: BroadcastEvent =
+            RelayEvent::LevelChanged(LevelChangedMessage::new(Direction::Up, 2, 3)).into();
+
+        // when (操作):
+        let first = gate.offer(controller(1), at(0));
+        let second = gate.offer(controller(2), at(10));
+        let third = gate.offer(controller(3), at(20));
+        let edge = gate.offer(edge, at(30));
+        let next_flush = gate.next_flush();
+        let early = gate.flush(at(50));
+        let flushed: Vec<_> = gate
+            .flush(at(100))
+            .into_iter()
+            .map(Some)
+            .map(json)
+            .collect();
+
+        // then (期待する結果):
+        assert_eq!(json(first), json(Some(controller(1))));
+        assert!(second.is_none() && third.is_none());
+        assert_eq!(edge.unwrap().message_type(), "level-changed");
+        assert_eq!(next_flush, Some(at(100)));
+        assert!(early.is_empty());
+        assert_eq!(flushed, vec![json(Some(controller(3)))]);
+        assert_eq!(gate.next_flush(), None);
+    }
+}
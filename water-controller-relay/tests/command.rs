@@ -0,0 +1,268 @@
+//! クライアント → リレーサーバのコマンドの統合テスト
+
+mod common;
+
+use futures_util::SinkExt;
+use serde_json::{Value, json};
+use tokio_tungstenite::tungstenite::Message;
+use water_controller_relay::source::InputSourceConfig;
+
+/// 存在しないシリアルポートを入力源にして、入力のないリレーサーバを起動する
+fn spawn_idle_relay() -> u16 {
+    common::spawn_relay(InputSourceConfig::Serial {
+        port: "/dev/water-controller-relay-test-missing".to_string(),
+        baud: 115_200,
+    })
+}
+
+async fn send(stream: &mut common::WsStream, command: Value) {
+    stream
+        .send(Message::Text(command.to_string().into()))
+        .await
+        .expect("failed to send command");
+}
+
+#[tokio::test]
+async fn test_ping_and_get_state() {
+    // テスト項目: ping に pong、get-state に入力のない state を返す
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    send(&mut stream, json!({"type": "ping", "id": 1})).await;
+    let pong = common::recv_json(&mut stream).await;
+    send(&mut stream, json!({"type": "get-state", "id": 2})).await;
+    let state = common::recv_json(&mut stream).await;
+
+    // then (期待する結果):
+    assert_eq!(pong["type"], "pong");
+    assert_eq!(pong["id"], 1);
+    assert_eq!(
+        state,
+        json!({"type": "state", "id": 2, "input": null, "settings": {
+            "paused": false,
+            "broadcastMode": "full",
+            "laggedPolicy": "notify",
+            "filter": {"minHoldMs": 0, "window": 1, "majority": 1, "thresholds": {
+                "left": {"rise": 1, "fall": 1},
+                "right": {"rise": 1, "fall": 1},
+                "up": {"rise": 1, "fall": 1},
+                "down": {"rise": 1, "fall": 1}
+            }},
+            "rawSensor": false,
+            "diagnostics": false,
+            "axis": {"enabled": false, "deadZone": 0.0, "curve": "linear"},
+            "gesture": {
+                "enabled": false,
+                "swipeWindowMs": 400,
+                "stirWindowMs": 1500,
+                "doubleTapWindowMs": 300,
+                "longPressMs": 800,
+                "splashWindowMs": 150
+            }
+        }, "frames": {
+            "frames": 0,
+            "corruptFrames": 0,
+            "droppedBytes": 0,
+            "sequenceGaps": 0,
+            "lostFrames": 0,
+            "sequenceResets": 0,
+            "invalidPayloads": 0
+        }})
+    );
+}
+
+#[tokio::test]
+async fn test_simulate_input_is_broadcast_to_all_clients() {
+    // テスト項目: あるクライアントの simulate-input が、すべてのクライアントに配信される
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut controller = common::connect(port).await;
+    let mut listener = common::connect(port).await;
+
+    // when (操作):
+    send(
+        &mut controller,
+        json!({"type": "simulate-input", "id": "sim", "isPushed": true, "up": 2}),
+    )
+    .await;
+    let mut controller_messages = Vec::new();
+    for _ in 0..5 {
+        controller_messages.push(common::recv_json(&mut controller).await);
+    }
+    let mut listener_messages = Vec::new();
+    for _ in 0..4 {
+        listener_messages.push(common::recv_json(&mut listener).await);
+    }
+
+    // then (期待する結果):
+    let expected_broadcast = vec![
+        json!({"type": "button-input", "isPushed": true}),
+        json!({"type": "controller-input", "left": 0, "right": 0, "up": 2, "down": 0}),
+        json!({"type": "button-pressed"}),
+        json!({"type": "level-changed", "direction": "up", "from": 0, "to": 2}),
+    ];
+    assert!(controller_messages.contains(&json!({
+        "type": "ack",
+        "id": "sim",
+        "command": "simulate-input"
+    })));
+    assert!(
+        expected_broadcast
+            .iter()
+            .all(|msg| controller_messages.contains(msg))
+    );
+    assert_eq!(listener_messages, expected_broadcast);
+}
+
+#[tokio::test]
+async fn test_unknown_command_returns_error_frame() {
+    // テスト項目: 未知のコマンドには error メッセージを返し、接続は維持される
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    send(&mut stream, json!({"type": "reboot", "id": 9})).await;
+    let error = common::recv_json(&mut stream).await;
+    send(&mut stream, json!({"type": "ping"})).await;
+    let pong = common::recv_json(&mut stream).await;
+
+    // then (期待する結果):
+    assert_eq!(error["type"], "error");
+    assert_eq!(error["code"], "unknown-command");
+    assert_eq!(error["id"], 9);
+    assert_eq!(pong["type"], "pong");
+}
+
+#[tokio::test]
+async fn test_gestures_are_broadcast_after_enabling() {
+    // テスト項目: set-config でジェスチャー認識を有効にすると、ダブルタップと長押しが gesture として配信される
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut stream = common::connect(port).await;
+    send(
+        &mut stream,
+        json!({"type": "set-config", "config": {"gesture": {"enabled": true, "longPressMs": 200}}}),
+    )
+    .await;
+    let config = common::recv_json(&mut stream).await;
+
+    // when (操作): 押す → 離す → 押したままにする
+    for is_pushed in [true, false, true] {
+        send(
+            &mut stream,
+            json!({"type": "simulate-input", "isPushed": is_pushed}),
+        )
+        .await;
+    }
+    let mut gestures = Vec::new();
+    while gestures.len() < 2 {
+        let message = common::recv_json(&mut stream).await;
+        if message["type"] == "gesture" {
+            gestures.push(message["gesture"].clone());
+        }
+    }
+
+    // then (期待する結果):
+    assert_eq!(config["settings"]["gesture"]["longPressMs"], 200);
+    assert_eq!(gestures, vec![json!("double-tap"), json!("long-press")]);
+}
+
+#[tokio::test]
+async fn test_subscription_filters_messages_per_client() {
+    // テスト項目: クエリパラメータと subscribe コマンドで、接続ごとに受信するトピックを選べる
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut controller = common::connect(port).await;
+    let (mut button_only, _) = common::connect_with_query(port, "topics=button").await;
+
+    // when (操作):
+    send(
+        &mut controller,
+        json!({"type": "simulate-input", "isPushed": true, "up": 2}),
+    )
+    .await;
+    let first = common::recv_json(&mut button_only).await;
+    let second = common::recv_json(&mut button_only).await;
+    send(
+        &mut button_only,
+        json!({"type": "subscribe", "id": 1, "topics": ["controller"]}),
+    )
+    .await;
+    let subscribed = common::recv_json(&mut button_only).await;
+    send(
+        &mut controller,
+        json!({"type": "simulate-input", "isPushed": false, "up": 3}),
+    )
+    .await;
+    let third = common::recv_json(&mut button_only).await;
+
+    // then (期待する結果):
+    assert_eq!(first, json!({"type": "button-input", "isPushed": true}));
+    assert_eq!(second, json!({"type": "button-pressed"}));
+    assert_eq!(
+        subscribed,
+        json!({"type": "subscription", "id": 1, "topics": ["controller"], "maxRate": null})
+    );
+    assert_eq!(
+        third,
+        json!({"type": "controller-input", "left": 0, "right": 0, "up": 3, "down": 0})
+    );
+}
+
+#[tokio::test]
+async fn test_binary_encodings_are_negotiated_per_client() {
+    // テスト項目: クエリパラメータか Sec-WebSocket-Protocol でバイナリのエンコーディングを選ぶと、
+    //             バイナリフレームでメッセージと返信を受信し、コマンドもバイナリフレームで送信できる
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut msgpack = common::connect_raw(port, "encoding=msgpack").await;
+    let (mut cbor, protocol) = common::connect_with_protocol(port, "cbor").await;
+
+    // when (操作):
+    let hello: Value = rmp_serde::from_slice(&common::recv_binary(&mut msgpack).await).unwrap();
+    let snapshot: Value = rmp_serde::from_slice(&common::recv_binary(&mut msgpack).await).unwrap();
+    let ping = rmp_serde::to_vec_named(&json!({"type": "ping", "id": 1})).unwrap();
+    msgpack
+        .send(Message::Binary(ping.into()))
+        .await
+        .expect("failed to send command");
+    let pong: Value = rmp_serde::from_slice(&common::recv_binary(&mut msgpack).await).unwrap();
+    let cbor_hello: Value =
+        ciborium::from_reader(common::recv_binary(&mut cbor).await.as_slice()).unwrap();
+    let cbor_snapshot: Value =
+        ciborium::from_reader(common::recv_binary(&mut cbor).await.as_slice()).unwrap();
+
+    // then (期待する結果):
+    assert_eq!(
+        (&hello["type"], &hello["encoding"]),
+        (&json!("hello"), &json!("msgpack"))
+    );
+    assert_eq!(snapshot["type"], "state-snapshot");
+    assert_eq!(
+        (pong["type"].as_str(), pong["id"].as_i64()),
+        (Some("pong"), Some(1))
+    );
+    assert_eq!(protocol.as_deref(), Some("cbor"));
+    assert_eq!(cbor_hello["encoding"], "cbor");
+    assert_eq!(
+        cbor_snapshot,
+        json!({"type": "state-snapshot", "isPushed": false, "left": 0, "right": 0, "up": 0, "down": 0})
+    );
+}
+
+#[tokio::test]
+async fn test_conflicting_encoding_query_and_protocol_is_rejected() {
+    // テスト項目: クエリパラメータと Sec-WebSocket-Protocol で異なるエンコーディングを指定すると、
+    //             アップグレードを 400 で拒否する
+    // given (前提条件):
+    let port = spawn_idle_relay();
+
+    // when (操作):
+    let status = common::connect_rejected(port, "encoding=json", "msgpack").await;
+
+    // then (期待する結果):
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,211 @@
+//! 統合テスト用の共通ヘルパー
+
+#![allow(dead_code)]
+
+use std::{net::TcpListener, time::Duration};
+
+use futures_util::StreamExt;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Error, Message, client::IntoClientRequest, http::HeaderValue},
+};
+use water_controller_relay::{
+    config::{ConfigReloader, RelayConfig},
+    relay::run_loop,
+    source::InputSourceConfig,
+};
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 空いている TCP ポートを取得する
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("failed to find a free port")
+}
+
+/// リレーサーバを専用スレッドで起動し、WebSocket のポート番号を返す
+///
+/// `run_loop` はブロッキングタスクを含み終了しないため、テストのランタイムとは別のスレッド・ランタイムで動かす。
+/// スレッドはテストプロセスの終了とともに破棄される。
+pub fn spawn_relay(source: InputSourceConfig) -> u16 {
+    spawn_relay_with_config(source, RelayConfig::default(), None)
+}
+
+/// 設定と設定ファイルの監視を指定してリレーサーバを起動し、WebSocket のポート番号を返す
+///
+/// `config.websocket` のホスト・ポートは空いているポートで上書きする。
+pub fn spawn_relay_with_config(
+    source: InputSourceConfig,
+    mut config: RelayConfig,
+    reloader: Option<ConfigReloader>,
+) -> u16 {
+    let port = free_port();
+    config.websocket.host = "127.0.0.1".to_string();
+    config.websocket.port = port;
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().expect("failed to build runtime");
+        let _ = runtime.block_on(run_loop(source, None, config, reloader, None));
+    });
+    port
+}
+
+/// サーバが起動するまで接続を試行し、接続直後の hello / state-snapshot メッセージを読み捨てる
+pub async fn connect(port: u16) -> WsStream {
+    connect_with_snapshot(port).await.0
+}
+
+/// サーバが起動するまで接続を試行し、接続直後の state-snapshot メッセージとともに返す（hello は読み捨てる）
+pub async fn connect_with_snapshot(port: u16) -> (WsStream, Value) {
+    connect_with_query(port, "").await
+}
+
+/// クエリパラメータ（`topics=button` など）を付けて接続し、接続直後の state-snapshot メッセージとともに返す
+pub async fn connect_with_query(port: u16, query: &str) -> (WsStream, Value) {
+    let mut stream = connect_raw(port, query).await;
+    let hello = recv_json(&mut stream).await;
+    assert_eq!(hello["type"], "hello");
+    let snapshot = recv_json(&mut stream).await;
+    assert_eq!(snapshot["type"], "state-snapshot");
+    (stream, snapshot)
+}
+
+/// `Sec-WebSocket-Protocol` を付けて接続し、サーバが選択したサブプロトコルとともに返す
+pub async fn connect_with_protocol(port: u16, protocol: &str) -> (WsStream, Option<String>) {
+    connect_with_headers(port, "", Some(protocol)).await
+}
+
+/// サーバが起動するまで接続を試行する
+pub async fn connect_raw(port: u16, query: &str) -> WsStream {
+    connect_with_headers(port, query, None).await.0
+}
+
+async fn connect_with_headers(
+    port: u16,
+    query: &str,
+    protocol: Option<&str>,
+) -> (WsStream, Option<String>) {
+    let url = format!("ws://127.0.0.1:{port}/ws?{query}");
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let mut request = url.as_str().into_client_request().expect("invalid url");
+        if let Some(protocol) = protocol {
+            request.headers_mut().insert(
+                "Sec-WebSocket-Protocol",
+                HeaderValue::from_str(protocol).expect("invalid protocol"),
+            );
+        }
+        match connect_async(request).await {
+            Ok((stream, response)) => {
+                let selected = response
+                    .headers()
+                    .get("Sec-WebSocket-Protocol")
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                return (stream, selected);
+            }
+            Err(e) if tokio::time::Instant::now() >= deadline => {
+                panic!("failed to connect to {url}: {e}")
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+/// サーバが起動するまで接続を試行し、アップグレードを拒否されたときの HTTP ステータスコードを返す
+pub async fn connect_rejected(port: u16, query: &str, protocol: &str) -> u16 {
+    let url = format!("ws://127.0.0.1:{port}/ws?{query}");
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let mut request = url.as_str().into_client_request().expect("invalid url");
+        request.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_str(protocol).expect("invalid protocol"),
+        );
+        match connect_async(request).await {
+            Ok(_) => panic!("upgrade to {url} was not rejected"),
+            Err(Error::Http(response)) => return response.status().as_u16(),
+            Err(e) if tokio::time::Instant::now() >= deadline => {
+                panic!("failed to connect to {url}: {e}")
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+/// 次のテキストメッセージを JSON として受信する
+pub async fn recv_json(stream: &mut WsStream) -> Value {
+    try_recv_json(stream, RECV_TIMEOUT)
+        .await
+        .expect("timed out waiting for a message")
+}
+
+/// 次のテキストメッセージを JSON として受信する（タイムアウトした場合は `None`）
+pub async fn try_recv_json(stream: &mut WsStream, timeout: Duration) -> Option<Value> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let msg = tokio::time::timeout_at(deadline, stream.next())
+            .await
+            .ok()?
+            .expect("stream closed")
+            .expect("websocket error");
+        if let Message::Text(text) = msg {
+            return Some(serde_json::from_str(&text).expect("message is not valid JSON"));
+        }
+    }
+}
+
+/// 次のバイナリメッセージを受信する
+pub async fn recv_binary(stream: &mut WsStream) -> Vec<u8> {
+    let deadline = tokio::time::Instant::now() + RECV_TIMEOUT;
+    loop {
+        let msg = tokio::time::timeout_at(deadline, stream.next())
+            .await
+            .expect("timed out waiting for a message")
+            .expect("stream closed")
+            .expect("websocket error");
+        if let Message::Binary(bytes) = msg {
+            return bytes.to_vec();
+        }
+    }
+}
+
+/// サーバが起動するまで接続を試行し、HTTP の GET リクエストのステータスコードと本文を返す
+pub async fn http_get(port: u16, path: &str) -> (u16, String) {
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    let mut stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)).await {
+            Ok(stream) => break stream,
+            Err(e) if tokio::time::Instant::now() >= deadline => {
+                panic!("failed to connect to port {port}: {e}")
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    };
+    let request =
+        format!("GET {path} HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    tokio::time::timeout(RECV_TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .expect("timed out waiting for a response")
+        .unwrap();
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .expect("malformed HTTP response");
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .expect("malformed HTTP status line");
+    (status, body.to_string())
+}
@@ -0,0 +1,114 @@
+//! 設定ファイルのホットリロードの統合テスト
+
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures_util::SinkExt;
+use serde_json::{Value, json};
+use tokio_tungstenite::tungstenite::Message;
+use water_controller_relay::{
+    config::{ConfigOverrides, ConfigReloader},
+    source::InputSourceConfig,
+};
+
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn temp_config_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "water-controller-relay-{}-{name}.json",
+        std::process::id()
+    ))
+}
+
+/// 存在しないシリアルポートを入力源にして、設定ファイルを監視するリレーサーバを起動する
+fn spawn_relay_watching(path: &Path, overrides: ConfigOverrides) -> u16 {
+    let config = overrides.resolve(Some(path)).unwrap();
+    common::spawn_relay_with_config(
+        InputSourceConfig::Serial {
+            port: "/dev/water-controller-relay-test-missing".to_string(),
+            baud: 115_200,
+        },
+        config,
+        Some(ConfigReloader::new(path.to_path_buf(), overrides)),
+    )
+}
+
+async fn get_settings(stream: &mut common::WsStream) -> Value {
+    stream
+        .send(Message::Text(
+            json!({"type": "get-state"}).to_string().into(),
+        ))
+        .await
+        .unwrap();
+    common::recv_json(stream).await["settings"].clone()
+}
+
+/// 設定が条件を満たすまで get-state を繰り返す
+async fn wait_for_settings(stream: &mut common::WsStream, done: impl Fn(&Value) -> bool) -> Value {
+    let deadline = tokio::time::Instant::now() + RELOAD_TIMEOUT;
+    loop {
+        let settings = get_settings(stream).await;
+        if done(&settings) {
+            return settings;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "settings were not reloaded: {settings}"
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_config_file_change_is_applied_without_reconnect() {
+    // テスト項目: 設定ファイルを書き換えると、接続中のクライアントを切断せずに配信方法とフィルタが反映される
+    // given (前提条件):
+    let path = temp_config_path("reload");
+    std::fs::write(&path, r#"{"websocket": {"broadcastMode": "full"}}"#).unwrap();
+    let port = spawn_relay_watching(&path, ConfigOverrides::default());
+    let mut stream = common::connect(port).await;
+    assert_eq!(get_settings(&mut stream).await["broadcastMode"], "full");
+
+    // when (操作):
+    std::fs::write(
+        &path,
+        r#"{"websocket": {"broadcastMode": "changes"}, "filter": {"window": 5, "majority": 3}}"#,
+    )
+    .unwrap();
+    let settings = wait_for_settings(&mut stream, |s| s["broadcastMode"] == "changes").await;
+    std::fs::remove_file(&path).unwrap();
+
+    // then (期待する結果):
+    assert_eq!(settings["filter"]["window"], 5);
+    assert_eq!(settings["filter"]["majority"], 3);
+}
+
+#[tokio::test]
+async fn test_cli_overrides_survive_reload_and_invalid_file_is_ignored() {
+    // テスト項目: 再読み込み後もコマンドライン引数の値が優先され、不正な設定ファイルは無視される
+    // given (前提条件):
+    let path = temp_config_path("override");
+    std::fs::write(&path, r#"{"filter": {"window": 3, "majority": 2}}"#).unwrap();
+    let overrides = ConfigOverrides {
+        filter_majority: Some(1),
+        ..Default::default()
+    };
+    let port = spawn_relay_watching(&path, overrides);
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    std::fs::write(&path, r#"{"filter": {"window": 4, "majority": 4}}"#).unwrap();
+    let reloaded = wait_for_settings(&mut stream, |s| s["filter"]["window"] == 4).await;
+    std::fs::write(&path, r#"{"filter": {"window": 0}}"#).unwrap();
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let after_invalid = get_settings(&mut stream).await;
+    std::fs::remove_file(&path).unwrap();
+
+    // then (期待する結果):
+    assert_eq!(reloaded["filter"]["majority"], 1);
+    assert_eq!(after_invalid, reloaded);
+}
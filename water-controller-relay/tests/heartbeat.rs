@@ -0,0 +1,125 @@
+//! サーバからのハートビートの統合テスト
+
+mod common;
+
+use std::time::Duration;
+
+use futures_util::SinkExt;
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_tungstenite::tungstenite::Message;
+use water_controller_relay::{
+    config::RelayConfig,
+    source::InputSourceConfig,
+    websocket::heartbeat::{CLOSE_CODE_HEARTBEAT_TIMEOUT, HeartbeatConfig},
+};
+
+/// 短い間隔でハートビートを行う、入力のないリレーサーバを起動する
+fn spawn_relay_with_heartbeat() -> u16 {
+    let mut config = RelayConfig::default();
+    config.websocket.heartbeat = HeartbeatConfig {
+        interval_ms: 50,
+        timeout_ms: 200,
+    };
+    common::spawn_relay_with_config(
+        InputSourceConfig::Serial {
+            port: "/dev/water-controller-relay-test-missing".to_string(),
+            baud: 115_200,
+        },
+        config,
+        None,
+    )
+}
+
+/// フレームを 1 つ読み、オペコードとペイロードを返す（サーバからのフレームはマスクされない）
+async fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await.unwrap();
+    let length = match header[1] & 0x7f {
+        126 => {
+            let mut extended = [0u8; 2];
+            stream.read_exact(&mut extended).await.unwrap();
+            u16::from_be_bytes(extended) as usize
+        }
+        127 => {
+            let mut extended = [0u8; 8];
+            stream.read_exact(&mut extended).await.unwrap();
+            u64::from_be_bytes(extended) as usize
+        }
+        length => length as usize,
+    };
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload).await.unwrap();
+    (header[0] & 0x0f, payload)
+}
+
+#[tokio::test]
+async fn test_responsive_client_reports_round_trip() {
+    // テスト項目: Pong を返すクライアントは接続したままで、get-clients で往復時間を参照できる
+    // given (前提条件):
+    let port = spawn_relay_with_heartbeat();
+    let mut stream = common::connect(port).await;
+
+    // when (操作): 受信中に Ping に自動で Pong を返す
+    let idle = common::try_recv_json(&mut stream, Duration::from_millis(400)).await;
+    stream
+        .send(Message::Text(
+            json!({"type": "get-clients", "id": 1}).to_string().into(),
+        ))
+        .await
+        .unwrap();
+    let reply = common::recv_json(&mut stream).await;
+
+    // then (期待する結果):
+    assert_eq!(idle, None);
+    assert_eq!(reply["type"], "clients");
+    let clients = reply["clients"].as_array().unwrap();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0]["encoding"], "json");
+    assert!(clients[0]["rttMs"].is_f64());
+}
+
+#[tokio::test]
+async fn test_silent_client_is_closed_with_heartbeat_timeout() {
+    // テスト項目: Pong を返さないクライアントは、期限が過ぎるとクローズコード 4000 で切断される
+    // given (前提条件): ハンドシェイクだけ行い、その後は何も送信しないクライアント
+    let port = spawn_relay_with_heartbeat();
+    drop(common::connect(port).await);
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let request = format!(
+        "GET /ws HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(stream.read_u8().await.unwrap());
+    }
+
+    // when (操作):
+    let frames = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut opcodes = Vec::new();
+        loop {
+            let (opcode, payload) = read_frame(&mut stream).await;
+            opcodes.push(opcode);
+            if opcode == 0x8 {
+                return (opcodes, payload);
+            }
+        }
+    })
+    .await
+    .expect("connection was not closed");
+
+    // then (期待する結果): hello と state-snapshot（テキスト）、Ping の後にクローズ
+    let (opcodes, payload) = frames;
+    assert!(response.starts_with(b"HTTP/1.1 101"));
+    assert!(opcodes.contains(&0x9));
+    assert_eq!(
+        u16::from_be_bytes([payload[0], payload[1]]),
+        CLOSE_CODE_HEARTBEAT_TIMEOUT
+    );
+    assert_eq!(&payload[2..], b"heartbeat timeout");
+}
@@ -0,0 +1,118 @@
+//! 受信が追いつかないクライアントの扱いの統合テスト
+
+mod common;
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio_tungstenite::tungstenite::Message;
+use water_controller_relay::{
+    config::RelayConfig,
+    hub::{CLOSE_CODE_LAGGED, LaggedPolicy},
+    source::InputSourceConfig,
+};
+
+/// 入力を切り替える回数の上限（送信タスクが偶然追いついた場合に備えて繰り返す）
+const MAX_ATTEMPTS: u64 = 10;
+
+/// ブロードキャストチャネルのサイズを 1 にした、入力のないリレーサーバを起動する
+///
+/// 1 回の simulate-input で複数のメッセージ（入力状態とエッジイベント）を続けて配信するので、
+/// 送信タスクはすぐに追いつけなくなる。
+fn spawn_tiny_channel_relay(lagged_policy: LaggedPolicy) -> u16 {
+    let mut config = RelayConfig::default();
+    config.websocket.broadcast_channel_size = 1;
+    config.websocket.lagged_policy = lagged_policy;
+    common::spawn_relay_with_config(
+        InputSourceConfig::Serial {
+            port: "/dev/water-controller-relay-test-missing".to_string(),
+            baud: 115_200,
+        },
+        config,
+        None,
+    )
+}
+
+/// すべての方向とボタンを、`attempt` が奇数なら最大のレベルに、偶数なら入力なしにする simulate-input を送信する
+async fn toggle_all(stream: &mut common::WsStream, attempt: u64) {
+    let level = if attempt % 2 == 1 { 3 } else { 0 };
+    let command = json!({
+        "type": "simulate-input",
+        "id": attempt,
+        "isPushed": level > 0,
+        "left": level,
+        "right": level,
+        "up": level,
+        "down": level,
+    });
+    stream
+        .send(Message::Text(command.to_string().into()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_lagged_client_is_notified_and_resynchronized() {
+    // テスト項目: notify では、読み飛ばした数を lagged で知らせ、続けて最新の状態を state-snapshot で送る
+    // given (前提条件):
+    let port = spawn_tiny_channel_relay(LaggedPolicy::Notify);
+    let mut stream = common::connect(port).await;
+
+    // when (操作): lagged を受信するまで入力を切り替える
+    let mut received: Vec<Value> = Vec::new();
+    let mut attempt = 0;
+    while attempt < MAX_ATTEMPTS && !received.iter().any(|m| m["type"] == "lagged") {
+        attempt += 1;
+        received.clear();
+        toggle_all(&mut stream, attempt).await;
+        while let Some(message) =
+            common::try_recv_json(&mut stream, Duration::from_millis(200)).await
+        {
+            received.push(message);
+        }
+    }
+
+    // then (期待する結果):
+    let lagged = received
+        .iter()
+        .position(|m| m["type"] == "lagged")
+        .expect("client never lagged");
+    assert!(received[lagged]["skipped"].as_u64().unwrap() > 0);
+    let snapshot = &received[lagged + 1];
+    let level = if attempt % 2 == 1 { 3 } else { 0 };
+    assert_eq!(snapshot["type"], "state-snapshot");
+    assert_eq!(snapshot["isPushed"], level > 0);
+    assert_eq!(snapshot["left"], level);
+    assert_eq!(snapshot["down"], level);
+}
+
+#[tokio::test]
+async fn test_lagged_client_is_disconnected_with_reason() {
+    // テスト項目: disconnect では、受信が追いつかないクライアントをクローズコード 4001 で切断する
+    // given (前提条件):
+    let port = spawn_tiny_channel_relay(LaggedPolicy::Disconnect);
+    let mut stream = common::connect(port).await;
+
+    // when (操作): 接続が閉じられるまで入力を切り替える
+    let mut close = None;
+    for attempt in 1..=MAX_ATTEMPTS {
+        toggle_all(&mut stream, attempt).await;
+        while let Ok(Some(message)) =
+            tokio::time::timeout(Duration::from_millis(200), stream.next()).await
+        {
+            if let Ok(Message::Close(frame)) = message {
+                close = frame;
+                break;
+            }
+        }
+        if close.is_some() {
+            break;
+        }
+    }
+
+    // then (期待する結果):
+    let close = close.expect("client was never disconnected");
+    assert_eq!(u16::from(close.code), CLOSE_CODE_LAGGED);
+    assert_eq!(close.reason.as_str(), "lagged");
+}
@@ -0,0 +1,301 @@
+//! 疑似端末（PTY）を使ったシリアル → WebSocket 経路の統合テスト
+//!
+//! PTY のマスター側にファームウェア形式の行を書き込み、スレーブ側をシリアルポートとして開いた
+//! リレーサーバから WebSocket クライアントが受信する JSON を検証する。
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures_util::SinkExt;
+use serde_json::{Value, json};
+use serialport::{SerialPort, TTYPort};
+use tokio_tungstenite::tungstenite::Message;
+use water_controller_relay::{
+    args::DEFAULT_BAUD_RATE,
+    config::{ConfigOverrides, ConfigReloader, RelayConfig},
+    serial::{
+        channel_map::ChannelMap,
+        decoder::LineFormat,
+        device::{ProbeOptions, ProbeStatus, probe_port},
+        framing::{Framing, encode_frame},
+    },
+    source::InputSourceConfig,
+};
+
+const FEED_INTERVAL: Duration = Duration::from_millis(20);
+const FEED_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// PTY ペアを作成し、スレーブ側を指すシンボリックリンクを `link` に張る
+///
+/// リレーサーバには常に `link` を開かせることで、PTY を作り直してもポート名を変えずに再接続させる。
+fn open_pty(link: &Path) -> TTYPort {
+    let (master, slave) = TTYPort::pair().expect("failed to create pty pair");
+    let slave_name = slave.name().expect("pty slave has no name");
+    // スレーブ側はリレーサーバが開くので、テスト側のハンドルは閉じておく
+    drop(slave);
+
+    let _ = std::fs::remove_file(link);
+    std::os::unix::fs::symlink(&slave_name, link).expect("failed to create symlink to pty");
+    master
+}
+
+fn temp_link(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "water-controller-relay-{}-{name}",
+        std::process::id()
+    ))
+}
+
+/// `line` を書き込み続け、`expected` のメッセージをすべて受信したら受信済みメッセージを返す
+async fn feed_until_received(
+    master: &mut TTYPort,
+    stream: &mut common::WsStream,
+    line: &str,
+    expected: &[Value],
+) -> Vec<Value> {
+    let deadline = tokio::time::Instant::now() + FEED_TIMEOUT;
+    let mut received = Vec::new();
+
+    while !expected.iter().all(|msg| received.contains(msg)) {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "expected {expected:?} but received {received:?}"
+        );
+        // リレーサーバがスレーブ側を開くまでは書き込みが BrokenPipe になるので、無視して再試行する
+        let _ = master.write_all(format!("{line}\n").as_bytes());
+        while let Some(msg) = common::try_recv_json(stream, FEED_INTERVAL).await {
+            received.push(msg);
+        }
+    }
+
+    received
+}
+
+#[tokio::test]
+async fn test_serial_lines_are_broadcast_as_json() {
+    // テスト項目: PTY に書き込んだ行が button-input / controller-input とエッジイベントの JSON として配信される
+    // given (前提条件):
+    let link = temp_link("pty-broadcast");
+    let mut master = open_pty(&link);
+    let port = common::spawn_relay(InputSourceConfig::Serial {
+        port: link.to_string_lossy().into_owned(),
+        baud: DEFAULT_BAUD_RATE,
+    });
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    let received = feed_until_received(
+        &mut master,
+        &mut stream,
+        "1,1,0,0,0,0,0,0,0,0,0,0,0",
+        &[
+            json!({"type": "button-input", "isPushed": true}),
+            json!({"type": "controller-input", "left": 0, "right": 0, "up": 0, "down": 1}),
+        ],
+    )
+    .await;
+
+    // then (期待する結果):
+    assert!(received.contains(&json!({"type": "button-pressed"})));
+    assert!(received.contains(&json!({
+        "type": "level-changed",
+        "direction": "down",
+        "from": 0,
+        "to": 1
+    })));
+    let _ = std::fs::remove_file(&link);
+}
+
+#[tokio::test]
+async fn test_relay_reconnects_after_pty_is_reopened() {
+    // テスト項目: PTY を閉じて作り直しても、リレーサーバが再接続し同じクライアントへ配信を続ける
+    // given (前提条件):
+    let link = temp_link("pty-reconnect");
+    let mut master = open_pty(&link);
+    let port = common::spawn_relay(InputSourceConfig::Serial {
+        port: link.to_string_lossy().into_owned(),
+        baud: DEFAULT_BAUD_RATE,
+    });
+    let mut stream = common::connect(port).await;
+    feed_until_received(
+        &mut master,
+        &mut stream,
+        "0,0,0,0,0,0,0,0,0,0,0,0,0",
+        &[json!({"type": "controller-input", "left": 0, "right": 0, "up": 0, "down": 0})],
+    )
+    .await;
+
+    // when (操作):
+    drop(master);
+    let mut master = open_pty(&link);
+    let received = feed_until_received(
+        &mut master,
+        &mut stream,
+        "0,0,0,0,1,1,1,0,0,0,0,0,0",
+        &[json!({"type": "controller-input", "left": 3, "right": 0, "up": 0, "down": 0})],
+    )
+    .await;
+
+    // then (期待する結果):
+    assert!(received.contains(&json!({"type": "button-input", "isPushed": false})));
+    let _ = std::fs::remove_file(&link);
+}
+
+#[tokio::test]
+async fn test_port_change_applies_while_current_port_is_silent() {
+    // テスト項目: 開いているポートから行が届かなくなっても、設定ファイルで変更したポートを開き直す
+    // given (前提条件):
+    let silent_link = temp_link("pty-silent");
+    let next_link = temp_link("pty-next");
+    let mut silent = open_pty(&silent_link);
+    let mut next = open_pty(&next_link);
+    let path = std::env::temp_dir().join(format!(
+        "water-controller-relay-{}-pty-port-change.json",
+        std::process::id()
+    ));
+    let write_config = |link: &Path| {
+        let json = json!({"serial": {"port": link.to_string_lossy(), "readTimeoutMs": 100}});
+        std::fs::write(&path, json.to_string()).unwrap();
+    };
+    write_config(&silent_link);
+    let overrides = ConfigOverrides::default();
+    let port = common::spawn_relay_with_config(
+        InputSourceConfig::Serial {
+            port: silent_link.to_string_lossy().into_owned(),
+            baud: DEFAULT_BAUD_RATE,
+        },
+        overrides.resolve(Some(&path)).unwrap(),
+        Some(ConfigReloader::new(path.clone(), overrides)),
+    );
+    let mut stream = common::connect(port).await;
+    feed_until_received(
+        &mut silent,
+        &mut stream,
+        "0,0,0,0,0,0,0,0,0,0,0,0,0",
+        &[json!({"type": "button-input", "isPushed": false})],
+    )
+    .await;
+
+    // when (操作): 最初のポートには何も書き込まないまま、ポートを変更する
+    write_config(&next_link);
+    let received = feed_until_received(
+        &mut next,
+        &mut stream,
+        "1,0,0,0,0,0,0,0,0,0,0,0,0",
+        &[json!({"type": "button-input", "isPushed": true})],
+    )
+    .await;
+
+    // then (期待する結果):
+    assert!(received.contains(&json!({"type": "button-pressed"})));
+    drop(silent);
+    let _ = std::fs::remove_file(&silent_link);
+    let _ = std::fs::remove_file(&next_link);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_probe_reports_firmware_lines() {
+    // テスト項目: ファームウェア形式の行を出力しているポートを調べると ok になる
+    // given (前提条件):
+    let link = temp_link("pty-probe");
+    let mut master = open_pty(&link);
+    let feeder = std::thread::spawn(move || {
+        for _ in 0..40 {
+            let _ = master.write_all(b"0,1,0,0,0,0,0,0,0,0,0,0,0\n");
+            std::thread::sleep(FEED_INTERVAL);
+        }
+    });
+
+    // when (操作):
+    let result = probe_port(
+        &link.to_string_lossy(),
+        ProbeOptions {
+            baud_rate: DEFAULT_BAUD_RATE,
+            line_format: LineFormat::Csv13,
+            channel_map: ChannelMap::default(),
+            timeout: Duration::from_millis(500),
+        },
+    );
+
+    // then (期待する結果):
+    feeder.join().unwrap();
+    assert_eq!(result.status, ProbeStatus::Ok, "{result:?}");
+    assert_eq!(result.lines_parsed, result.lines_read);
+    assert_eq!(result.sample[0], "0,1,0,0,0,0,0,0,0,0,0,0,0");
+    let _ = std::fs::remove_file(&link);
+}
+
+#[tokio::test]
+async fn test_binary_frames_report_corruption_and_gaps() {
+    // テスト項目: バイナリフレームの行が配信され、破損したフレームとシーケンス番号の飛びが state に反映される
+    // given (前提条件):
+    let link = temp_link("pty-binary");
+    let mut master = open_pty(&link);
+    let mut config = RelayConfig::default();
+    config.serial.framing = Framing::Binary;
+    let port = common::spawn_relay_with_config(
+        InputSourceConfig::Serial {
+            port: link.to_string_lossy().into_owned(),
+            baud: DEFAULT_BAUD_RATE,
+        },
+        config,
+        None,
+    );
+    let mut stream = common::connect(port).await;
+    let idle = "0,0,0,0,0,0,0,0,0,0,0,0,0";
+    let left = "0,0,0,0,1,1,1,0,0,0,0,0,0";
+
+    // リレーサーバがスレーブ側を開くまで、同じシーケンス番号のフレームを書き込み続ける
+    let deadline = tokio::time::Instant::now() + FEED_TIMEOUT;
+    while common::try_recv_json(&mut stream, FEED_INTERVAL)
+        .await
+        .is_none()
+    {
+        assert!(tokio::time::Instant::now() < deadline, "no frame received");
+        let _ = master.write_all(&encode_frame(0, idle.as_bytes()).unwrap());
+    }
+    while common::try_recv_json(&mut stream, FEED_INTERVAL)
+        .await
+        .is_some()
+    {}
+
+    // when (操作): 破損したフレームと、シーケンス番号を 2 つ飛ばしたフレームを書き込む
+    let mut corrupt = encode_frame(1, left.as_bytes()).unwrap();
+    corrupt[4] ^= 0x01;
+    master.write_all(&corrupt).unwrap();
+    master
+        .write_all(&encode_frame(3, left.as_bytes()).unwrap())
+        .unwrap();
+    let mut received = Vec::new();
+    let expected = json!({"type": "controller-input", "left": 3, "right": 0, "up": 0, "down": 0});
+    while !received.contains(&expected) {
+        received.push(common::recv_json(&mut stream).await);
+    }
+    stream
+        .send(Message::Text(
+            json!({"type": "get-state"}).to_string().into(),
+        ))
+        .await
+        .unwrap();
+    // controller-input に続くエッジイベントを読み飛ばす
+    let state = loop {
+        let msg = common::recv_json(&mut stream).await;
+        if msg["type"] == "state" {
+            break msg;
+        }
+    };
+
+    // then (期待する結果):
+    assert_eq!(state["frames"]["corruptFrames"], 1);
+    assert_eq!(state["frames"]["sequenceGaps"], 1);
+    assert_eq!(state["frames"]["lostFrames"], 2);
+    let _ = std::fs::remove_file(&link);
+}
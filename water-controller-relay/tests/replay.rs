@@ -0,0 +1,92 @@
+//! キャプチャ再生の統合テスト
+//!
+//! キャプチャファイル → パース → ブロードキャスト → WebSocket の経路を検証する
+
+mod common;
+
+use serde_json::json;
+use water_controller_relay::{
+    config::RelayConfig, serial::decoder::LineFormat, source::InputSourceConfig,
+};
+
+#[tokio::test]
+async fn test_replayed_capture_is_broadcast_to_websocket_client() {
+    // テスト項目: ループ再生したキャプチャの行が button-input / controller-input として配信される
+    // given (前提条件):
+    let path = std::env::temp_dir().join(format!(
+        "water-controller-relay-{}-replay-test.capture",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        "# water-controller-relay capture v1\n20\t1,1,1,1,0,0,0,0,0,0,0,0,0\n",
+    )
+    .unwrap();
+    let port = common::spawn_relay(InputSourceConfig::Replay {
+        path: path.clone(),
+        speed: 1.0,
+        looped: true,
+    });
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    let mut messages = Vec::new();
+    for _ in 0..2 {
+        messages.push(common::recv_json(&mut stream).await);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // then (期待する結果):
+    assert!(messages.contains(&json!({"type": "button-input", "isPushed": true})));
+    assert!(messages.contains(&json!({
+        "type": "controller-input",
+        "left": 0,
+        "right": 0,
+        "up": 0,
+        "down": 3
+    })));
+}
+
+#[tokio::test]
+async fn test_replayed_capture_uses_configured_line_format() {
+    // テスト項目: 設定した行形式（proto8）でキャプチャの行がデコードされ、通知行は読み飛ばされる
+    // given (前提条件):
+    let path = std::env::temp_dir().join(format!(
+        "water-controller-relay-{}-replay-proto8-test.capture",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        "# water-controller-relay capture v1\n10\t8 touched\n20\t0,0,0,0,0,0,1,1\n30\t\n",
+    )
+    .unwrap();
+    let mut config = RelayConfig::default();
+    config.serial.line_format = LineFormat::Proto8;
+    let port = common::spawn_relay_with_config(
+        InputSourceConfig::Replay {
+            path: path.clone(),
+            speed: 1.0,
+            looped: true,
+        },
+        config,
+        None,
+    );
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    let mut messages = Vec::new();
+    for _ in 0..2 {
+        messages.push(common::recv_json(&mut stream).await);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // then (期待する結果):
+    assert!(messages.contains(&json!({"type": "button-input", "isPushed": false})));
+    assert!(messages.contains(&json!({
+        "type": "controller-input",
+        "left": 0,
+        "right": 0,
+        "up": 2,
+        "down": 0
+    })));
+}
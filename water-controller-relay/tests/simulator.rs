@@ -0,0 +1,57 @@
+//! シミュレータ入力源の統合テスト
+//!
+//! シリアルデバイスなしで、シミュレータ → ブロードキャスト → WebSocket の経路を検証する
+
+mod common;
+
+use std::time::Duration;
+
+use water_controller_relay::source::{InputSourceConfig, simulator::SimulatorMode};
+
+#[tokio::test]
+async fn test_simulator_pattern_is_broadcast_to_websocket_client() {
+    // テスト項目: pattern モードのシミュレータ入力が button-input / controller-input とエッジイベントとして配信される
+    // given (前提条件):
+    let port = common::spawn_relay(InputSourceConfig::Simulator {
+        mode: SimulatorMode::Pattern,
+        interval: Duration::from_millis(10),
+        seed: None,
+    });
+    let mut stream = common::connect(port).await;
+
+    // when (操作):
+    let mut messages = Vec::new();
+    while messages
+        .iter()
+        .filter(|m: &&serde_json::Value| m["type"] == "controller-input")
+        .count()
+        < 20
+    {
+        messages.push(common::recv_json(&mut stream).await);
+    }
+
+    // then (期待する結果):
+    let button_count = messages
+        .iter()
+        .filter(|m| m["type"] == "button-input" && m["isPushed"].is_boolean())
+        .count();
+    let controller_levels: Vec<i64> = messages
+        .iter()
+        .filter(|m| m["type"] == "controller-input")
+        .flat_map(|m| {
+            ["left", "right", "up", "down"]
+                .into_iter()
+                .map(|key| m[key].as_i64().expect("level is an integer"))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(button_count, 20);
+    assert_eq!(controller_levels.len(), 20 * 4);
+    assert!(
+        controller_levels
+            .iter()
+            .all(|level| (0..=3).contains(level))
+    );
+    assert!(controller_levels.iter().any(|level| *level > 0));
+    assert!(messages.iter().any(|m| m["type"] == "level-changed"));
+}
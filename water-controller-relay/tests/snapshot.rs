@@ -0,0 +1,80 @@
+//! 接続直後の hello / state-snapshot メッセージの統合テスト
+
+mod common;
+
+use futures_util::SinkExt;
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message;
+use water_controller_relay::{source::InputSourceConfig, websocket::message::PROTOCOL_VERSION};
+
+/// 存在しないシリアルポートを入力源にして、入力のないリレーサーバを起動する
+fn spawn_idle_relay() -> u16 {
+    common::spawn_relay(InputSourceConfig::Serial {
+        port: "/dev/water-controller-relay-test-missing".to_string(),
+        baud: 115_200,
+    })
+}
+
+#[tokio::test]
+async fn test_hello_is_sent_before_snapshot() {
+    // テスト項目: 接続直後、state-snapshot より前にプロトコルのバージョンを含む hello を送信する
+    // given (前提条件):
+    let port = spawn_idle_relay();
+
+    // when (操作):
+    let mut stream = common::connect_raw(port, "").await;
+    let hello = common::recv_json(&mut stream).await;
+    let snapshot = common::recv_json(&mut stream).await;
+
+    // then (期待する結果):
+    assert_eq!(hello["type"], "hello");
+    assert_eq!(hello["protocolVersion"], PROTOCOL_VERSION);
+    assert_eq!(hello["serverVersion"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(hello["encoding"], "json");
+    assert_eq!(snapshot["type"], "state-snapshot");
+}
+
+#[tokio::test]
+async fn test_snapshot_is_idle_before_any_input() {
+    // テスト項目: 入力を一度も受信していない場合、入力なしの state-snapshot を送信する
+    // given (前提条件):
+    let port = spawn_idle_relay();
+
+    // when (操作):
+    let (_stream, snapshot) = common::connect_with_snapshot(port).await;
+
+    // then (期待する結果):
+    assert_eq!(
+        snapshot,
+        json!({"type": "state-snapshot", "isPushed": false, "left": 0, "right": 0, "up": 0, "down": 0})
+    );
+}
+
+#[tokio::test]
+async fn test_snapshot_contains_latest_input_for_new_client() {
+    // テスト項目: 後から接続したクライアントは、最後に配信された入力を state-snapshot として受信する
+    // given (前提条件):
+    let port = spawn_idle_relay();
+    let mut controller = common::connect(port).await;
+    controller
+        .send(Message::Text(
+            json!({"type": "simulate-input", "isPushed": true, "left": 3})
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+    // ack と配信された 4 メッセージ（入力状態 2 つとエッジイベント 2 つ）を受信し終えるまで待つ
+    for _ in 0..5 {
+        common::recv_json(&mut controller).await;
+    }
+
+    // when (操作):
+    let (_stream, snapshot) = common::connect_with_snapshot(port).await;
+
+    // then (期待する結果):
+    assert_eq!(
+        snapshot,
+        json!({"type": "state-snapshot", "isPushed": true, "left": 3, "right": 0, "up": 0, "down": 0})
+    );
+}
@@ -0,0 +1,118 @@
+//! ヘルスチェックと稼働状況の HTTP エンドポイントの統合テスト
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::Value;
+use water_controller_relay::source::{InputSourceConfig, simulator::SimulatorMode};
+
+#[tokio::test]
+async fn test_missing_serial_port_is_healthy_but_not_ready() {
+    // テスト項目: シリアルポートを開けない間も /healthz は 200 を返し、/readyz は 503 を返す
+    // given (前提条件):
+    let port = common::spawn_relay(InputSourceConfig::Serial {
+        port: "/dev/water-controller-relay-test-missing".to_string(),
+        baud: 115_200,
+    });
+
+    // when (操作):
+    let health = common::http_get(port, "/healthz").await;
+    let ready = common::http_get(port, "/readyz").await;
+    let (status_code, body) = common::http_get(port, "/status").await;
+    let status: Value = serde_json::from_str(&body).unwrap();
+
+    // then (期待する結果):
+    assert_eq!(health, (200, "ok".to_string()));
+    assert_eq!(ready, (503, "input source is not open".to_string()));
+    assert_eq!(status_code, 200);
+    assert_eq!(status["ready"], false);
+    assert_eq!(status["source"]["open"], false);
+    assert_eq!(status["source"]["lastLineAt"], Value::Null);
+    assert_eq!(status["clients"], 0);
+}
+
+#[tokio::test]
+async fn test_status_reports_lines_parse_errors_and_clients() {
+    // テスト項目: 行を受信している入力源は準備完了で、/status に行・パースエラーの数と接続数が含まれる
+    // given (前提条件): 正しい行とフィールド数の足りない行を繰り返し再生する
+    let path = std::env::temp_dir().join(format!(
+        "water-controller-relay-{}-status-test.capture",
+        std::process::id()
+    ));
+    std::fs::write(
+        &path,
+        "# water-controller-relay capture v1\n10\t1,1,1,1,0,0,0,0,0,0,0,0,0\n10\t1,1\n",
+    )
+    .unwrap();
+    let port = common::spawn_relay(InputSourceConfig::Replay {
+        path: path.clone(),
+        speed: 1.0,
+        looped: true,
+    });
+    let _stream = common::connect(port).await;
+
+    // when (操作):
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let ready = common::http_get(port, "/readyz").await;
+    let (_, body) = common::http_get(port, "/status").await;
+    let status: Value = serde_json::from_str(&body).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // then (期待する結果):
+    assert_eq!(ready, (200, "ready".to_string()));
+    assert_eq!(status["ready"], true);
+    assert_eq!(status["source"]["open"], true);
+    assert_eq!(status["source"]["port"], Value::Null);
+    assert!(status["source"]["lastLineAt"].is_i64());
+    assert!(status["source"]["lines"].as_u64().unwrap() >= 2);
+    assert!(
+        status["source"]["parseErrorsByKind"]["field-count"]
+            .as_u64()
+            .unwrap()
+            >= 1
+    );
+    assert_eq!(status["clients"], 1);
+    assert!(status["uptimeMs"].as_u64().unwrap() >= 200);
+}
+
+#[tokio::test]
+async fn test_metrics_exports_lines_broadcasts_and_latency() {
+    // テスト項目: /metrics に受信した行・配信したメッセージの数と、送信までの遅延のヒストグラムが含まれる
+    // given (前提条件):
+    let port = common::spawn_relay(InputSourceConfig::Simulator {
+        mode: SimulatorMode::Pattern,
+        interval: Duration::from_millis(10),
+        seed: None,
+    });
+    let _stream = common::connect(port).await;
+
+    // when (操作):
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let (status_code, body) = common::http_get(port, "/metrics").await;
+
+    // then (期待する結果):
+    let sample = |name: &str| {
+        body.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or_else(|| panic!("missing {name} in\n{body}"))
+    };
+    assert_eq!(status_code, 200);
+    assert!(sample("water_controller_lines_read_total") > 0);
+    assert!(sample("water_controller_messages_broadcast_total{type=\"controller-input\"}") > 0);
+    assert_eq!(sample("water_controller_connected_clients"), 1);
+    // シミュレータはテキストの行を使うので、バイナリフレームのカウンタは 0 のまま出力される
+    for name in [
+        "water_controller_frames_total",
+        "water_controller_corrupt_frames_total",
+        "water_controller_lost_frames_total",
+        "water_controller_frame_dropped_bytes_total",
+        "water_controller_frame_sequence_gaps_total",
+        "water_controller_frame_sequence_resets_total",
+        "water_controller_frame_invalid_payloads_total",
+    ] {
+        assert_eq!(sample(name), 0);
+    }
+    assert!(sample("water_controller_line_to_send_latency_seconds_count") > 0);
+}